@@ -0,0 +1,120 @@
+// 访问控制层 - 局域网模式下只接受公布的主机名, 防止 DNS 重绑定攻击通过恶意网页读取成绩
+use crate::{business::print_error, config::AppConfig};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response}
+};
+use serde::Serialize;
+use std::{
+    net::{IpAddr, Ipv4Addr, UdpSocket},
+    sync::Arc
+};
+
+// 本机访问时使用的主机名, 任何模式下都允许
+const LOOPBACK_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[::1]"];
+
+// 监听模式, 显示在每个页面顶部
+#[derive(Debug, Clone, Serialize)]
+pub struct BindingInfo {
+    pub lan: bool,
+    pub label: String,
+}
+
+// 访问策略
+pub struct AccessPolicy {
+    lan: bool,
+    hosts: Vec<String>, // 允许的主机名, 均为小写且不含端口
+}
+
+impl AccessPolicy {
+    pub fn new(config: &AppConfig) -> Self {
+        let mut hosts: Vec<String> = LOOPBACK_HOSTS.iter().map(|h| h.to_string()).collect();
+
+        if let Some(ip) = config.bind {
+            // 监听 0.0.0.0 时公布本机的局域网地址
+            let advertised = if ip.is_unspecified() { lan_address() } else { Some(ip) };
+            if let Some(ip) = advertised {
+                hosts.push(match ip {
+                    IpAddr::V4(v4) => v4.to_string(),
+                    IpAddr::V6(v6) => format!("[{}]", v6)
+                });
+            }
+        }
+        for host in &config.allowed_hosts {
+            if !hosts.contains(host) {
+                hosts.push(host.clone());
+            }
+        }
+
+        Self { lan: config.lan_mode(), hosts }
+    }
+
+    /// 公布给局域网设备的主机名, 不含本机地址
+    pub fn advertised_hosts(&self) -> &[String] {
+        &self.hosts[LOOPBACK_HOSTS.len()..]
+    }
+
+    /// 页面顶部显示的监听模式
+    pub fn binding_info(&self) -> BindingInfo {
+        let advertised = self.advertised_hosts();
+        let label = if self.lan && advertised.is_empty() {
+            "局域网模式：未能获取本机的局域网地址，请通过 --allowed-hosts 指定访问时使用的主机名".to_string()
+        } else if self.lan {
+            format!("局域网模式：同一网络中的设备可以通过 {} 访问本程序，请勿在公共网络中使用", advertised.join("、"))
+        } else {
+            "本机模式：只有本机可以访问本程序".to_string()
+        };
+
+        BindingInfo { lan: self.lan, label }
+    }
+
+    fn allows(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        self.hosts.contains(&host)
+    }
+}
+
+/// 检查 Host 与 Origin 头, 只在局域网模式下生效
+/// Host 不在允许列表中的请求一律拒绝; 会修改数据的请求还需要 Origin 为允许的主机(没有 Origin 的请求不是浏览器跨站发出的, 放行)
+pub async fn check_origin(State(policy): State<Arc<AccessPolicy>>, req: Request, next: Next) -> Response {
+    if !policy.lan {
+        return next.run(req).await;
+    }
+
+    let headers = req.headers();
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok()).map(host_name);
+    if !host.is_some_and(|h| policy.allows(h)) {
+        print_error(&format!("已拒绝主机名不在允许列表中的请求: {}", host.unwrap_or("(空)")));
+        return (StatusCode::FORBIDDEN, "主机名不在允许列表中").into_response();
+    }
+
+    let safe_method = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !safe_method && let Some(origin) = headers.get(header::ORIGIN) {
+        let origin_host = origin.to_str().ok().and_then(|o| o.split_once("://")).map(|(_, rest)| host_name(rest));
+        if !origin_host.is_some_and(|h| policy.allows(h)) {
+            print_error(&format!("已拒绝来源不在允许列表中的请求: {:?}", origin));
+            return (StatusCode::FORBIDDEN, "请求来源不在允许列表中").into_response();
+        }
+    }
+
+    next.run(req).await
+}
+
+/// 去掉端口, 保留 IPv6 地址的方括号
+fn host_name(value: &str) -> &str {
+    let value = value.trim().trim_end_matches('/');
+    match value.strip_prefix('[') {
+        Some(rest) => rest.find(']').map_or(value, |end| &value[..end + 2]),
+        None => value.split(':').next().unwrap_or(value)
+    }
+}
+
+/// 本机在局域网中的地址: 向外部地址"连接" UDP 套接字并读取本地地址, 不会实际发送数据
+fn lan_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(223, 5, 5, 5), 53)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
@@ -0,0 +1,158 @@
+// 备份层 - 注销或导入新数据覆盖当前结果之前, 自动把当前结果备份到数据目录, 可以一键恢复
+use crate::{business::DataOrigin, models::Course};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf}
+};
+
+const BACKUP_DIR: &str = "backups";
+const BACKUP_LIMIT: usize = 20;     // 每个所有者最多保留的备份数量, 超出时删除该所有者最旧的
+
+// 一份备份, 包含恢复结果页所需的全部数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    pub id: String,     // 以创建时间命名, 同时作为文件名
+    pub created_at: DateTime<Local>,
+    pub reason: String, // 触发备份的操作, 如"注销"
+    pub result_mode: String,    // login 或 file
+    pub courses: Vec<Course>,   // 全部课程
+    #[serde(default)]
+    pub official_gpa: Option<Decimal>,
+    #[serde(default)]
+    pub history_key: Option<String>,
+    #[serde(default)]
+    pub origin: Option<DataOrigin>, // 备份的数据最初的来源与获取时间
+    #[serde(default)]
+    pub owner: Option<String>,  // 备份的所有者: 登录查询的结果为账号的哈希值, 其他结果为创建备份的会话的随机标识
+}
+
+impl Backup {
+    /// 以当前时间创建备份
+    pub fn new(
+        reason: &str, result_mode: String, courses: Vec<Course>, official_gpa: Option<Decimal>, history_key: Option<String>, origin: Option<DataOrigin>,
+        owner: String
+    ) -> Self {
+        let created_at = Local::now();
+
+        Self {
+            id: created_at.format("%Y%m%d-%H%M%S-%3f").to_string(),
+            created_at,
+            reason: reason.to_string(),
+            result_mode,
+            courses,
+            official_gpa,
+            history_key,
+            origin,
+            owner: Some(owner),
+        }
+    }
+
+    /// 备份是否属于给定的所有者之一; 旧版本的备份没有记录所有者, 只有登录查询的结果能按账号找回
+    pub fn is_owned_by(&self, owners: &[String]) -> bool {
+        self.owner.as_ref().or(self.history_key.as_ref()).is_some_and(|owner| owners.contains(owner))
+    }
+}
+
+// 备份列表中显示的摘要
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupSummary {
+    pub id: String,
+    pub created_at: DateTime<Local>,
+    pub reason: String,
+    pub course_count: usize,
+}
+
+// 备份存储, 每份备份是一个单独的 JSON 文件
+pub struct BackupStore {
+    dir: PathBuf,
+}
+
+impl BackupStore {
+    pub fn new(data_dir: &Path) -> Self {
+        Self { dir: data_dir.join(BACKUP_DIR) }
+    }
+
+    /// 保存一份备份
+    pub fn create(&self, backup: &Backup) -> Result<()> {
+        fs::create_dir_all(&self.dir).with_context(|| format!("无法创建备份目录 {}", self.dir.display()))?;
+
+        let path = self.path_of(&backup.id);
+        fs::write(&path, serde_json::to_string(backup)?).with_context(|| format!("无法写入备份文件 {}", path.display()))?;
+
+        self.prune();
+
+        Ok(())
+    }
+
+    /// 列出属于给定所有者的备份, 最新的在前, 无法读取的文件会被跳过
+    pub fn list(&self, owners: &[String]) -> Vec<BackupSummary> {
+        let mut summaries: Vec<BackupSummary> = self.backup_ids()
+            .iter()
+            .filter_map(|id| self.load(id).ok())
+            .filter(|backup| backup.is_owned_by(owners))
+            .map(|backup| BackupSummary {
+                id: backup.id,
+                created_at: backup.created_at,
+                reason: backup.reason,
+                course_count: backup.courses.len(),
+            })
+            .collect();
+
+        summaries.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        summaries
+    }
+
+    /// 读取指定备份
+    pub fn load(&self, id: &str) -> Result<Backup> {
+        // ID 只能由数字和连字符组成, 防止通过 ID 读取数据目录以外的文件
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return Err(anyhow!("无效的备份 ID: {}", id));
+        }
+
+        let path = self.path_of(id);
+        let content = fs::read_to_string(&path).with_context(|| format!("找不到备份 {}", id))?;
+        serde_json::from_str(&content).with_context(|| format!("备份文件 {} 已损坏", path.display()))
+    }
+
+    fn path_of(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// 备份目录中所有备份的 ID, 按文件名(即创建时间)升序
+    fn backup_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        ids.sort();
+        ids
+    }
+
+    /// 按所有者分别删除超出数量上限的旧备份, 避免一个会话的备份挤掉其他人的备份
+    fn prune(&self) {
+        let mut by_owner: HashMap<Option<String>, Vec<String>> = HashMap::new();
+        for id in self.backup_ids() {
+            // 无法读取的文件不属于任何人, 与没有记录所有者的旧备份一起计数
+            let owner = self.load(&id).ok().and_then(|backup| backup.owner.or(backup.history_key));
+            by_owner.entry(owner).or_default().push(id);
+        }
+
+        // backup_ids 已按创建时间升序, 每组前面的即为最旧的
+        for ids in by_owner.values().filter(|ids| ids.len() > BACKUP_LIMIT) {
+            for id in &ids[..ids.len() - BACKUP_LIMIT] {
+                let _ = fs::remove_file(self.path_of(id));
+            }
+        }
+    }
+}
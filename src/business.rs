@@ -1,14 +1,24 @@
 // 业务逻辑层 - 处理获取到的数据
-use crate::models::Course;
+use crate::{models::{Course, CourseNature, Score, Semester, SkippedRow}, profile::{EvaluationRules, GradingTable, SchoolProfile}, settings::Benchmark};
 
 use base64::{engine::general_purpose::STANDARD, Engine as _};
-use chrono::Local;
-use rust_decimal::Decimal;
+use chrono::{DateTime, Local};
+use rust_decimal::{prelude::ToPrimitive, Decimal, RoundingStrategy};
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::{BTreeMap, HashMap}};
 
+pub const IMPACT_REFERENCE_GRADE: Decimal = dec!(4.33);   // 影响分析中"如果这门课取得优"对应的绩点
+pub const DEFAULT_GPA_STD_DEV: Decimal = dec!(0.5);  // 未填写班级标准差时假设的绩点标准差, 取自往届班级的经验值
+pub const DEFAULT_SCORE_STD_DEV: Decimal = dec!(7); // 未填写班级标准差时假设的平均分标准差
+pub const MAX_COURSE_CREDIT: Decimal = dec!(10);    // 单门课程学分的合理上限
+pub const MAX_TOTAL_CREDITS: Decimal = dec!(250);   // 总学分的合理上限, 本科一般在 160~180 学分左右
+pub const MAX_SEMESTER_CREDITS: Decimal = dec!(45);     // 单个学期学分的合理上限, 一般每学期 20~30 学分
+pub const EDIT_HISTORY_LIMIT: usize = 20;   // 撤销栈的最大深度
+// 内置的排除规则, 用户未修改时使用
 pub const PERMANENT_IGNORED_COURSES: &[&str] = &["入学教育"];
 pub const NATURE_EXCLUSIONS: &[&str] = &["公共选修课", "通识教育选修"];
+pub const COMPULSORY_NATURES: &[&str] = &["必修", "学科基础"];   // 课程性质包含这些关键字的课程计入仅必修课模式, 专业排名只计算这些课程
 pub const EXCLUDED_COURSES_KEYWORD: &[&str] = &[
     "体育", "职业生涯规划与就业指导", "大学生安全教育", "大学生心理健康教育",
     "形势与政策", "军事理论", "军事训练", "军事技能", "创新创业教育",
@@ -16,24 +26,352 @@ pub const EXCLUDED_COURSES_KEYWORD: &[&str] = &[
     "综合实训", "综合设计与展示", "职场体验", "实习", "见习",
     "名师大讲堂", "领导力", "系列讲座"
 ];
+const GRADE_NAMES: &[&str] = &["大一", "大二", "大三", "大四", "大五"];  // 按入学后的学年给学期命名
 
 // 绩点计算模式
-enum GPAMode {
+enum GPAMode<'a> {
     Default,    // 默认模式 - 排除部分课程 GPA
     All,         // 完全模式 - 计算所有课程 GPA
+    Compulsory,     // 必修模式 - 只计算必修课与学科基础课 GPA, 与专业排名的口径一致
+    Year(&'a str),  // 学年模式 - 只计算指定学年(如 2023-2024)的课程 GPA, 用于奖学金评定
+    Custom(&'a CourseFilter),   // 自定义模式 - 按前端提交的筛选条件计算 GPA
 }
 
 // 数据来源
 pub enum ResultSource {
     OfficialWebsite,    // 登录获取
+    SavedPage,  // 导入另存为的教务系统成绩页面或带课程性质的文件(如教务系统导出的成绩单), 与登录获取的数据一致
     InputFile,   // 导入文件计算
 }
 
+// 绩点、平均分的取整方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoundingMode {
+    HalfUp,     // 四舍五入
+    #[default]
+    Bankers,    // 四舍六入五成双, 程序一直以来的取整方式
+    Truncate,   // 直接截断, 部分教务处的做法
+}
+
+// 取整规则, 绩点、加权绩点与平均分都按该规则取整
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RoundingRule {
+    #[serde(default)]
+    pub mode: RoundingMode,
+    #[serde(default = "default_precision")]
+    pub precision: u32, // 保留的小数位数
+}
+
+pub const MAX_ROUNDING_PRECISION: u32 = 4;
+
+fn default_precision() -> u32 {
+    2
+}
+
+impl Default for RoundingRule {
+    fn default() -> Self {
+        Self { mode: RoundingMode::default(), precision: default_precision() }
+    }
+}
+
+impl RoundingRule {
+    pub fn round(&self, d: Decimal) -> Decimal {
+        let strategy = match self.mode {
+            RoundingMode::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            RoundingMode::Bankers => RoundingStrategy::MidpointNearestEven,
+            RoundingMode::Truncate => RoundingStrategy::ToZero
+        };
+        d.round_dp_with_strategy(self.precision, strategy)
+    }
+
+    /// 计算依据中的取整说明, 如"学分加权平均后四舍五入保留 2 位小数"
+    pub fn label(&self) -> String {
+        let mode = match self.mode {
+            RoundingMode::HalfUp => "四舍五入",
+            RoundingMode::Bankers => "四舍六入五成双",
+            RoundingMode::Truncate => "截断"
+        };
+        format!("学分加权平均后{}保留 {} 位小数", mode, self.precision)
+    }
+}
+
+// 同一课程有多条成绩(补考、重修)时保留哪一条
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetakePolicy {
+    #[default]
+    Highest,    // 绩点最高的一次
+    Latest,     // 最近的一次
+    First,      // 第一次
+    Average,    // 各次成绩的平均
+}
+
+pub const RETAKE_POLICIES: &[RetakePolicy] = &[RetakePolicy::Highest, RetakePolicy::Latest, RetakePolicy::First, RetakePolicy::Average];
+
+impl RetakePolicy {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RetakePolicy::Highest => "取绩点最高的一次",
+            RetakePolicy::Latest => "取最近的一次",
+            RetakePolicy::First => "取第一次",
+            RetakePolicy::Average => "取各次的平均"
+        }
+    }
+}
+
+// 缓考、免修、违纪等特殊成绩的处理方式, 在换算表中按成绩文字逐个设置
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpecialScoreRule {
+    Pass,       // 视为通过, 学分计入已获得学分, 但不计入绩点, 如免修
+    Exclude,    // 完全不计入, 如缓考, 之后参加考试的成绩另有记录
+    Zero,       // 按 0 分、0 绩点计入, 如违纪、作弊
+}
+
+impl SpecialScoreRule {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SpecialScoreRule::Pass => "视为通过, 不计绩点",
+            SpecialScoreRule::Exclude => "不计入",
+            SpecialScoreRule::Zero => "按 0 分计入"
+        }
+    }
+}
+
+// 因特殊成绩未计入绩点的课程, 在结果页单独列出
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UncountedCourse {
+    pub course: Course,
+    pub rule: SpecialScoreRule,
+    pub label: String,  // 处理方式的说明
+}
+
+// 结果数据的来源, 获取新数据时记录, 编辑课程或调整规则时不变
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataOrigin {
+    pub source: String,     // 如"教务系统登录查询"、"导入文件 成绩.xlsx"
+    pub fetched_at: DateTime<Local>,    // 数据的获取时间
+}
+
+impl DataOrigin {
+    /// 以当前时间记录数据来源
+    pub fn now(source: impl Into<String>) -> Self {
+        Self { source: source.into(), fetched_at: Local::now() }
+    }
+}
+
+// 一个绩点的计算依据, 显示在结果页脚注并写入导出文件, 以便日后按相同条件复现
+#[derive(Debug, Clone, Serialize)]
+pub struct Provenance {
+    pub source: String,
+    pub fetched_at: DateTime<Local>,
+    pub edited: bool,   // 课程是否经过手动修改或删除
+    pub profile: String,    // 学校规则名称
+    pub mode: String,   // 计算模式, 如"默认(排除部分课程)"
+    pub scale: &'static str,    // 绩点换算标准
+    pub rounding: String,
+    pub dedup: &'static str,    // 重复课程的处理方式
+}
+
+impl Provenance {
+    pub fn new(origin: DataOrigin, edited: bool, profile: &SchoolProfile, mode: &str, scale: GradeScale) -> Self {
+        Self {
+            source: origin.source,
+            fetched_at: origin.fetched_at,
+            edited,
+            profile: profile.name.clone(),
+            mode: mode.to_string(),
+            scale: scale.label(),
+            rounding: profile.grading.rounding.label(),
+            dedup: profile.retake_policy.label(),
+        }
+    }
+
+    /// 一行文字的脚注, 如"数据来源: 教务系统登录查询(2024-01-31 13:45 获取); 学校规则: ..."
+    pub fn footnote(&self) -> String {
+        let edited = if self.edited { ", 课程经过手动修改" } else { "" };
+        format!(
+            "数据来源: {}({} 获取{}); 学校规则: {}; 计算模式: {}; 换算标准: {}; 取整: {}; 重复课程: {}",
+            self.source, self.fetched_at.format("%Y-%m-%d %H:%M"), edited, self.profile, self.mode, self.scale, self.rounding, self.dedup
+        )
+    }
+}
+
+// 辅修成绩的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MinorMode {
+    #[default]
+    Ignore,     // 不查询辅修成绩
+    Merge,      // 与主修课程合并计算
+    Separate,   // 主修与辅修分别计算绩点
+}
+
 // 绩点计算信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GPAResult {
     pub gpa: Decimal,
     pub courses: Vec<Course>,
+    #[serde(default)]
+    pub semesters: Vec<SemesterGPA>,    // 各学期的绩点, 按时间先后排列
+    #[serde(default)]
+    pub averages: ScoreAverages,    // 百分制平均分, 部分保研排名以平均分而非绩点排序
+}
+
+impl GPAResult {
+    /// 由课程列表及其平均绩点得出学期绩点与平均分
+    pub fn new(gpa: Decimal, courses: Vec<Course>, profile: &SchoolProfile) -> Self {
+        Self { gpa, semesters: semester_gpas(&courses, profile), averages: ScoreAverages::from_courses(&courses, &profile.grading), courses }
+    }
+}
+
+// 百分制平均分, 等级制成绩按换算表中的 level_scores 计, 无法识别的成绩不计入
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScoreAverages {
+    pub weighted: Option<Decimal>,  // 加权平均分 = Σ(成绩 × 学分) / Σ学分
+    pub arithmetic: Option<Decimal>,    // 算术平均分 = Σ成绩 / 课程数
+}
+
+impl ScoreAverages {
+    pub fn from_courses(courses: &[Course], grading: &GradingTable) -> Self {
+        let scored: Vec<(Decimal, Decimal)> = courses
+            .iter()
+            .filter_map(|c| Some((score_percentage(&c.score, grading)?, c.credit)))
+            .collect();
+        if scored.is_empty() {
+            return Self::default();
+        }
+
+        let total_credits: Decimal = scored.iter().map(|(_, credit)| credit).sum();
+        let weighted = (total_credits > Decimal::ZERO)
+            .then(|| grading.rounding.round(scored.iter().map(|(score, credit)| score * credit).sum::<Decimal>() / total_credits));
+        let arithmetic = grading.rounding.round(scored.iter().map(|(score, _)| score).sum::<Decimal>() / Decimal::from(scored.len()));
+
+        Self { weighted, arithmetic: Some(arithmetic) }
+    }
+}
+
+// 单个学期的绩点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemesterGPA {
+    pub semester: String,   // 教务系统中的学期, 如 2023-2024-1
+    pub label: String,      // 年级与学期, 如"大一上", 无法识别时与 semester 相同
+    pub gpa: Decimal,
+    pub credits: Decimal,
+    pub course_count: usize,
+}
+
+// 绩点走势中的一个学期, 累计绩点包含该学期及之前的全部课程
+#[derive(Debug, Clone, Serialize)]
+pub struct TrendPoint {
+    pub semester: String,
+    pub label: String,
+    pub term_gpa: Decimal,  // 该学期单独计算的绩点
+    pub cumulative_gpa: Decimal,    // 截至该学期的累计绩点
+    pub cumulative_credits: Decimal,
+}
+
+// 绩点换算标准, 同一组课程可以按不同标准换算后对照
+// 除本校标准外, 其他标准都先把成绩换算为百分制再计算
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum GradeScale {
+    #[default]
+    #[serde(rename = "school")]
+    School,     // 本校标准, 即学校规则中的换算表
+    #[serde(rename = "4.0")]
+    Standard4,  // 标准 4.0 分制: 90 分以上为 4, 80~89 为 3, 70~79 为 2, 60~69 为 1
+    #[serde(rename = "5.0")]
+    Five,       // 5 分制: 60 分以上为 (成绩 - 50) / 10, 不及格为 0
+    #[serde(rename = "percentage")]
+    Percentage, // 百分制: 学分加权平均分
+    #[serde(rename = "wes")]
+    Wes,        // WES iGPA: 先换算为字母等级, A 为 4, B 为 3, C 为 2, F 为 0, 用于留学申请
+}
+
+pub const GRADE_SCALES: &[GradeScale] = &[GradeScale::School, GradeScale::Standard4, GradeScale::Five, GradeScale::Percentage, GradeScale::Wes];
+
+// WES 对中国百分制成绩的字母等级划分: (最低分, 等级, 绩点), 从高到低
+const WES_BANDS: &[(Decimal, &str, Decimal)] = &[
+    (dec!(85), "A", dec!(4)), (dec!(75), "B", dec!(3)), (dec!(60), "C", dec!(2)), (dec!(0), "F", dec!(0)),
+];
+// WES 对五级制成绩的字母等级, 与百分制区间的代表分数不完全一致(如"中"为 C), 单独列出
+const WES_LEVELS: &[(&str, &str)] = &[
+    ("优", "A"), ("良", "B"), ("中", "C"), ("及格", "C"), ("合格", "C"), ("不及格", "F"), ("不合格", "F"),
+];
+const WES_MAX_GPA: Decimal = dec!(4);   // WES iGPA 的上限
+// 绩点对应的字母等级: (最低绩点, 等级), 从高到低, 与本校换算表的 12 档一一对应; 等级制的"及格"(1.0)计为 D
+const LETTER_GRADES: &[(Decimal, &str)] = &[
+    (dec!(4.67), "A+"), (dec!(4.33), "A"), (dec!(4.00), "A-"), (dec!(3.67), "B+"), (dec!(3.33), "B"), (dec!(3.00), "B-"),
+    (dec!(2.67), "C+"), (dec!(2.33), "C"), (dec!(2.00), "C-"), (dec!(1.67), "D+"), (dec!(1.00), "D"), (dec!(0), "F"),
+];
+// 成绩分布的百分制分档, 从高到低, 成绩不低于 min 时计入该档
+const SCORE_BANDS: &[(Decimal, &str)] = &[
+    (dec!(90), "90-100"), (dec!(80), "80-89"), (dec!(70), "70-79"), (dec!(60), "60-69"), (dec!(0), "60 以下"),
+];
+
+impl GradeScale {
+    pub fn label(&self) -> &'static str {
+        match self {
+            GradeScale::School => "本校标准",
+            GradeScale::Standard4 => "标准 4.0",
+            GradeScale::Five => "5 分制",
+            GradeScale::Percentage => "百分制",
+            GradeScale::Wes => "WES"
+        }
+    }
+
+    /// 按该标准把成绩换算为绩点, 无法识别的成绩返回 None
+    pub fn grade(&self, score: &Score, grading: &GradingTable) -> Option<Decimal> {
+        match self {
+            GradeScale::School => return score_trans_grade(score, grading),
+            GradeScale::Wes => {
+                let letter = wes_letter(score, grading)?;
+                let &(_, _, grade) = WES_BANDS.iter().find(|(_, l, _)| *l == letter)?;
+                return Some(grade.min(WES_MAX_GPA));
+            }
+            _ => {}
+        }
+
+        let percentage = score_percentage(score, grading)?;
+        let grade = match self {
+            GradeScale::Standard4 => match percentage {
+                p if p >= dec!(90) => dec!(4),
+                p if p >= dec!(80) => dec!(3),
+                p if p >= dec!(70) => dec!(2),
+                p if p >= dec!(60) => dec!(1),
+                _ => Decimal::ZERO
+            },
+            GradeScale::Five if percentage >= dec!(60) => (percentage - dec!(50)) / dec!(10),
+            GradeScale::Five => Decimal::ZERO,
+            GradeScale::Percentage | GradeScale::School | GradeScale::Wes => percentage
+        };
+
+        Some(grading.rounding.round(grade))
+    }
+}
+
+// 成绩分布中的一档
+#[derive(Debug, Clone, Serialize)]
+pub struct BandCount {
+    pub label: &'static str,
+    pub count: usize,
+}
+
+// 成绩分布, 供前端绘制直方图
+#[derive(Debug, Clone, Serialize)]
+pub struct GradeDistribution {
+    pub score_bands: Vec<BandCount>,    // 按百分制分档的课程数, 等级制成绩按 level_scores 换算
+    pub letters: Vec<BandCount>,    // 按 WES 字母等级的课程数
+    pub unrecognized: usize,    // 成绩无法识别、未计入分布的课程数
+}
+
+// 某一换算标准下的平均绩点, 用于并列对照
+#[derive(Debug, Clone, Serialize)]
+pub struct ScaleGPA {
+    pub scale: GradeScale,
+    pub label: &'static str,
+    pub gpa: Decimal,
 }
 
 // 不同模式的绩点计算信息
@@ -41,6 +379,266 @@ pub struct GPAResult {
 pub struct ProcessedGPAResults {
     pub default: Option<GPAResult>, // 可能不存在
     pub all: GPAResult,  // 必定存在
+    pub compulsory: GPAResult,  // 仅必修课, 不含课程性质的数据中为空
+    pub credit_warnings: Vec<String>,   // 学分异常提示, 不影响计算
+    pub credit_stats: CreditStats,  // 学分完成情况, 按全部课程统计
+    #[serde(default)]
+    pub skipped_rows: Vec<SkippedRow>,  // 解析时无法识别而未计入的行, 由解析数据的调用方填入
+    #[serde(default)]
+    pub uncounted: Vec<UncountedCourse>,    // 缓考、免修等特殊成绩而未计入绩点的课程
+}
+
+// 某一课程性质已获得的学分
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NatureCredits {
+    pub nature: String,
+    pub credits: Decimal,
+}
+
+// 学分完成情况, 绩点为 0 的课程视为未通过, 不计入已获得学分
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreditStats {
+    pub earned: Decimal,    // 已获得的总学分
+    pub failed: Decimal,    // 未通过课程的学分
+    pub failed_count: usize,
+    pub by_nature: Vec<NatureCredits>,  // 各课程性质已获得的学分, 按学分从多到少排列
+}
+
+impl CreditStats {
+    /// exempt 为视为通过但不计绩点的课程(如免修), 学分同样计入已获得学分
+    pub fn from_courses(courses: &[Course], exempt: &[&Course]) -> Self {
+        let (mut passed, failed): (Vec<&Course>, Vec<&Course>) = courses.iter().partition(|c| !c.grade.is_zero());
+        passed.extend(exempt);
+
+        let mut natures: BTreeMap<&str, Decimal> = BTreeMap::new();
+        for course in &passed {
+            let nature = if course.nature.is_empty() { "未注明" } else { course.nature.as_str() };
+            *natures.entry(nature).or_default() += course.credit;
+        }
+        let mut by_nature: Vec<NatureCredits> = natures
+            .into_iter()
+            .map(|(nature, credits)| NatureCredits { nature: nature.to_string(), credits })
+            .collect();
+        by_nature.sort_by_key(|n| Reverse(n.credits));
+
+        Self {
+            earned: passed.iter().map(|c| c.credit).sum(),
+            failed: failed.iter().map(|c| c.credit).sum(),
+            failed_count: failed.len(),
+            by_nature,
+        }
+    }
+}
+
+
+
+// 与目标绩点的对比结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkComparison {
+    pub benchmark: Decimal,     // 目标绩点
+    pub delta: Decimal,     // 当前绩点 - 目标绩点, 负数表示还差多少
+    pub reached: bool,      // 是否已经达到
+    pub required_gpa: Option<Decimal>,  // 剩余学期平均需要达到的绩点, 没有剩余学期时为 None
+    pub achievable: bool,   // 在剩余学期内是否还有可能达到
+}
+
+// 目标绩点规划的结论
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanStatus {
+    Secured,    // 剩余课程即使绩点全为 0 也能达到目标
+    Achievable, // 剩余课程平均绩点达到 required_gpa 即可
+    Unreachable,    // 剩余课程全部取得最高绩点也无法达到
+}
+
+// 目标绩点规划结果
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetPlan {
+    pub target: Decimal,
+    pub current_gpa: Decimal,
+    pub earned_credits: Decimal,    // 已计入绩点的学分
+    pub remaining_credits: Decimal,
+    pub required_gpa: Option<Decimal>,  // 剩余课程至少需要达到的平均绩点, 没有剩余学分时为 None
+    pub status: PlanStatus,
+}
+
+
+// 与教务系统官方绩点的对照结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OfficialComparison {
+    pub official: Decimal,  // 教务系统给出的平均学分绩点
+    pub ours: Decimal,      // 本工具 Default 模式的绩点
+    pub delta: Decimal,     // 本工具 - 官方
+    pub consistent: bool,   // 差值在舍入误差范围内视为一致
+}
+
+// 综测计算时用户手动填写的内容
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EvaluationInput {
+    #[serde(default)]
+    pub semesters: Vec<String>,     // 参与计算的学期, 为空则使用全部学期
+    #[serde(default)]
+    pub components: Vec<Decimal>,   // 各手填项得分, 顺序与规则中的 components 一致
+    #[serde(default)]
+    pub bonuses: Vec<EvaluationBonus>,
+}
+
+// 加分项, 如竞赛获奖、志愿服务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationBonus {
+    pub name: String,
+    pub points: Decimal,
+}
+
+// 综测中一项的得分明细
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationPart {
+    pub name: String,
+    pub score: Decimal,     // 原始得分
+    pub weighted: Decimal,  // 按占比折算后的得分
+}
+
+// 综测计算结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationResult {
+    pub gpa: Decimal,   // 所选学期的平均绩点
+    pub parts: Vec<EvaluationPart>, // 智育与各手填项
+    pub bonus: Decimal,     // 计入总分的加分, 已按上限截断
+    pub bonus_capped: bool, // 加分是否超过上限
+    pub total: Decimal,
+}
+
+// 自定义筛选条件, 各条件同时满足的课程才计入, 列表为空或未设置的条件不限制
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CourseFilter {
+    pub include_natures: Vec<String>,   // 只计入这些课程性质的课程
+    pub exclude_natures: Vec<String>,   // 不计入这些课程性质的课程
+    pub include_keywords: Vec<String>,  // 只计入名称包含其中任一关键字的课程
+    pub exclude_keywords: Vec<String>,  // 不计入名称包含其中任一关键字的课程
+    pub semester_from: Option<String>,  // 起始学期(含), 如 2023-2024-1
+    pub semester_to: Option<String>,    // 结束学期(含)
+    pub min_credit: Option<Decimal>,    // 不计入学分低于该值的课程
+}
+
+impl CourseFilter {
+    /// 检查学期范围的格式, 其余条件不需要检查
+    pub fn validate(&self) -> Result<(), String> {
+        let from = self.semester_from.as_deref().map(str::parse::<Semester>).transpose()?;
+        let to = self.semester_to.as_deref().map(str::parse::<Semester>).transpose()?;
+        if let (Some(from), Some(to)) = (from, to) && from > to {
+            return Err("起始学期不能晚于结束学期".to_string());
+        }
+
+        Ok(())
+    }
+
+    fn matches(&self, course: &Course) -> bool {
+        let semester = course.parsed_semester();
+        // 设置了学期范围时, 没有学期信息的课程不计入
+        let in_range = |bound: &Option<String>, after: bool| match (bound.as_deref().and_then(|b| b.parse::<Semester>().ok()), semester) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(bound), Some(semester)) => if after { semester >= bound } else { semester <= bound }
+        };
+
+        (self.include_natures.is_empty() || course.nature.is_any_of(&self.include_natures))
+            && !course.nature.is_any_of(&self.exclude_natures)
+            && (self.include_keywords.is_empty() || self.include_keywords.iter().any(|k| course.name.contains(k.as_str())))
+            && !self.exclude_keywords.iter().any(|k| course.name.contains(k.as_str()))
+            && in_range(&self.semester_from, true)
+            && in_range(&self.semester_to, false)
+            && self.min_credit.is_none_or(|min| course.credit >= min)
+    }
+}
+
+// 模拟计算中假设要修读的一门课程
+#[derive(Debug, Clone, Deserialize)]
+pub struct HypotheticalCourse {
+    pub name: String,
+    pub credit: Decimal,
+    pub score: String,  // 预期成绩, 百分制或等级制
+}
+
+// 模拟计算结果, 只用于展示, 不会写入 Session
+#[derive(Debug, Clone, Serialize)]
+pub struct WhatIfResult {
+    pub current_gpa: Decimal,
+    pub simulated_gpa: Decimal, // 加入假设课程后的绩点
+    pub delta: Decimal,     // 模拟绩点 - 当前绩点
+    pub courses: Vec<Course>,   // 计入模拟的假设课程, 已换算绩点
+    pub excluded: Vec<String>,  // 按当前模式的排除规则不计入的假设课程
+}
+
+// 单门课程对平均绩点的影响
+#[derive(Debug, Clone, Serialize)]
+pub struct CourseImpact {
+    pub name: String,
+    pub semester: String,
+    pub credit: Decimal,
+    pub grade: Decimal,
+    pub removed_delta: Option<Decimal>,    // 不计入这门课时的绩点 - 当前绩点, 正数表示这门课拉低了绩点; 只有这一门课时为 None
+    pub reference_delta: Decimal,   // 这门课取得 IMPACT_REFERENCE_GRADE 时的绩点 - 当前绩点
+}
+
+// 排名估算的比较依据
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RankingBasis {
+    #[default]
+    Gpa,    // 平均绩点
+    Score,  // 加权平均分
+}
+
+// 排名估算结果, 假设班级成绩服从正态分布, 只是粗略的参考
+#[derive(Debug, Clone, Serialize)]
+pub struct RankingEstimate {
+    pub basis: RankingBasis,
+    pub value: Decimal, // 本人的绩点或加权平均分
+    pub class_average: Decimal,
+    pub std_dev: Decimal,   // 计算使用的标准差, 未填写时为默认值
+    pub top_percent: Decimal,   // 约位于前百分之几, 限制在 1 ~ 99 之间
+    pub rank: Option<u32>,  // 填写了班级人数时估算的名次
+}
+
+// 课程编辑历史, 保存在 Session 中用于撤销/重做
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EditHistory {
+    undo: Vec<Vec<Course>>, // 每次编辑前的课程列表
+    redo: Vec<Vec<Course>>, // 被撤销的课程列表
+}
+
+impl EditHistory {
+    /// 记录一次编辑前的状态, 新的编辑会清空重做栈
+    pub fn record(&mut self, before: Vec<Course>) {
+        self.undo.push(before);
+        if self.undo.len() > EDIT_HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// 撤销, 返回上一次的课程列表
+    pub fn undo(&mut self, current: Vec<Course>) -> Option<Vec<Course>> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current);
+        Some(previous)
+    }
+
+    /// 重做, 返回被撤销的课程列表
+    pub fn redo(&mut self, current: Vec<Course>) -> Option<Vec<Course>> {
+        let next = self.redo.pop()?;
+        self.undo.push(current);
+        Some(next)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
 }
 
 
@@ -50,55 +648,162 @@ pub fn b64_encode(text: &str) -> String {
     STANDARD.encode(text)
 }
 
-/// 成绩转换绩点
-pub fn score_trans_grade(score: &str) -> Option<Decimal> {
+/// 按学校规则中的换算表把成绩转换为绩点
+/// 按 0 分计入的特殊成绩(如作弊)换算为 0, 视为通过或不计入的特殊成绩(如免修、缓考)没有绩点, 返回 None
+pub fn score_trans_grade(score: &Score, grading: &GradingTable) -> Option<Decimal> {
     // 返回值有两个状态, Some 表示有值返回, 括号里面是值, None 表示无值
-    // 等级制的判断更简短, 先做等级制判断
-    match score {
-        "不及格" | "不合格" => return Some(Decimal::ZERO),
-        "及格" | "合格" => return Some(Decimal::ONE),
-        "中" => return Some(dec!(2.33)),
-        "良" => return Some(dec!(3.33)),
-        "优" => return Some(dec!(4.33)),
-        _ => {} // 默认值, 空括号表示不处理, 执行下面的代码
-    }
-
-    // parse::<Decimal> 表示转换成 Decimal 类型
-    // Ok 表示成功, 箭头后面表示要赋的值
-    // Err 表示失败, 返回空值 None
-    let score_val = match score.parse::<Decimal>() {
-        Ok(val) => val,
-        Err(_) => return None
+    // 文字成绩先查等级制, 再查特殊成绩的处理方式
+    let score_val = match score {
+        Score::Numeric(value) => *value,
+        Score::Level(text) | Score::Special(text) => {
+            if let Some(grade) = grading.levels.get(text) {
+                return Some(*grade);
+            }
+            let rule = grading.special_rule(score)?;
+            return (rule == SpecialScoreRule::Zero).then_some(Decimal::ZERO);
+        }
     };
+    if score_val.is_sign_negative() || score_val > grading.max_score {
+        return None;
+    }
 
-    // match 从上到下匹配, s 表示一个变量(可以自己取别的名字), 后面if补充条件
-    // 性能比 if-else 语句略好
-    let grade = match score_val {
-        s if s < dec!(60) => Decimal::ZERO,
-        s if s < dec!(64) => dec!(1.33),
-        s if s < dec!(67) => dec!(1.67),
-        s if s < dec!(70) => dec!(2.00),
-        s if s < dec!(74) => dec!(2.33),
-        s if s < dec!(77) => dec!(2.67),
-        s if s < dec!(80) => dec!(3.00),
-        s if s < dec!(83) => dec!(3.33),
-        s if s < dec!(87) => dec!(3.67),
-        s if s < dec!(90) => dec!(4.00),
-        s if s < dec!(95) => dec!(4.33),
-        s if s <= dec!(100) => dec!(4.67),
-        _ => return None
+    // 取下限不超过成绩的最高一档
+    grading.ranges
+        .iter()
+        .filter(|range| range.min <= score_val)
+        .max_by_key(|range| range.min)
+        .map(|range| range.grade)
+}
+
+/// 解析成绩页面或文件时使用: 视为通过或不计入的特殊成绩也保留该课程, 绩点先记为 0, 计算时再单独列出
+/// 其余无法识别的成绩返回 None
+pub fn parsed_grade(score: &Score, grading: &GradingTable) -> Option<Decimal> {
+    score_trans_grade(score, grading).or_else(|| grading.special_rule(score).map(|_| Decimal::ZERO))
+}
+
+/// 按 WES 的规则把成绩换算为字母等级 A/B/C/F, 无法识别的成绩返回 None
+pub fn wes_letter(score: &Score, grading: &GradingTable) -> Option<&'static str> {
+    if let Some(&(_, letter)) = WES_LEVELS.iter().find(|(level, _)| score.text() == Some(*level)) {
+        return Some(letter);
+    }
+
+    let percentage = score_percentage(score, grading)?;
+    WES_BANDS.iter().find(|(min, _, _)| percentage >= *min).map(|&(_, letter, _)| letter)
+}
+
+/// 各课程的 WES 字母等级, 与课程列表一一对应, 用于结果页的 WES 列; 无法识别的成绩显示为 "-"
+pub fn wes_letters(courses: &[Course], grading: &GradingTable) -> Vec<&'static str> {
+    courses.iter().map(|course| wes_letter(&course.score, grading).unwrap_or("-")).collect()
+}
+
+/// 按绩点得出字母等级 A+/A/A-…F, 许多申请表要求填写字母等级而不是百分制成绩
+pub fn letter_grade(grade: Decimal) -> &'static str {
+    LETTER_GRADES.iter().find(|(min, _)| grade >= *min).map_or("F", |&(_, letter)| letter)
+}
+
+/// 各课程的字母等级, 与课程列表一一对应, 用于结果页与导出文件的等级列
+pub fn letter_grades(courses: &[Course]) -> Vec<&'static str> {
+    courses.iter().map(|course| letter_grade(course.grade)).collect()
+}
+
+/// 统计各分数段与各字母等级的课程数, 各档按从高到低排列, 没有课程的档位计为 0
+pub fn grade_distribution(courses: &[Course], grading: &GradingTable) -> GradeDistribution {
+    let mut score_bands: Vec<BandCount> = SCORE_BANDS.iter().map(|&(_, label)| BandCount { label, count: 0 }).collect();
+    let mut letters: Vec<BandCount> = WES_BANDS.iter().map(|&(_, label, _)| BandCount { label, count: 0 }).collect();
+    let mut unrecognized = 0;
+
+    for course in courses {
+        match score_percentage(&course.score, grading) {
+            Some(percentage) => {
+                let index = SCORE_BANDS.iter().position(|(min, _)| percentage >= *min).unwrap_or(SCORE_BANDS.len() - 1);
+                score_bands[index].count += 1;
+            }
+            None => unrecognized += 1
+        }
+        if let Some(letter) = wes_letter(&course.score, grading)
+            && let Some(band) = letters.iter_mut().find(|b| b.label == letter) {
+            band.count += 1;
+        }
+    }
+
+    GradeDistribution { score_bands, letters, unrecognized }
+}
+
+/// 把成绩换算为百分制, 满分不是 100 分时按比例换算; 等级制成绩按换算表中的 level_scores 计
+fn score_percentage(score: &Score, grading: &GradingTable) -> Option<Decimal> {
+    let Score::Numeric(value) = *score else {
+        if grading.special_rule(score) == Some(SpecialScoreRule::Zero) {
+            return Some(Decimal::ZERO);
+        }
+        return score.text().and_then(|text| grading.level_scores.get(text)).copied();
     };
 
-    // 到最后的必定是 grade 有值, 因为没值的在上面被返回 None 了
-    // 函数末尾省略 return
-    Some(grade)
+    let max_score = grading.max_score;
+    if value.is_sign_negative() || value > max_score || max_score.is_zero() {
+        return None;
+    }
+
+    Some(value * dec!(100) / max_score)
+}
+
+/// 按指定标准重新换算结果中各课程的绩点, 无法换算的课程不计入
+pub fn rescale_result(result: &GPAResult, scale: GradeScale, profile: &SchoolProfile) -> GPAResult {
+    if scale == GradeScale::School {
+        return result.clone();
+    }
+
+    let policy = profile.policy();
+    let courses: Vec<Course> = result.courses
+        .iter()
+        .filter_map(|course| {
+            let grade = scale.grade(&course.score, &profile.grading)?;
+            Some(Course { grade, credit_gpa: policy.round(grade * course.credit, profile), ..course.clone() })
+        })
+        .collect();
+
+    GPAResult { gpa: weighted_gpa(&courses, profile), semesters: semester_gpas(&courses, profile), averages: result.averages.clone(), courses }
+}
+
+/// 同一组课程在各换算标准下的平均绩点
+pub fn scale_comparison(result: &GPAResult, profile: &SchoolProfile) -> Vec<ScaleGPA> {
+    GRADE_SCALES
+        .iter()
+        .map(|&scale| ScaleGPA { scale, label: scale.label(), gpa: rescale_result(result, scale, profile).gpa })
+        .collect()
+}
+
+/// 学分加权平均绩点, 没有学分时为 0
+fn weighted_gpa(courses: &[Course], profile: &SchoolProfile) -> Decimal {
+    credit_weighted_average(courses).map_or(Decimal::ZERO, |avg| profile.policy().round(avg, profile))
+}
+
+/// 未取整的学分加权平均绩点, 没有学分时为 None
+fn credit_weighted_average(courses: &[Course]) -> Option<Decimal> {
+    let total_credits: Decimal = courses.iter().map(|c| c.credit).sum();
+    let total_cg: Decimal = courses.iter().map(|c| c.credit_gpa).sum();
+
+    (total_credits > Decimal::ZERO).then(|| total_cg / total_credits)
 }
 
-/// 保留小数点后2位
+/// 根据成绩和学分重新计算绩点与加权绩点, 成绩无法识别时返回 None
+/// 改为免修、缓考等特殊成绩时保留该课程, 重新计算时移入未计入的课程
+pub fn regrade_course(mut course: Course, grading: &GradingTable) -> Option<Course> {
+    course.grade = parsed_grade(&course.score, grading)?;
+    course.credit_gpa = grading.rounding.round(course.grade * course.credit);
+
+    Some(course)
+}
+
+/// 保留小数点后2位, 用于班级统计等与学校取整规则无关的数值
 pub fn round_2decimal(d: Decimal) -> Decimal {
     d.round_dp(2)
 }
 
+/// 向上保留小数点后2位, 用于"至少需要达到"的绩点, 达到该值一定满足要求
+fn round_up_2decimal(d: Decimal) -> Decimal {
+    d.round_dp_with_strategy(2, RoundingStrategy::ToPositiveInfinity)
+}
+
 /// 提供当前时间
 fn current_time() -> String {
     Local::now().format("%Y-%m-%d %H:%M:%S%.6f").to_string()
@@ -106,56 +811,625 @@ fn current_time() -> String {
 
 
 /// 计算GPA
-fn calculate_gpa_from_list(courses: &[Course], mode: GPAMode) -> (Decimal, Vec<Course>) {
+/// exclude_failed 为 true 时不计入绩点为 0 的课程(未通过的考试), 部分排名规则只看通过的成绩
+fn calculate_gpa_from_list(courses: &[Course], mode: GPAMode, profile: &SchoolProfile, exclude_failed: bool) -> (Decimal, Vec<Course>) {
+    let policy = profile.policy();
     let courses: Vec<Course> = courses
         .iter()
-        .filter(|c| !PERMANENT_IGNORED_COURSES.contains(&c.name.as_str()))
+        .filter(|c| !policy.is_ignored(c, profile))
+        .filter(|c| !exclude_failed || !c.grade.is_zero())
         .cloned()
         .collect();
 
     let courses_to_use: Vec<Course> = match mode {
         GPAMode::Default => {
             courses.iter()
-                .filter(|c|
-                    !EXCLUDED_COURSES_KEYWORD.iter().any(|k| c.name.contains(k))
-                        && !NATURE_EXCLUSIONS.contains(&c.nature.as_str())
-                ).cloned().collect()
+                .filter(|c| policy.counts_in_default(c, profile))
+                .cloned().collect()
         }
         GPAMode::All => { courses.to_vec() }
+        GPAMode::Compulsory => {
+            courses.iter()
+                .filter(|c| is_compulsory(&c.nature))
+                .cloned().collect()
+        }
+        GPAMode::Custom(filter) => {
+            courses.iter()
+                .filter(|c| filter.matches(c))
+                .cloned().collect()
+        }
+        GPAMode::Year(year) => {
+            courses.iter()
+                .filter(|c| c.parsed_semester().map(|s| s.academic_year()).as_deref() == Some(year))
+                .cloned().collect()
+        }
     };
 
-    let total_credits: Decimal = courses_to_use.iter().map(|c| c.credit).sum();
-    let total_cg: Decimal = courses_to_use.iter().map(|c| c.credit_gpa).sum();
-    let gpa = if total_credits > Decimal::ZERO {
-        round_2decimal(total_cg / total_credits)
-    } else {
-        Decimal::ZERO
-    };
+    let gpa = credit_weighted_average(&courses_to_use).map_or(Decimal::ZERO, |avg| policy.round(avg, profile));
 
     (gpa, courses_to_use)
 }
 
-pub fn process_scraped_course_results(courses: &[Course], source: ResultSource) -> ProcessedGPAResults {
+/// 筛选出 Default 模式计入计算的课程
+pub fn default_mode_courses(courses: &[Course], profile: &SchoolProfile) -> Vec<Course> {
+    calculate_gpa_from_list(courses, GPAMode::Default, profile, false).1
+}
+
+/// 筛选出仅必修课模式计入计算的课程
+pub fn compulsory_mode_courses(courses: &[Course], profile: &SchoolProfile) -> Vec<Course> {
+    calculate_gpa_from_list(courses, GPAMode::Compulsory, profile, false).1
+}
+
+/// 课程性质是否属于必修课, 学科基础课等其他性质按名称中的关键字判断
+fn is_compulsory(nature: &CourseNature) -> bool {
+    match nature {
+        CourseNature::Compulsory => true,
+        CourseNature::Unknown(text) => COMPULSORY_NATURES.iter().any(|k| text.contains(k)),
+        CourseNature::Elective | CourseNature::PublicElective | CourseNature::GeneralElective => false
+    }
+}
+
+/// 按学校规则换算、去重并计算各模式的结果, 登录获取与导入文件的数据都经过这里
+/// 成绩按当前计算策略重新换算绩点, 策略无法识别的成绩保留获取时的绩点
+/// 视为通过或不计入的特殊成绩在去重前取出, 以免缓考等记录顶替之后的正式成绩
+pub fn process_scraped_course_results(courses: &[Course], source: ResultSource, profile: &SchoolProfile) -> ProcessedGPAResults {
+    let policy = profile.policy();
+    let (courses, uncounted) = split_uncounted(courses, &profile.grading);
+    let graded: Vec<Course> = courses
+        .iter()
+        .map(|course| match policy.grade(&course.score, profile) {
+            Some(grade) => Course { grade, credit_gpa: policy.round(grade * course.credit, profile), ..course.clone() },
+            None => course.clone()
+        })
+        .collect();
+    let courses = &policy.dedup(&graded, profile);
+
     // 先计算 All 模式的结果
     let all_result = {
-        let (gpa_all, courses_all) = calculate_gpa_from_list(&courses, GPAMode::All);
+        let (gpa_all, courses_all) = calculate_gpa_from_list(courses, GPAMode::All, profile, false);
 
-        GPAResult { gpa: gpa_all, courses: courses_all }
+        GPAResult::new(gpa_all, courses_all, profile)
     };
 
     // 根据数据来源决定是否需要计算 Default 模式
     let default_result = match source {
-        ResultSource::OfficialWebsite => {
-            let (gpa_default, courses_default) = calculate_gpa_from_list(&courses, GPAMode::Default);
+        ResultSource::OfficialWebsite | ResultSource::SavedPage => {
+            let (gpa_default, courses_default) = calculate_gpa_from_list(courses, GPAMode::Default, profile, false);
 
-            Some(GPAResult { gpa: gpa_default, courses: courses_default })
+            Some(GPAResult::new(gpa_default, courses_default, profile))
         }
         ResultSource::InputFile => None
     };
 
+    // 仅必修课模式, 课程性质为空的课程不计入
+    let compulsory_result = {
+        let (gpa_compulsory, courses_compulsory) = calculate_gpa_from_list(courses, GPAMode::Compulsory, profile, false);
+
+        GPAResult::new(gpa_compulsory, courses_compulsory, profile)
+    };
+
+    let exempt: Vec<&Course> = uncounted.iter().filter(|u| u.rule == SpecialScoreRule::Pass).map(|u| &u.course).collect();
+
     ProcessedGPAResults {
+        credit_stats: CreditStats::from_courses(&all_result.courses, &exempt),
         default: default_result,
         all: all_result,
+        compulsory: compulsory_result,
+        credit_warnings: check_credit_sanity(courses),
+        skipped_rows: Vec::new(),
+        uncounted,
+    }
+}
+
+/// 取出视为通过或不计入的特殊成绩, 返回其余课程与取出的课程
+fn split_uncounted(courses: &[Course], grading: &GradingTable) -> (Vec<Course>, Vec<UncountedCourse>) {
+    let mut counted: Vec<Course> = Vec::new();
+    let mut uncounted: Vec<UncountedCourse> = Vec::new();
+    for course in courses {
+        match grading.special_rule(&course.score) {
+            Some(rule) if rule != SpecialScoreRule::Zero => {
+                uncounted.push(UncountedCourse { course: course.clone(), rule, label: rule.label().to_string() });
+            }
+            _ => counted.push(course.clone())
+        }
+    }
+
+    (counted, uncounted)
+}
+
+/// 课程去重: 同一课程(按名称区分, 主修与辅修分开)有多条成绩时按 policy 只保留一条, 课程顺序保持不变
+/// 多条成绩按学期先后排列, 同一学期内保持原顺序(补考、重修排在正常考试之后)
+pub fn dedup_courses(courses: &[Course], policy: RetakePolicy, rounding: &RoundingRule) -> Vec<Course> {
+    let mut attempts: Vec<Vec<&Course>> = Vec::new();
+    let mut index: HashMap<(&str, bool), usize> = HashMap::new();
+    for course in courses {
+        match index.get(&(course.name.as_str(), course.minor)) {
+            Some(&i) => attempts[i].push(course),
+            None => {
+                index.insert((course.name.as_str(), course.minor), attempts.len());
+                attempts.push(vec![course]);
+            }
+        }
+    }
+
+    attempts
+        .into_iter()
+        .filter_map(|mut group| {
+            group.sort_by(|a, b| Semester::order_key(&a.semester).cmp(&Semester::order_key(&b.semester)));
+            match policy {
+                // 绩点相同时保留较早的一次
+                RetakePolicy::Highest => group.iter().copied().reduce(|best, c| if c.grade > best.grade { c } else { best }).cloned(),
+                RetakePolicy::Latest => group.last().copied().cloned(),
+                RetakePolicy::First => group.first().copied().cloned(),
+                RetakePolicy::Average => average_attempts(&group, rounding)
+            }
+        })
+        .collect()
+}
+
+/// 合并同一课程的多次成绩: 绩点取平均, 成绩均为百分制时也取平均, 否则沿用最近一次的成绩
+fn average_attempts(group: &[&Course], rounding: &RoundingRule) -> Option<Course> {
+    let latest = *group.last()?;
+    if group.len() == 1 {
+        return Some(latest.clone());
+    }
+
+    let count = Decimal::from(group.len());
+    let grade = rounding.round(group.iter().map(|c| c.grade).sum::<Decimal>() / count);
+    let scores: Option<Vec<Decimal>> = group.iter().map(|c| c.score.numeric()).collect();
+    let score = match scores {
+        Some(scores) => Score::Numeric(rounding.round(scores.iter().sum::<Decimal>() / count).normalize()),
+        None => latest.score.clone()
+    };
+
+    Some(Course { score, grade, credit_gpa: rounding.round(grade * latest.credit), ..latest.clone() })
+}
+
+/// 按学期分组计算绩点, 没有学期信息的课程(如模板文件导入的课程)不参与分组
+/// 以最早的学年作为大一, 据此给出"大一上"这样的名称
+pub fn semester_gpas(courses: &[Course], profile: &SchoolProfile) -> Vec<SemesterGPA> {
+    let policy = profile.policy();
+
+    // 按学期先后分组, 无法识别的学期排在最后
+    let mut groups: BTreeMap<(bool, Option<Semester>, &str), Vec<&Course>> = BTreeMap::new();
+    for course in courses.iter().filter(|c| !c.semester.is_empty()) {
+        groups.entry(Semester::order_key(&course.semester)).or_default().push(course);
+    }
+
+    let first_year = groups.keys().filter_map(|(_, parsed, _)| parsed.map(|s| s.start_year)).min();
+
+    groups
+        .into_iter()
+        .map(|((_, parsed, semester), courses)| {
+            let credits: Decimal = courses.iter().map(|c| c.credit).sum();
+            let total_cg: Decimal = courses.iter().map(|c| c.credit_gpa).sum();
+            let gpa = if credits > Decimal::ZERO { policy.round(total_cg / credits, profile) } else { Decimal::ZERO };
+
+            let label = parsed
+                .zip(first_year)
+                .and_then(|(parsed, first)| semester_label(parsed.start_year - first, parsed.term))
+                .unwrap_or_else(|| semester.to_string());
+
+            SemesterGPA { semester: semester.to_string(), label, gpa, credits, course_count: courses.len() }
+        })
+        .collect()
+}
+
+/// 按学期先后计算绩点走势, 没有学期信息的课程不计入
+pub fn gpa_trend(courses: &[Course], profile: &SchoolProfile) -> Vec<TrendPoint> {
+    let policy = profile.policy();
+    let mut cumulative_credits = Decimal::ZERO;
+    let mut cumulative_cg = Decimal::ZERO;
+
+    semester_gpas(courses, profile)
+        .into_iter()
+        .map(|term| {
+            cumulative_credits += term.credits;
+            cumulative_cg += courses.iter().filter(|c| c.semester == term.semester).map(|c| c.credit_gpa).sum::<Decimal>();
+            let cumulative_gpa = if cumulative_credits > Decimal::ZERO { policy.round(cumulative_cg / cumulative_credits, profile) } else { Decimal::ZERO };
+
+            TrendPoint { semester: term.semester, label: term.label, term_gpa: term.gpa, cumulative_gpa, cumulative_credits }
+        })
+        .collect()
+}
+
+/// 入学后第几学年(从 0 开始)的第几学期, 如 (1, 1) 为"大二上"
+fn semester_label(year_index: i32, term: u8) -> Option<String> {
+    let grade = GRADE_NAMES.get(usize::try_from(year_index).ok()?)?;
+    let half = match term {
+        1 => "上",
+        2 => "下",
+        _ => return None
+    };
+
+    Some(format!("{}{}", grade, half))
+}
+
+/// 计算指定学年的绩点, courses 应为已按当前模式筛选过的课程
+pub fn year_gpa(courses: &[Course], year: &str, profile: &SchoolProfile) -> GPAResult {
+    let (gpa, courses) = calculate_gpa_from_list(courses, GPAMode::Year(year), profile, false);
+
+    GPAResult::new(gpa, courses, profile)
+}
+
+/// 按自定义筛选条件计算绩点, courses 应为全部课程
+pub fn filter_gpa(courses: &[Course], filter: &CourseFilter, profile: &SchoolProfile) -> Result<GPAResult, String> {
+    filter.validate()?;
+    let (gpa, courses) = calculate_gpa_from_list(courses, GPAMode::Custom(filter), profile, false);
+
+    Ok(GPAResult::new(gpa, courses, profile))
+}
+
+/// 在已按当前模式筛选的结果中去掉未通过的课程并重新计算
+pub fn exclude_failed_courses(result: &GPAResult, profile: &SchoolProfile) -> GPAResult {
+    let (gpa, courses) = calculate_gpa_from_list(&result.courses, GPAMode::All, profile, true);
+
+    GPAResult::new(gpa, courses, profile)
+}
+
+/// 课程涉及的全部学年, 按时间先后排列
+pub fn academic_years(courses: &[Course]) -> Vec<String> {
+    let mut years: Vec<String> = courses.iter().filter_map(|c| c.parsed_semester()).map(|s| s.academic_year()).collect();
+    years.sort();
+    years.dedup();
+
+    years
+}
+
+/// 是否为 2023-2024 格式的学年
+pub fn is_academic_year(value: &str) -> bool {
+    match value.split_once('-') {
+        Some((start, end)) => match (start.parse::<i32>(), end.parse::<i32>()) {
+            (Ok(start), Ok(end)) => end == start + 1,
+            _ => false
+        },
+        None => false
+    }
+}
+
+/// 检查学分是否在合理范围内, 例如上传文件时把 3.0 误填成 30
+/// 分别检查每门课程、每个学期与全部课程的学分, 只给出警告, 不会剔除课程
+pub fn check_credit_sanity(courses: &[Course]) -> Vec<String> {
+    let mut warnings: Vec<String> = courses
+        .iter()
+        .filter_map(|c| {
+            if c.credit < Decimal::ZERO {
+                Some(format!("课程“{}”的学分为负数({}), 请检查数据", c.name, c.credit))
+            } else if c.credit > MAX_COURSE_CREDIT {
+                Some(format!("课程“{}”的学分为 {}, 超过了常见上限 {}, 是否误填了小数点？", c.name, c.credit, MAX_COURSE_CREDIT))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    // 按学期汇总, 没有学期的课程只计入总学分
+    let mut semester_credits: BTreeMap<&str, Decimal> = BTreeMap::new();
+    for course in courses.iter().filter(|c| !c.semester.is_empty()) {
+        *semester_credits.entry(&course.semester).or_default() += course.credit;
+    }
+    for (semester, credits) in semester_credits {
+        if credits > MAX_SEMESTER_CREDITS {
+            warnings.push(format!("{} 学期的学分合计为 {}, 超过了常见上限 {}, 是否重复导入或误填了学分？", semester, credits, MAX_SEMESTER_CREDITS));
+        }
+    }
+
+    let total_credits: Decimal = courses.iter().map(|c| c.credit).sum();
+    if total_credits > MAX_TOTAL_CREDITS {
+        warnings.push(format!("总学分为 {}, 超过了常见上限 {}, 计算结果可能有误", total_credits, MAX_TOTAL_CREDITS));
+    }
+
+    warnings
+}
+
+/// 将当前结果与目标绩点对比, 并估算剩余学期每学期需要达到的平均绩点
+/// max_grade 为学校规则中单门课程的最高绩点, 需要的平均绩点超过它时目标无法达到
+pub fn compare_with_benchmark(result: &GPAResult, benchmark: &Benchmark, max_grade: Decimal) -> BenchmarkComparison {
+    let delta = result.gpa - benchmark.gpa;
+    let reached = delta >= Decimal::ZERO;
+
+    let remaining_credits = Decimal::from(benchmark.remaining_semesters) * benchmark.credits_per_semester;
+    let required_gpa = required_average(result, benchmark.gpa, remaining_credits).map(|r| round_2decimal(r.max(Decimal::ZERO)));
+
+    let achievable = match required_gpa {
+        Some(required) => required <= max_grade,
+        None => reached
+    };
+
+    BenchmarkComparison {
+        benchmark: benchmark.gpa,
+        delta: round_2decimal(delta),
+        reached,
+        required_gpa,
+        achievable,
+    }
+}
+
+/// 逐门课程计算去掉该课程、或该课程取得 IMPACT_REFERENCE_GRADE 时平均绩点的变化
+/// 按去掉后的变化从大到小排列, 最拉低绩点的课程(成绩低、学分高)排在最前面
+pub fn course_impacts(result: &GPAResult, profile: &SchoolProfile) -> Vec<CourseImpact> {
+    let policy = profile.policy();
+    let total_credits: Decimal = result.courses.iter().map(|c| c.credit).sum();
+    let total_cg: Decimal = result.courses.iter().map(|c| c.credit_gpa).sum();
+    if total_credits <= Decimal::ZERO {
+        return Vec::new();
+    }
+
+    let mut impacts: Vec<CourseImpact> = result.courses
+        .iter()
+        .map(|course| {
+            let rest_credits = total_credits - course.credit;
+            let removed_delta = (rest_credits > Decimal::ZERO)
+                .then(|| policy.round((total_cg - course.credit_gpa) / rest_credits, profile) - result.gpa);
+
+            let reference_cg = total_cg - course.credit_gpa + policy.round(IMPACT_REFERENCE_GRADE * course.credit, profile);
+            let reference_delta = policy.round(reference_cg / total_credits, profile) - result.gpa;
+
+            CourseImpact {
+                name: course.name.clone(),
+                semester: course.semester.clone(),
+                credit: course.credit,
+                grade: course.grade,
+                removed_delta,
+                reference_delta,
+            }
+        })
+        .collect();
+
+    impacts.sort_by(|a, b| b.removed_delta.cmp(&a.removed_delta).then(b.reference_delta.cmp(&a.reference_delta)));
+
+    impacts
+}
+
+/// 在当前结果中加入假设的课程后重新计算绩点, 用于预估下学期的课程对绩点的影响
+/// default_mode 为 true 时, 假设课程同样按 Default 模式的排除规则筛选
+pub fn simulate_courses(
+    result: &GPAResult, hypothetical: &[HypotheticalCourse], default_mode: bool, profile: &SchoolProfile
+) -> Result<WhatIfResult, String> {
+    if hypothetical.is_empty() {
+        return Err("请至少添加一门假设课程".to_string());
+    }
+
+    let policy = profile.policy();
+    let mut added: Vec<Course> = Vec::new();
+    for course in hypothetical {
+        let name = course.name.trim();
+        if name.is_empty() {
+            return Err("假设课程的名称不能为空".to_string());
+        }
+        if course.credit <= Decimal::ZERO || course.credit > MAX_COURSE_CREDIT {
+            return Err(format!("课程“{}”的学分应在 0 到 {} 之间", name, MAX_COURSE_CREDIT));
+        }
+
+        let score = Score::from(course.score.as_str());
+        let grade = policy.grade(&score, profile)
+            .ok_or_else(|| format!("课程“{}”的预期成绩无法识别: {}", name, course.score))?;
+        added.push(Course {
+            course_code: String::new(),
+            semester: String::new(),
+            name: name.to_string(),
+            nature: CourseNature::default(),
+            score,
+            credit: course.credit,
+            grade,
+            credit_gpa: policy.round(grade * course.credit, profile),
+            exam_type: "假设".to_string(),
+            teacher: String::new(),
+            hours: String::new(),
+            assessment: String::new(),
+            minor: false,
+            source: String::new(),
+        });
+    }
+
+    let counted = match default_mode {
+        true => default_mode_courses(&added, profile),
+        false => calculate_gpa_from_list(&added, GPAMode::All, profile, false).1
+    };
+    let excluded = added.iter().filter(|c| !counted.iter().any(|k| k.name == c.name)).map(|c| c.name.clone()).collect();
+
+    let combined: Vec<Course> = result.courses.iter().chain(&counted).cloned().collect();
+    let simulated_gpa = weighted_gpa(&combined, profile);
+
+    Ok(WhatIfResult {
+        current_gpa: result.gpa,
+        simulated_gpa,
+        delta: simulated_gpa - result.gpa,
+        courses: counted,
+        excluded,
+    })
+}
+
+/// 根据班级平均值与标准差估算排名, 假设班级成绩服从正态分布
+pub fn estimate_ranking(
+    result: &GPAResult, basis: RankingBasis, class_average: Decimal, std_dev: Option<Decimal>, class_size: Option<u32>
+) -> Result<RankingEstimate, String> {
+    let (value, default_std_dev) = match basis {
+        RankingBasis::Gpa => (result.gpa, DEFAULT_GPA_STD_DEV),
+        RankingBasis::Score => (result.averages.weighted.ok_or("没有可计算平均分的课程")?, DEFAULT_SCORE_STD_DEV),
+    };
+    let std_dev = std_dev.unwrap_or(default_std_dev);
+    if class_average <= Decimal::ZERO {
+        return Err("班级平均值需大于 0".to_string());
+    }
+    if std_dev <= Decimal::ZERO {
+        return Err("标准差需大于 0".to_string());
+    }
+    if class_size == Some(0) {
+        return Err("班级人数需大于 0".to_string());
+    }
+
+    // 高于本人的比例 = 1 - Φ(z), 标准差极小或平均值极大时 z 超出 Decimal 的范围, 不能直接相除
+    let z = value
+        .checked_sub(class_average)
+        .and_then(|diff| diff.checked_div(std_dev))
+        .ok_or("班级平均值或标准差超出可计算的范围")?
+        .to_f64()
+        .unwrap_or_default();
+    let above = (1.0 - normal_cdf(z)).clamp(0.01, 0.99);
+    let top_percent = Decimal::try_from(above * 100.0).map(|p| p.round_dp(1)).map_err(|e| e.to_string())?;
+    let rank = class_size.map(|size| ((above * f64::from(size)).ceil() as u32).clamp(1, size));
+
+    Ok(RankingEstimate { basis, value, class_average, std_dev, top_percent, rank })
+}
+
+/// 标准正态分布的累积分布函数, 使用 Abramowitz-Stegun 7.1.26 近似 erf, 误差小于 1.5e-7
+fn normal_cdf(z: f64) -> f64 {
+    let x = z.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - poly * (-x * x).exp();
+
+    if z >= 0.0 { 0.5 * (1.0 + erf) } else { 0.5 * (1.0 - erf) }
+}
+
+/// 给定目标累计绩点与剩余学分, 计算剩余课程至少需要达到的平均绩点
+pub fn plan_target(result: &GPAResult, target: Decimal, remaining_credits: Decimal, max_grade: Decimal) -> Result<TargetPlan, String> {
+    if target <= Decimal::ZERO || target > max_grade {
+        return Err(format!("目标绩点应在 0 到 {} 之间", max_grade.normalize()));
+    }
+    if remaining_credits.is_sign_negative() || remaining_credits > MAX_TOTAL_CREDITS {
+        return Err(format!("剩余学分应在 0 到 {} 之间", MAX_TOTAL_CREDITS));
+    }
+
+    let required = required_average(result, target, remaining_credits);
+    let status = match required {
+        Some(r) if r <= Decimal::ZERO => PlanStatus::Secured,
+        Some(r) if r <= max_grade => PlanStatus::Achievable,
+        None if result.gpa >= target => PlanStatus::Secured,
+        _ => PlanStatus::Unreachable
+    };
+
+    Ok(TargetPlan {
+        target,
+        current_gpa: result.gpa,
+        earned_credits: result.courses.iter().map(|c| c.credit).sum(),
+        remaining_credits,
+        required_gpa: required.map(|r| round_up_2decimal(r.max(Decimal::ZERO))),
+        status,
+    })
+}
+
+/// 剩余学分需要达到的平均绩点, 未截断为非负数; 没有剩余学分时为 None
+/// 目标总加权绩点 = 目标绩点 × (已修学分 + 剩余学分), 剩余学分需要补足差额
+fn required_average(result: &GPAResult, target: Decimal, remaining_credits: Decimal) -> Option<Decimal> {
+    if remaining_credits <= Decimal::ZERO {
+        return None;
+    }
+
+    let earned_credits: Decimal = result.courses.iter().map(|c| c.credit).sum();
+    let earned_cg: Decimal = result.courses.iter().map(|c| c.credit_gpa).sum();
+
+    Some((target * (earned_credits + remaining_credits) - earned_cg) / remaining_credits)
+}
+
+/// 按学校的综测规则计算综测总分
+/// 智育部分使用所选学期课程的加权平均绩点, 手填项得分超出满分时按满分计
+/// 规则中设置了学期权重时, 先算出每个学期的平均绩点, 再按学生的第几个学期取权重加权平均
+pub fn calculate_evaluation(
+    courses: &[Course], profile: &SchoolProfile, input: &EvaluationInput
+) -> Result<EvaluationResult, String> {
+    let rules = &profile.evaluation;
+    if input.components.len() != rules.components.len() {
+        return Err(format!("需要填写 {} 项得分", rules.components.len()));
+    }
+
+    let selected: Vec<&Course> = courses
+        .iter()
+        .filter(|c| input.semesters.is_empty() || input.semesters.contains(&c.semester))
+        .collect();
+    let total_credits: Decimal = selected.iter().map(|c| c.credit).sum();
+    if total_credits <= Decimal::ZERO {
+        return Err("所选学期没有可计算的课程".to_string());
+    }
+    let gpa = if rules.semester_weights.is_empty() {
+        let total_cg: Decimal = selected.iter().map(|c| c.credit_gpa).sum();
+        total_cg / total_credits
+    } else {
+        semester_weighted_gpa(courses, &selected, rules)?
+    };
+    let gpa = profile.policy().round(gpa, profile);
+
+    let mut parts = Vec::with_capacity(rules.components.len() + 1);
+
+    let gpa_score = (gpa * rules.gpa_multiplier).min(dec!(100));
+    parts.push(EvaluationPart {
+        name: "智育".to_string(),
+        score: round_2decimal(gpa_score),
+        weighted: round_2decimal(gpa_score * rules.gpa_weight),
+    });
+
+    for (component, &score) in rules.components.iter().zip(&input.components) {
+        if score < Decimal::ZERO {
+            return Err(format!("{} 得分不能为负数", component.name));
+        }
+        let score = score.min(component.max_score);
+        parts.push(EvaluationPart {
+            name: component.name.clone(),
+            score,
+            weighted: round_2decimal(score * component.weight),
+        });
+    }
+
+    let bonus_sum: Decimal = input.bonuses.iter().map(|b| b.points).sum();
+    let bonus = bonus_sum.clamp(Decimal::ZERO, rules.bonus_cap);
+    let total = parts.iter().map(|p| p.weighted).sum::<Decimal>() + bonus;
+
+    Ok(EvaluationResult {
+        gpa,
+        parts,
+        bonus,
+        bonus_capped: bonus_sum > rules.bonus_cap,
+        total: round_2decimal(total),
+    })
+}
+
+/// 按学期权重计算综测的智育绩点, 学期的序号按学生的全部课程排列, 只选部分学期时序号不变
+fn semester_weighted_gpa(courses: &[Course], selected: &[&Course], rules: &EvaluationRules) -> Result<Decimal, String> {
+    if let Some(course) = selected.iter().find(|c| c.semester.is_empty()) {
+        return Err(format!("课程 {} 没有学期信息, 无法按学期加权", course.name));
+    }
+
+    let mut all_semesters: Vec<&str> = courses.iter().map(|c| c.semester.as_str()).filter(|s| !s.is_empty()).collect();
+    all_semesters.sort_by(|a, b| Semester::order_key(a).cmp(&Semester::order_key(b)));
+    all_semesters.dedup();
+
+    // 每个学期的学分与学分绩点合计
+    let mut per_semester: BTreeMap<&str, (Decimal, Decimal)> = BTreeMap::new();
+    for course in selected {
+        let entry = per_semester.entry(course.semester.as_str()).or_default();
+        entry.0 += course.credit;
+        entry.1 += course.credit_gpa;
+    }
+
+    let mut weighted_sum = Decimal::ZERO;
+    let mut weight_sum = Decimal::ZERO;
+    for (semester, (credits, credit_gpa)) in per_semester {
+        if credits <= Decimal::ZERO {
+            continue;
+        }
+        let index = all_semesters.iter().position(|s| *s == semester)
+            .ok_or_else(|| format!("学期 {} 不在全部课程的学期中, 无法确定学期序号", semester))?;
+        let weight = rules.semester_weight(index).unwrap_or(Decimal::ONE);
+        weighted_sum += credit_gpa / credits * weight;
+        weight_sum += weight;
+    }
+
+    if weight_sum <= Decimal::ZERO {
+        return Err("所选学期的权重均为 0, 无法计算智育绩点".to_string());
+    }
+    Ok(weighted_sum / weight_sum)
+}
+
+/// 对照本工具与教务系统官方的绩点, 差值不超过 0.01 视为一致
+pub fn compare_with_official(ours: Decimal, official: Decimal) -> OfficialComparison {
+    let delta = round_2decimal(ours - official);
+
+    OfficialComparison {
+        official,
+        ours,
+        delta,
+        consistent: delta.abs() <= dec!(0.01),
     }
 }
 
@@ -172,4 +1446,80 @@ pub fn print_info(msg: &str) {
 /// 打印异常信息
 pub fn print_error(msg: &str) {
     eprintln!("{}", format_log_msg(msg));
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn course(semester: &str, name: &str, score: &str, grade: Decimal, minor: bool) -> Course {
+        Course {
+            course_code: String::new(),
+            semester: semester.to_string(),
+            name: name.to_string(),
+            nature: CourseNature::Compulsory,
+            score: Score::from(score),
+            credit: dec!(4),
+            grade,
+            credit_gpa: grade * dec!(4),
+            exam_type: String::new(),
+            teacher: String::new(),
+            hours: String::new(),
+            assessment: String::new(),
+            minor,
+            source: String::new(),
+        }
+    }
+
+    // 同一课程三次成绩, 故意不按学期先后排列; 另有一门只考一次的课程与同名的辅修课程
+    fn attempts() -> Vec<Course> {
+        vec![
+            course("2023-2024-1", "高等数学", "80", dec!(3.33), false),
+            course("2022-2023-1", "高等数学", "55", dec!(0), false),
+            course("2022-2023-1", "大学英语", "88", dec!(4.00), false),
+            course("2022-2023-2", "高等数学", "90", dec!(4.33), false),
+            course("2022-2023-1", "高等数学", "70", dec!(2.33), true),
+        ]
+    }
+
+    fn dedup(policy: RetakePolicy) -> Vec<Course> {
+        let courses = dedup_courses(&attempts(), policy, &RoundingRule::default());
+        let names: Vec<(&str, bool)> = courses.iter().map(|c| (c.name.as_str(), c.minor)).collect();
+        assert_eq!(names, [("高等数学", false), ("大学英语", false), ("高等数学", true)]);
+        courses
+    }
+
+    #[test]
+    fn dedup_highest_keeps_best_grade() {
+        let courses = dedup(RetakePolicy::Highest);
+        assert_eq!(courses[0].semester, "2022-2023-2");
+        assert_eq!(courses[0].grade, dec!(4.33));
+    }
+
+    #[test]
+    fn dedup_latest_keeps_last_semester() {
+        let courses = dedup(RetakePolicy::Latest);
+        assert_eq!(courses[0].semester, "2023-2024-1");
+        assert_eq!(courses[0].score, Score::from("80"));
+    }
+
+    #[test]
+    fn dedup_first_keeps_earliest_semester() {
+        let courses = dedup(RetakePolicy::First);
+        assert_eq!(courses[0].semester, "2022-2023-1");
+        assert_eq!(courses[0].grade, Decimal::ZERO);
+    }
+
+    #[test]
+    fn dedup_average_combines_attempts() {
+        let courses = dedup(RetakePolicy::Average);
+        // (0 + 4.33 + 3.33) / 3 = 2.5533.., 成绩 (55 + 90 + 80) / 3 = 75, 其余字段沿用最近一次
+        assert_eq!(courses[0].grade, dec!(2.55));
+        assert_eq!(courses[0].score, Score::Numeric(dec!(75)));
+        assert_eq!(courses[0].credit_gpa, dec!(10.20));
+        assert_eq!(courses[0].semester, "2023-2024-1");
+        // 只考一次的课程与辅修课程不受影响
+        assert_eq!(courses[1].grade, dec!(4.00));
+        assert_eq!(courses[2].grade, dec!(2.33));
+    }
+}
@@ -0,0 +1,64 @@
+// 更新日志层 - 程序内嵌结构化的更新日志, 升级后在登录页提示本版本新增的功能
+use crate::BinaryAsset;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+const CHANGELOG_FILE: &str = "changelog.json";
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");   // 当前程序的版本号, 来自 Cargo.toml
+pub const UNTRACKED_VERSION: &str = "0.5.2";    // 开始记录版本号之前的最后一个版本, 从该版本升级的数据目录中没有版本记录
+
+// 一个版本的更新内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Release {
+    pub version: String,    // 版本号, 如 0.5.2
+    pub date: String,       // 发布日期, 如 2026-10-16
+    pub changes: Vec<String>,
+}
+
+// 全部更新日志, 新版本在前
+pub struct Changelog {
+    releases: Vec<Release>,
+}
+
+impl Changelog {
+    /// 读取内嵌的更新日志
+    pub fn load() -> Result<Self> {
+        let file = BinaryAsset::get(CHANGELOG_FILE).ok_or_else(|| anyhow!("缺少嵌入资源 {}", CHANGELOG_FILE))?;
+        let mut releases: Vec<Release> = serde_json::from_slice(&file.data)
+            .with_context(|| format!("更新日志 {} 格式有误", CHANGELOG_FILE))?;
+
+        releases.sort_by_key(|r| std::cmp::Reverse(version_key(&r.version)));
+
+        Ok(Self { releases })
+    }
+
+    pub fn releases(&self) -> &[Release] {
+        &self.releases
+    }
+
+    /// 上次使用的版本之后、当前版本及以前的更新内容
+    pub fn since(&self, last_seen: &str) -> Vec<Release> {
+        let last_seen = version_key(last_seen);
+        let current = version_key(CURRENT_VERSION);
+
+        self.releases
+            .iter()
+            .filter(|r| {
+                let version = version_key(&r.version);
+                version > last_seen && version <= current
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// 把 0.5.2 这样的版本号转换为可比较的数字列表, 无法识别的部分按 0 处理
+fn version_key(version: &str) -> Vec<u32> {
+    version
+        .trim()
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
@@ -0,0 +1,122 @@
+// 班级统计层 - 辅导员批量导入全班的成绩文件, 只汇总绩点分布, 不保留任何能对应到个人的数据
+use crate::business::round_2decimal;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+pub const MIN_COHORT_SIZE: usize = 5;   // 人数过少时可以反推个人绩点, 不予统计
+pub const BATCH_TTL: Duration = Duration::from_secs(7 * 24 * 3600); // 分批上传的统计超过这么久未更新则作废
+const BUCKET_WIDTH: Decimal = dec!(0.5);    // 直方图每一档的绩点宽度
+
+// 直方图的一档, 区间左闭右开, 最后一档包含最高绩点
+#[derive(Debug, Clone, Serialize)]
+pub struct Bucket {
+    pub from: Decimal,
+    pub to: Decimal,
+    pub count: usize,
+}
+
+// 全班绩点分布, 只包含统计量, 不含最高分、最低分等单个学生的绩点
+#[derive(Debug, Clone, Serialize)]
+pub struct Distribution {
+    pub count: usize,
+    pub mean: Decimal,
+    pub median: Decimal,
+    pub q1: Decimal,    // 下四分位数
+    pub q3: Decimal,    // 上四分位数
+    pub histogram: Vec<Bucket>,
+}
+
+impl Distribution {
+    /// 由各学生的绩点计算分布, 人数不足 MIN_COHORT_SIZE 时返回 None
+    /// 直方图分档到学校规则中单门课程的最高绩点 max_grade 为止
+    pub fn from_gpas(mut gpas: Vec<Decimal>, max_grade: Decimal) -> Option<Self> {
+        if gpas.len() < MIN_COHORT_SIZE {
+            return None;
+        }
+        gpas.sort();
+
+        let count = gpas.len();
+        let mean = gpas.iter().sum::<Decimal>() / Decimal::from(count);
+
+        Some(Self {
+            count,
+            mean: round_2decimal(mean),
+            median: quantile(&gpas, dec!(0.5)),
+            q1: quantile(&gpas, dec!(0.25)),
+            q3: quantile(&gpas, dec!(0.75)),
+            histogram: histogram(&gpas, max_grade),
+        })
+    }
+
+    /// 导出为 CSV, 供院系汇总上报
+    pub fn to_csv(&self) -> Result<Vec<u8>, csv::Error> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+
+        writer.write_record(["统计项", "值"])?;
+        writer.write_record(["人数", &self.count.to_string()])?;
+        writer.write_record(["平均绩点", &self.mean.to_string()])?;
+        writer.write_record(["中位数", &self.median.to_string()])?;
+        writer.write_record(["下四分位数", &self.q1.to_string()])?;
+        writer.write_record(["上四分位数", &self.q3.to_string()])?;
+        writer.write_record(["", ""])?;
+
+        writer.write_record(["绩点区间", "人数"])?;
+        for bucket in &self.histogram {
+            writer.write_record([format!("{}~{}", bucket.from, bucket.to), bucket.count.to_string()])?;
+        }
+
+        writer.into_inner().map_err(|e| e.into_error().into())
+    }
+}
+
+// 分批上传的班级统计, 每统计一个文件就保存一次, 上传中断或程序重启后可以继续
+// 以文件内容的哈希值区分已统计的文件, 不保存文件名与课程
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CohortBatch {
+    pub mode: String,   // default 或 all
+    pub gpas: BTreeMap<String, Decimal>,    // 文件哈希 → 绩点
+}
+
+impl CohortBatch {
+    /// 当前已统计文件的分布, 人数不足时返回 None
+    pub fn distribution(&self, max_grade: Decimal) -> Option<Distribution> {
+        Distribution::from_gpas(self.gpas.values().copied().collect(), max_grade)
+    }
+}
+
+/// 线性插值计算分位数, sorted 必须已升序排列且非空
+fn quantile(sorted: &[Decimal], q: Decimal) -> Decimal {
+    let position = q * Decimal::from(sorted.len() - 1);
+    let lower = position.floor();
+    let index = usize::try_from(lower).unwrap_or_default();
+
+    let value = match sorted.get(index + 1) {
+        Some(next) => sorted[index] + (next - sorted[index]) * (position - lower),
+        None => sorted[index]
+    };
+
+    round_2decimal(value)
+}
+
+/// 按 BUCKET_WIDTH 把 0 ~ max_grade 分档计数, 至少有一档
+fn histogram(gpas: &[Decimal], max_grade: Decimal) -> Vec<Bucket> {
+    let max_grade = max_grade.max(BUCKET_WIDTH);
+    let mut buckets: Vec<Bucket> = Vec::new();
+    let mut from = Decimal::ZERO;
+    while from < max_grade {
+        let to = (from + BUCKET_WIDTH).min(max_grade);
+        buckets.push(Bucket { from: from.normalize(), to: to.normalize(), count: 0 });
+        from += BUCKET_WIDTH;
+    }
+
+    let last = buckets.len() - 1;
+    for gpa in gpas {
+        let index = usize::try_from((gpa / BUCKET_WIDTH).floor()).unwrap_or_default();
+        buckets[index.min(last)].count += 1;
+    }
+
+    buckets
+}
@@ -0,0 +1,363 @@
+// 运行配置层 - 从命令行参数与环境变量读取启动配置
+// 命令行参数优先, 其次是环境变量, 最后是默认值
+use anyhow::{anyhow, Result};
+use fake_user_agent::get_rua;
+use std::{env, fs, net::IpAddr, path::PathBuf, time::Duration};
+
+// 默认超时时间(秒)
+const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+const DEFAULT_REQUEST_TIMEOUT: u64 = 30;
+
+// 默认的请求间隔与随机抖动(毫秒), 学校服务器比较脆弱, 请求过密会触发防火墙
+const DEFAULT_REQUEST_INTERVAL_MS: u64 = 300;
+const DEFAULT_REQUEST_JITTER_MS: u64 = 200;
+
+// 默认的单次上传大小上限(MB), 成绩单通常只有几十 KB, 班级统计一次上传多份文件也足够
+const DEFAULT_MAX_UPLOAD_SIZE_MB: usize = 10;
+
+// 后台成绩监控的默认查询间隔与最小间隔(分钟), 间隔过短会给教务系统带来不必要的压力
+const DEFAULT_WATCH_INTERVAL_MINUTES: u64 = 30;
+const MIN_WATCH_INTERVAL_MINUTES: u64 = 5;
+
+// SMTP 服务器的默认端口, 使用 SSL/TLS 连接, QQ 邮箱、163 邮箱等都支持
+const DEFAULT_SMTP_PORT: u16 = 465;
+
+// 默认数据目录名, 位于可执行文件旁边
+const DEFAULT_DATA_DIR: &str = "yit-gpa-data";
+// 数据目录下保存调试网页的子目录
+const DEBUG_HTML_DIR: &str = "debug";
+
+// 程序启动配置
+#[derive(Debug, Clone, Default)]
+pub struct AppConfig {
+    pub read_only: bool,    // 只读模式: 禁用所有登录/爬取功能, 仅允许导入文件计算
+    pub scraper: ScraperConfig, // 爬虫客户端配置
+    pub data_dir: PathBuf,  // 数据目录, 保存用户设置、成绩历史等本地数据
+    pub low_memory: bool,   // 低内存模式: 逐个获取并解析网页, Session 中只保存一份课程列表
+    pub demo: bool,     // 演示模式: 不连接教务系统, 登录后返回模拟成绩
+    pub api_token: Option<String>,  // 摘要 API 的访问令牌, 设置后允许其他网页跨域读取
+    pub bind: Option<IpAddr>,   // 监听地址, 为空时只监听 127.0.0.1; 设为 0.0.0.0 或局域网 IP 时同一局域网的设备也可以访问
+    pub allowed_hosts: Vec<String>, // 局域网模式下额外允许的主机名, 如 gpa.lan
+    pub pdf_font: Option<PathBuf>,  // 导出 PDF 使用的中文字体文件, 为空时在系统字体中查找
+    pub max_upload_size: usize, // 单次上传的大小上限(MB)
+    pub watch_interval: Duration,   // 后台成绩监控的查询间隔
+    pub smtp: Option<SmtpConfig>,   // 发送邮件通知的 SMTP 服务器, 为空时不发送邮件
+}
+
+// SMTP 服务器设置, 后台成绩监控发现新成绩时发送邮件
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,      // 465 端口使用 SSL/TLS, 其他端口使用 STARTTLS
+    pub username: String,
+    pub password: String,   // 邮箱的密码或授权码
+    pub from: String,   // 发件人, 默认与用户名相同
+    pub to: Vec<String>,    // 收件人, 默认发给发件人自己
+}
+
+// 爬虫客户端配置
+#[derive(Debug, Clone)]
+pub struct ScraperConfig {
+    pub connect_timeout: Duration,  // 建立连接的超时时间
+    pub request_timeout: Duration,  // 单个请求(含读取响应)的总超时时间
+    pub proxy: ProxyConfig,     // 代理设置
+    pub sequential_fetch: bool, // 逐个学期、逐页获取成绩, 同一时间只保留一个网页在内存中(每个网页仍完整读入后再解析)
+    pub debug_html_dir: Option<PathBuf>,    // 解析不到成绩时保存网页的目录, 为空则不保存
+    pub user_agent: UserAgentPolicy,    // 爬虫使用的 UA
+    pub min_request_interval: Duration, // 相邻两次请求的最小间隔
+    pub request_jitter: Duration,   // 在最小间隔之上随机增加的等待时间上限
+    pub login: LoginMethod,     // 登录教务系统的方式
+}
+
+// 登录教务系统的方式
+#[derive(Debug, Clone, Default)]
+pub enum LoginMethod {
+    #[default]
+    Direct,         // 直接提交教务系统自己的登录表单
+    Cas(String),    // 通过统一身份认证(CAS)登录, 值为认证服务器的登录地址
+}
+
+// 爬虫 UA 的选择方式
+#[derive(Debug, Clone)]
+pub enum UserAgentPolicy {
+    Fixed(String),      // 程序运行期间始终使用同一个 UA, 在启动时随机选择
+    RotatePerSession,   // 每次登录查询都随机选择一个新的 UA
+}
+
+impl UserAgentPolicy {
+    /// 为新的爬虫实例选择 UA
+    pub fn pick(&self) -> String {
+        match self {
+            UserAgentPolicy::Fixed(user_agent) => user_agent.clone(),
+            UserAgentPolicy::RotatePerSession => get_rua().to_string()
+        }
+    }
+}
+
+// 代理设置, 支持 http://, https://, socks5://, socks5h:// 形式的地址
+#[derive(Debug, Clone, Default)]
+pub struct ProxyConfig {
+    pub all: Option<String>,    // 所有请求使用的代理
+    pub http: Option<String>,   // 仅 http 请求使用的代理, 优先于 all
+    pub https: Option<String>,  // 仅 https 请求使用的代理, 优先于 all
+    pub disabled: bool,     // 完全不使用代理(包括系统代理)
+}
+
+impl Default for ScraperConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
+            request_timeout: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT),
+            proxy: ProxyConfig::default(),
+            sequential_fetch: false,
+            debug_html_dir: None,
+            user_agent: UserAgentPolicy::Fixed(get_rua().to_string()),
+            min_request_interval: Duration::from_millis(DEFAULT_REQUEST_INTERVAL_MS),
+            request_jitter: Duration::from_millis(DEFAULT_REQUEST_JITTER_MS),
+            login: LoginMethod::Direct,
+        }
+    }
+}
+
+impl AppConfig {
+    /// 从命令行参数和环境变量加载配置, 参数值非法时返回错误
+    pub fn load() -> Result<Self> {
+        let args: Vec<String> = env::args().skip(1).collect();
+        let low_memory = flag(&args, "--low-memory", "YIT_GPA_LOW_MEMORY");
+
+        let data_dir = value(&args, "--data-dir", "YIT_GPA_DATA_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(default_data_dir);
+
+        let scraper = ScraperConfig {
+            connect_timeout: Duration::from_secs(
+                parse_secs(&args, "--connect-timeout", "YIT_GPA_CONNECT_TIMEOUT")?.unwrap_or(DEFAULT_CONNECT_TIMEOUT)
+            ),
+            request_timeout: Duration::from_secs(
+                parse_secs(&args, "--timeout", "YIT_GPA_TIMEOUT")?.unwrap_or(DEFAULT_REQUEST_TIMEOUT)
+            ),
+            proxy: ProxyConfig {
+                all: parse_proxy(&args, "--proxy", "YIT_GPA_PROXY")?,
+                http: parse_proxy(&args, "--http-proxy", "YIT_GPA_HTTP_PROXY")?,
+                https: parse_proxy(&args, "--https-proxy", "YIT_GPA_HTTPS_PROXY")?,
+                disabled: flag(&args, "--no-proxy", "YIT_GPA_NO_PROXY"),
+            },
+            sequential_fetch: low_memory,
+            debug_html_dir: flag(&args, "--debug-html", "YIT_GPA_DEBUG_HTML").then(|| data_dir.join(DEBUG_HTML_DIR)),
+            user_agent: if flag(&args, "--rotate-ua", "YIT_GPA_ROTATE_UA") {
+                UserAgentPolicy::RotatePerSession
+            } else {
+                UserAgentPolicy::Fixed(get_rua().to_string())
+            },
+            min_request_interval: Duration::from_millis(
+                parse_millis(&args, "--request-interval", "YIT_GPA_REQUEST_INTERVAL")?.unwrap_or(DEFAULT_REQUEST_INTERVAL_MS)
+            ),
+            request_jitter: Duration::from_millis(
+                parse_millis(&args, "--request-jitter", "YIT_GPA_REQUEST_JITTER")?.unwrap_or(DEFAULT_REQUEST_JITTER_MS)
+            ),
+            login: match parse_http_url(&args, "--cas-url", "YIT_GPA_CAS_URL")? {
+                Some(url) => LoginMethod::Cas(url),
+                None => LoginMethod::Direct
+            },
+        };
+
+        Ok(Self {
+            read_only: flag(&args, "--read-only", "YIT_GPA_READ_ONLY"),
+            scraper,
+            data_dir,
+            low_memory,
+            demo: flag(&args, "--demo", "YIT_GPA_DEMO"),
+            api_token: parse_secret(&args, "--api-token", "YIT_GPA_API_TOKEN", "API 令牌")?
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty()),
+            bind: parse_ip(&args, "--bind", "YIT_GPA_BIND")?,
+            allowed_hosts: value(&args, "--allowed-hosts", "YIT_GPA_ALLOWED_HOSTS")
+                .map(|v| v.split(',').map(|h| h.trim().to_lowercase()).filter(|h| !h.is_empty()).collect())
+                .unwrap_or_default(),
+            pdf_font: value(&args, "--pdf-font", "YIT_GPA_PDF_FONT").map(PathBuf::from),
+            max_upload_size: parse_megabytes(&args, "--max-upload-size", "YIT_GPA_MAX_UPLOAD_SIZE")?.unwrap_or(DEFAULT_MAX_UPLOAD_SIZE_MB),
+            watch_interval: Duration::from_secs(
+                parse_minutes(&args, "--watch-interval", "YIT_GPA_WATCH_INTERVAL", MIN_WATCH_INTERVAL_MINUTES)?.unwrap_or(DEFAULT_WATCH_INTERVAL_MINUTES) * 60
+            ),
+            smtp: parse_smtp(&args)?,
+        })
+    }
+
+    /// 是否允许本机以外的设备访问
+    pub fn lan_mode(&self) -> bool {
+        self.bind.is_some_and(|ip| !ip.is_loopback())
+    }
+}
+
+/// 默认数据目录: 可执行文件所在目录下的 yit-gpa-data, 获取失败则使用当前目录
+fn default_data_dir() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(DEFAULT_DATA_DIR)))
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DATA_DIR))
+}
+
+/// 读取开关型参数, 如 `--read-only` 或环境变量 `YIT_GPA_READ_ONLY=1`
+fn flag(args: &[String], name: &str, env_key: &str) -> bool {
+    if args.iter().any(|a| a == name) {
+        return true;
+    }
+
+    match env::var(env_key) {
+        Ok(v) => matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes" | "on"),
+        Err(_) => false
+    }
+}
+
+/// 读取带值的参数, 支持 `--name value` 与 `--name=value` 两种写法, 找不到时读取环境变量
+fn value(args: &[String], name: &str, env_key: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+
+    for (i, arg) in args.iter().enumerate() {
+        if arg == name {
+            return args.get(i + 1).cloned();
+        }
+        if let Some(v) = arg.strip_prefix(&prefix) {
+            return Some(v.to_string());
+        }
+    }
+
+    env::var(env_key).ok().filter(|v| !v.trim().is_empty())
+}
+
+/// 读取以秒为单位的正整数参数
+fn parse_secs(args: &[String], name: &str, env_key: &str) -> Result<Option<u64>> {
+    match value(args, name, env_key) {
+        Some(v) => match v.trim().parse::<u64>() {
+            Ok(secs) if secs > 0 => Ok(Some(secs)),
+            _ => Err(anyhow!("参数 {} 的值无效: {}, 需要为正整数(秒)", name, v))
+        },
+        None => Ok(None)
+    }
+}
+
+/// 读取以 MB 为单位的正整数参数
+fn parse_megabytes(args: &[String], name: &str, env_key: &str) -> Result<Option<usize>> {
+    match value(args, name, env_key) {
+        Some(v) => match v.trim().parse::<usize>() {
+            Ok(mb) if mb > 0 => Ok(Some(mb)),
+            _ => Err(anyhow!("参数 {} 的值无效: {}, 需要为正整数(MB)", name, v))
+        },
+        None => Ok(None)
+    }
+}
+
+/// 读取以分钟为单位的整数参数, 不能小于 min
+fn parse_minutes(args: &[String], name: &str, env_key: &str, min: u64) -> Result<Option<u64>> {
+    match value(args, name, env_key) {
+        Some(v) => match v.trim().parse::<u64>() {
+            Ok(minutes) if minutes >= min => Ok(Some(minutes)),
+            _ => Err(anyhow!("参数 {} 的值无效: {}, 需要为不小于 {} 的整数(分钟)", name, v, min))
+        },
+        None => Ok(None)
+    }
+}
+
+/// 读取以毫秒为单位的非负整数参数, 0 表示不等待
+fn parse_millis(args: &[String], name: &str, env_key: &str) -> Result<Option<u64>> {
+    match value(args, name, env_key) {
+        Some(v) => v.trim().parse::<u64>()
+            .map(Some)
+            .map_err(|_| anyhow!("参数 {} 的值无效: {}, 需要为非负整数(毫秒)", name, v)),
+        None => Ok(None)
+    }
+}
+
+/// 读取 http:// 或 https:// 开头的网址
+fn parse_http_url(args: &[String], name: &str, env_key: &str) -> Result<Option<String>> {
+    match value(args, name, env_key) {
+        Some(v) => {
+            let url = v.trim().to_string();
+            let lower = url.to_lowercase();
+            if lower.starts_with("http://") || lower.starts_with("https://") {
+                Ok(Some(url))
+            } else {
+                Err(anyhow!("参数 {} 的地址无效: {}, 需以 http:// 或 https:// 开头", name, v))
+            }
+        }
+        None => Ok(None)
+    }
+}
+
+/// 读取 SMTP 设置, 没有设置服务器地址时不发送邮件; 设置了地址时账号与密码必填
+/// 命令行参数可能被同一台电脑上的其他用户看到, 所以密码只从环境变量或文件读取
+fn parse_smtp(args: &[String]) -> Result<Option<SmtpConfig>> {
+    let Some(host) = value(args, "--smtp-host", "YIT_GPA_SMTP_HOST").map(|h| h.trim().to_string()) else {
+        return Ok(None);
+    };
+
+    let port = match value(args, "--smtp-port", "YIT_GPA_SMTP_PORT") {
+        Some(v) => v.trim().parse::<u16>()
+            .ok()
+            .filter(|port| *port > 0)
+            .ok_or_else(|| anyhow!("参数 --smtp-port 的值无效: {}, 需要为端口号, 如 465", v))?,
+        None => DEFAULT_SMTP_PORT
+    };
+    let username = value(args, "--smtp-user", "YIT_GPA_SMTP_USER")
+        .map(|u| u.trim().to_string())
+        .ok_or_else(|| anyhow!("设置了 --smtp-host 时还需要设置 --smtp-user"))?;
+    let password = parse_secret(args, "--smtp-password", "YIT_GPA_SMTP_PASSWORD", "SMTP 密码")?
+        .ok_or_else(|| anyhow!("设置了 --smtp-host 时还需要设置环境变量 YIT_GPA_SMTP_PASSWORD 或 --smtp-password-file"))?;
+    let from = value(args, "--smtp-from", "YIT_GPA_SMTP_FROM").map(|f| f.trim().to_string()).unwrap_or_else(|| username.clone());
+    let to: Vec<String> = value(args, "--smtp-to", "YIT_GPA_SMTP_TO")
+        .map(|v| v.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect())
+        .filter(|to: &Vec<String>| !to.is_empty())
+        .unwrap_or_else(|| vec![from.clone()]);
+
+    Ok(Some(SmtpConfig { host, port, username, password, from, to }))
+}
+
+/// 读取密码、令牌等机密参数: 优先使用环境变量, 其次读取 `<name>-file` 指定的文件
+/// 命令行参数会出现在进程列表与 shell 历史中, 所以不接受直接在命令行中填写
+fn parse_secret(args: &[String], name: &str, env_key: &str, label: &str) -> Result<Option<String>> {
+    if args.iter().any(|a| a == name || a.starts_with(&format!("{}=", name))) {
+        return Err(anyhow!("不支持在命令行中填写 {}, 请改用环境变量 {} 或 {}-file", name, env_key, name));
+    }
+
+    if let Some(secret) = env::var(env_key).ok().filter(|v| !v.trim().is_empty()) {
+        return Ok(Some(secret));
+    }
+
+    let Some(path) = value(args, &format!("{}-file", name), &format!("{}_FILE", env_key)) else {
+        return Ok(None);
+    };
+    let content = fs::read_to_string(path.trim()).map_err(|e| anyhow!("无法读取 {}文件 {}: {}", label, path, e))?;
+    // 文件末尾通常带有换行, 机密本身不会以换行结尾
+    let secret = content.trim_end_matches(['\r', '\n']).to_string();
+    if secret.is_empty() {
+        return Err(anyhow!("{}文件 {} 为空", label, path));
+    }
+
+    Ok(Some(secret))
+}
+
+/// 读取 IP 地址参数
+fn parse_ip(args: &[String], name: &str, env_key: &str) -> Result<Option<IpAddr>> {
+    match value(args, name, env_key) {
+        Some(v) => v.trim().parse::<IpAddr>()
+            .map(Some)
+            .map_err(|_| anyhow!("参数 {} 的值无效: {}, 需要为 IP 地址, 如 0.0.0.0", name, v)),
+        None => Ok(None)
+    }
+}
+
+/// 读取代理地址并检查协议是否受支持
+fn parse_proxy(args: &[String], name: &str, env_key: &str) -> Result<Option<String>> {
+    match value(args, name, env_key) {
+        Some(v) => {
+            let url = v.trim().to_string();
+            let supported = ["http://", "https://", "socks5://", "socks5h://"];
+            if supported.iter().any(|scheme| url.to_lowercase().starts_with(scheme)) {
+                Ok(Some(url))
+            } else {
+                Err(anyhow!("参数 {} 的代理地址无效: {}, 需以 {} 开头", name, v, supported.join(" / ")))
+            }
+        }
+        None => Ok(None)
+    }
+}
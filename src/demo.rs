@@ -0,0 +1,149 @@
+// 演示模式 - 不连接教务系统, 返回固定的模拟成绩
+use crate::{
+    business::{parsed_grade, print_info},
+    models::{Course, CourseNature, GradeProbe, Score, StudentProfile, WebScrapingError},
+    profile::GradingTable,
+    scraping::GradeSource
+};
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+// 模拟数据: (学期, 课程编号, 课程名称, 课程性质, 成绩, 学分, 考试性质)
+// 覆盖了百分制、等级制、补考、免修、排除关键字与排除性质等情况
+type DemoCourse = (&'static str, &'static str, &'static str, &'static str, &'static str, Decimal, &'static str);
+
+const DEMO_COURSES: &[DemoCourse] = &[
+    ("2023-2024-1", "DEMO1001", "高等数学A(一)", "必修课", "86", dec!(5), "正常考试"),
+    ("2023-2024-1", "DEMO1002", "大学英语(一)", "必修课", "78", dec!(3), "正常考试"),
+    ("2023-2024-1", "DEMO1003", "程序设计基础", "必修课", "92", dec!(3.5), "正常考试"),
+    ("2023-2024-1", "DEMO1004", "思想道德与法治", "必修课", "良", dec!(3), "正常考试"),
+    ("2023-2024-1", "DEMO1005", "体育(一)", "必修课", "优", dec!(1), "正常考试"),
+    ("2023-2024-1", "DEMO1006", "军事理论", "必修课", "合格", dec!(2), "正常考试"),
+    ("2023-2024-1", "DEMO1007", "入学教育", "必修课", "合格", dec!(0.5), "正常考试"),
+    ("2023-2024-2", "DEMO1008", "高等数学A(二)", "必修课", "58", dec!(5), "正常考试"),
+    ("2023-2024-2", "DEMO1008", "高等数学A(二)", "必修课", "72", dec!(5), "补考"),
+    ("2023-2024-2", "DEMO1009", "线性代数", "必修课", "81", dec!(3), "正常考试"),
+    ("2023-2024-2", "DEMO1010", "数据结构", "必修课", "88", dec!(4), "正常考试"),
+    ("2023-2024-2", "DEMO1011", "电影鉴赏", "公共选修课", "95", dec!(2), "正常考试"),
+    ("2023-2024-2", "DEMO1012", "形势与政策", "必修课", "中", dec!(0.5), "正常考试"),
+    ("2023-2024-2", "DEMO1013", "大学计算机基础", "必修课", "免修", dec!(2), "正常考试"),
+];
+
+// 模拟的辅修课程, 格式同上
+const DEMO_MINOR_COURSES: &[DemoCourse] = &[
+    ("2023-2024-2", "DEMO9001", "会计学原理", "辅修课", "84", dec!(3), "正常考试"),
+    ("2023-2024-2", "DEMO9002", "经济学基础", "辅修课", "79", dec!(2), "正常考试"),
+];
+
+// 模拟的任课教师与每学分学时, 考核方式按成绩类型模拟: 百分制为考试, 等级制为考查
+const DEMO_TEACHER: &str = "演示教师";
+const DEMO_HOURS_PER_CREDIT: Decimal = dec!(16);
+
+// 以该账号登录时模拟还没有任何成绩的新生
+const DEMO_FRESHMAN_ACCOUNT: &str = "freshman";
+
+// 演示模式的数据来源, 任意账号密码都能登录
+pub struct DemoSource {
+    freshman: bool,     // 模拟没有成绩的新生
+    grading: GradingTable,  // 与真实数据使用同一张换算表
+}
+
+impl DemoSource {
+    pub fn new(grading: &GradingTable) -> Self {
+        Self { freshman: false, grading: grading.clone() }
+    }
+}
+
+impl GradeSource for DemoSource {
+    async fn sign_in(&mut self, account: &str, _password: &str) -> Result<(), WebScrapingError> {
+        // 空账号仍然视为登录失败, 便于演示错误提示
+        if account.trim().is_empty() {
+            return Err(WebScrapingError::LoginFailed);
+        }
+        self.freshman = account.trim() == DEMO_FRESHMAN_ACCOUNT;
+
+        print_info(&format!("演示模式: 账号 {} 登录成功, 将返回模拟成绩", account));
+        Ok(())
+    }
+
+    async fn fetch_grades(&mut self, semesters: &[String]) -> Result<Vec<Course>, WebScrapingError> {
+        if self.freshman {
+            return Ok(Vec::new());
+        }
+
+        let courses = demo_courses(DEMO_COURSES, false, &self.grading)
+            .into_iter()
+            .filter(|c| semesters.is_empty() || semesters.contains(&c.semester))
+            .collect::<Vec<_>>();
+
+        Ok(courses)
+    }
+
+    async fn fetch_official_gpa(&self) -> Result<Option<Decimal>, WebScrapingError> {
+        Ok(Some(dec!(3.21)))
+    }
+
+    async fn fetch_minor_grades(&mut self, semesters: &[String]) -> Result<Vec<Course>, WebScrapingError> {
+        if self.freshman {
+            return Ok(Vec::new());
+        }
+
+        let courses = demo_courses(DEMO_MINOR_COURSES, true, &self.grading)
+            .into_iter()
+            .filter(|c| semesters.is_empty() || semesters.contains(&c.semester))
+            .collect::<Vec<_>>();
+
+        Ok(courses)
+    }
+
+    async fn fetch_student_profile(&self) -> Result<Option<StudentProfile>, WebScrapingError> {
+        Ok(Some(StudentProfile {
+            name: "演示同学".to_string(),
+            student_id: "2023000001".to_string(),
+            major: "计算机科学与技术".to_string(),
+            class_name: "计科2301".to_string(),
+            enrollment_year: Some(2023),
+        }))
+    }
+
+    async fn fetch_semesters(&self) -> Result<Vec<String>, WebScrapingError> {
+        let semesters = ["2025-2026-1", "2024-2025-2", "2024-2025-1", "2023-2024-2", "2023-2024-1"];
+        Ok(semesters.iter().map(|s| s.to_string()).collect())
+    }
+
+    async fn probe_grades(&self) -> Result<GradeProbe, WebScrapingError> {
+        let mut semesters: Vec<String> = DEMO_COURSES.iter().map(|c| c.0.to_string()).collect();
+        semesters.dedup();
+
+        Ok(GradeProbe { page_count: 1, first_page_rows: DEMO_COURSES.len(), semesters })
+    }
+}
+
+/// 把模拟数据转换为课程列表, 绩点按与真实数据相同的规则计算
+fn demo_courses(list: &[DemoCourse], minor: bool, grading: &GradingTable) -> Vec<Course> {
+    list
+        .iter()
+        .filter_map(|&(semester, code, name, nature, score, credit, exam_type)| {
+            let score = Score::from(score);
+            let grade = parsed_grade(&score, grading)?;
+            let assessment = if matches!(score, Score::Numeric(_)) { "考试" } else { "考查" };
+            Some(Course {
+                course_code: code.to_string(),
+                semester: semester.to_string(),
+                name: name.to_string(),
+                nature: CourseNature::from(nature),
+                score,
+                credit,
+                grade,
+                credit_gpa: grading.rounding.round(grade * credit),
+                exam_type: exam_type.to_string(),
+                teacher: DEMO_TEACHER.to_string(),
+                hours: (credit * DEMO_HOURS_PER_CREDIT).normalize().to_string(),
+                assessment: assessment.to_string(),
+                minor,
+                source: String::new(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,158 @@
+// CSV 导出, 末尾附带签名时间与校验码, 可以通过"校验导出文件"检查是否被修改过
+use super::{mode_label, ExportLocale, ExportSigner, Exporter, Snapshot, VerifyResult, INTEGRITY_LABEL, SIGNED_AT_LABEL};
+use crate::business::letter_grade;
+
+use anyhow::Result;
+
+const UTF8_BOM: char = '\u{feff}';
+
+pub struct CsvExporter;
+
+impl Exporter for CsvExporter {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn mime(&self) -> &'static str {
+        "text/csv; charset=utf-8"
+    }
+
+    fn extension(&self) -> &'static str {
+        "csv"
+    }
+
+    /// 以 UTF-8 BOM 开头, 否则中文版 Excel 会按 GBK 打开而显示乱码; BOM 不计入校验码
+    fn render(&self, snapshot: &Snapshot) -> Result<Vec<u8>> {
+        Ok(format!("{}{}", UTF8_BOM, render_csv(snapshot)).into_bytes())
+    }
+}
+
+/// 生成 CSV 文本
+fn render_csv(snapshot: &Snapshot) -> String {
+    let locale = snapshot.locale;
+    let delimiter = csv_delimiter(locale);
+    let mut lines: Vec<String> = Vec::new();
+
+    // 学生信息放在表格开头, 与课程数据之间空一行
+    if let Some(student) = &snapshot.student {
+        let rows = [
+            ("姓名", student.name.clone()),
+            ("学号", student.student_id.clone()),
+            ("专业", student.major.clone()),
+            ("班级", student.class_name.clone()),
+            ("入学年份", student.enrollment_year.map(|year| year.to_string()).unwrap_or_default()),
+        ];
+        for (label, value) in rows.into_iter().filter(|(_, value)| !value.is_empty()) {
+            lines.push(join_csv_row([label.to_string(), value].into_iter(), delimiter));
+        }
+        lines.push(String::new());
+    }
+
+    let header = ["序号", "学期", "课程编号", "课程", "课程性质", "学分", "成绩", "绩点", "加权绩点", "等级", "考试性质", "学时", "考核方式", "任课教师"];
+    lines.push(join_csv_row(header.iter().map(|h| h.to_string()), delimiter));
+
+    for (index, course) in snapshot.courses.iter().enumerate() {
+        let row = [
+            (index + 1).to_string(),
+            course.semester.clone(),
+            course.course_code.clone(),
+            course.name.clone(),
+            course.nature.to_string(),
+            locale.format_decimal(course.credit),
+            course.score.to_string(),
+            locale.format_decimal(course.grade),
+            locale.format_decimal(course.credit_gpa),
+            letter_grade(course.grade).to_string(),
+            course.exam_type.clone(),
+            course.hours.clone(),
+            course.assessment.clone(),
+            course.teacher.clone(),
+        ];
+        lines.push(join_csv_row(row.into_iter(), delimiter));
+    }
+
+    // 汇总信息放在表格末尾, 与课程数据之间空一行
+    lines.push(String::new());
+    lines.push(join_csv_row(["平均绩点".to_string(), locale.format_decimal(snapshot.gpa)].into_iter(), delimiter));
+    if let (Some(weighted), Some(arithmetic)) = (snapshot.averages.weighted, snapshot.averages.arithmetic) {
+        lines.push(join_csv_row(["加权平均分".to_string(), locale.format_decimal(weighted)].into_iter(), delimiter));
+        lines.push(join_csv_row(["算术平均分".to_string(), locale.format_decimal(arithmetic)].into_iter(), delimiter));
+    }
+    lines.push(join_csv_row(["计算模式".to_string(), mode_label(&snapshot.mode).to_string()].into_iter(), delimiter));
+    lines.push(join_csv_row(["导出时间".to_string(), locale.format_datetime(&snapshot.generated_at)].into_iter(), delimiter));
+
+    // 计算依据, 按这些条件可以复现上面的平均绩点
+    if let Some(provenance) = &snapshot.provenance {
+        let rows = [
+            ("数据来源", provenance.source.clone()),
+            ("获取时间", locale.format_datetime(&provenance.fetched_at)),
+            ("手动修改", if provenance.edited { "是" } else { "否" }.to_string()),
+            ("学校规则", provenance.profile.clone()),
+            ("换算标准", provenance.scale.to_string()),
+            ("取整规则", provenance.rounding.to_string()),
+            ("重复课程", provenance.dedup.to_string()),
+        ];
+        for (label, value) in rows {
+            lines.push(join_csv_row([label.to_string(), value].into_iter(), delimiter));
+        }
+    }
+
+    // 校验码覆盖前面的全部内容(包括签名时间), 作为最后一行
+    lines.push(join_csv_row([SIGNED_AT_LABEL.to_string(), snapshot.generated_at.to_rfc3339()].into_iter(), delimiter));
+    let body = lines.join("\r\n");
+    let hash = snapshot.signer.sign(body.as_bytes());
+    format!("{}\r\n{}{}{}", body, INTEGRITY_LABEL, delimiter, hash)
+}
+
+/// 校验导出的 CSV 文件, 校验码位于最后一行"校验码,xxxx"
+pub(super) fn verify(data: &[u8], signer: &ExportSigner) -> VerifyResult {
+    let text = String::from_utf8_lossy(data);
+    let text = text.trim_start_matches(UTF8_BOM).trim_end_matches(['\r', '\n']);
+
+    // 兼容被其他软件另存后换行符变为 \n 的情况
+    let (body, last_line) = match text.rfind('\n') {
+        Some(i) => (text[..i].trim_end_matches('\r'), &text[i + 1..]),
+        None => ("", text)
+    };
+
+    let Some(recorded_hash) = last_line.strip_prefix(INTEGRITY_LABEL).map(field_value) else {
+        return VerifyResult::unsigned("csv");
+    };
+    let signed_at = body.lines().rev().find_map(|line| line.strip_prefix(SIGNED_AT_LABEL)).map(field_value);
+
+    let body = body.replace("\r\n", "\n").replace('\n', "\r\n");
+    VerifyResult {
+        valid: signer.verify(body.as_bytes(), &recorded_hash),
+        format: "csv",
+        recorded_hash: Some(recorded_hash),
+        signed_at,
+        signed_content: None,
+    }
+}
+
+/// CSV 分隔符, 小数点为逗号时 Excel 默认以分号分列
+fn csv_delimiter(locale: ExportLocale) -> char {
+    match locale.decimal_separator() {
+        ',' => ';',
+        _ => ','
+    }
+}
+
+/// 标签之后的字段值, 如",xxxx"中的 xxxx
+fn field_value(rest: &str) -> String {
+    rest.trim_start_matches([',', ';']).trim().trim_matches('"').to_string()
+}
+
+/// 拼接一行 CSV, 字段中含有分隔符、引号或换行时用引号包裹
+fn join_csv_row(fields: impl Iterator<Item = String>, delimiter: char) -> String {
+    fields
+        .map(|field| {
+            if field.contains(delimiter) || field.contains('"') || field.contains('\n') || field.contains('\r') {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(&delimiter.to_string())
+}
@@ -0,0 +1,112 @@
+// 导出任务 - 在阻塞线程池中生成文件, 生成较大的文件时不会占用处理其他请求的异步线程
+// 前端先创建任务, 再轮询任务状态, 完成后下载
+use super::{Exporter, Snapshot};
+use crate::business::print_error;
+
+use anyhow::Result;
+use rand::Rng;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant}
+};
+
+const JOB_RETAIN: Duration = Duration::from_secs(600);  // 任务创建后保留的时间, 超时未下载的文件会被清除
+
+// 生成好的文件
+pub struct ExportFile {
+    pub body: Vec<u8>,
+    pub mime: &'static str,
+    pub file_name: String,
+}
+
+// 任务状态, 返回给前端轮询
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Done,
+    Failed { error: String },
+}
+
+enum JobState {
+    Running,
+    Done(ExportFile),
+    Failed(String),
+}
+
+struct Job {
+    state: JobState,
+    created_at: Instant,
+}
+
+// 正在进行或等待下载的导出任务, 以随机生成的任务 ID 区分
+#[derive(Default)]
+pub struct ExportJobs {
+    jobs: Mutex<HashMap<String, Job>>,
+}
+
+impl ExportJobs {
+    /// 开始一个导出任务, 返回任务 ID
+    pub fn start(self: &Arc<Self>, exporter: Arc<dyn Exporter>, snapshot: Snapshot) -> String {
+        let id = format!("{:032x}", rand::rng().random::<u128>());
+
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.retain(|_, job| job.created_at.elapsed() < JOB_RETAIN);
+            jobs.insert(id.clone(), Job { state: JobState::Running, created_at: Instant::now() });
+        }
+
+        let store = self.clone();
+        let job_id = id.clone();
+        tokio::spawn(async move {
+            let state = match render(exporter, snapshot).await {
+                Ok(file) => JobState::Done(file),
+                Err(e) => {
+                    print_error(&format!("导出任务 {} 失败: {}", job_id, e));
+                    JobState::Failed(e.to_string())
+                }
+            };
+
+            if let Some(job) = store.jobs.lock().unwrap().get_mut(&job_id) {
+                job.state = state;
+            }
+        });
+
+        id
+    }
+
+    /// 查询任务状态, 任务不存在或已过期时返回 None
+    pub fn status(&self, id: &str) -> Option<JobStatus> {
+        let jobs = self.jobs.lock().unwrap();
+
+        jobs.get(id).map(|job| match &job.state {
+            JobState::Running => JobStatus::Running,
+            JobState::Done(_) => JobStatus::Done,
+            JobState::Failed(error) => JobStatus::Failed { error: error.clone() }
+        })
+    }
+
+    /// 取出已完成的文件, 取出后任务即被移除; 任务未完成时返回 None
+    pub fn take(&self, id: &str) -> Option<ExportFile> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if !matches!(jobs.get(id)?.state, JobState::Done(_)) {
+            return None;
+        }
+
+        match jobs.remove(id)?.state {
+            JobState::Done(file) => Some(file),
+            _ => None
+        }
+    }
+}
+
+/// 在阻塞线程池中生成文件
+pub async fn render(exporter: Arc<dyn Exporter>, snapshot: Snapshot) -> Result<ExportFile> {
+    let file_name = format!("GPA-{}.{}", snapshot.generated_at.format("%Y%m%d%H%M%S"), exporter.extension());
+    let mime = exporter.mime();
+    let body = tokio::task::spawn_blocking(move || exporter.render(&snapshot)).await??;
+
+    Ok(ExportFile { body, mime, file_name })
+}
@@ -0,0 +1,46 @@
+// JSON 导出, 原样输出全部模式的计算结果, 供其他程序读取而不必解析结果页
+use super::{Exporter, Snapshot};
+use crate::{business::{ProcessedGPAResults, Provenance}, models::StudentProfile};
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+pub struct JsonExporter;
+
+// 导出的 JSON 结构, 计算结果的字段与 ProcessedGPAResults 相同, 另附导出时间、学生信息与计算依据
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    generated_at: &'a DateTime<Local>,
+    mode: &'a str,  // 导出时结果页的计算模式
+    student: &'a Option<StudentProfile>,
+    provenance: &'a Option<Provenance>,
+    #[serde(flatten)]
+    results: &'a ProcessedGPAResults,
+}
+
+impl Exporter for JsonExporter {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn mime(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    /// 小数以字符串输出, 避免其他程序按浮点数读取时损失精度
+    fn render(&self, snapshot: &Snapshot) -> Result<Vec<u8>> {
+        let report = JsonReport {
+            generated_at: &snapshot.generated_at,
+            mode: &snapshot.mode,
+            student: &snapshot.student,
+            provenance: &snapshot.provenance,
+            results: &snapshot.results,
+        };
+        Ok(serde_json::to_vec_pretty(&report)?)
+    }
+}
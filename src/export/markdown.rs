@@ -0,0 +1,69 @@
+// Markdown 导出, 课程表格与绩点汇总, 方便贴到 Notion、知乎或论坛的帖子中
+// 帖子通常是公开的, 不写入姓名、学号等学生信息
+use super::{mode_label, Exporter, Snapshot};
+
+use anyhow::Result;
+use rust_decimal::Decimal;
+
+pub struct MarkdownExporter;
+
+impl Exporter for MarkdownExporter {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn mime(&self) -> &'static str {
+        "text/markdown; charset=utf-8"
+    }
+
+    fn extension(&self) -> &'static str {
+        "md"
+    }
+
+    fn render(&self, snapshot: &Snapshot) -> Result<Vec<u8>> {
+        Ok(render_markdown(snapshot).into_bytes())
+    }
+}
+
+/// 生成 Markdown 文本
+fn render_markdown(snapshot: &Snapshot) -> String {
+    let locale = snapshot.locale;
+    let mut lines = vec![
+        "## 绩点汇总".to_string(),
+        String::new(),
+        format!("- 平均绩点：**{}**（{}）", locale.format_decimal(snapshot.gpa), mode_label(&snapshot.mode)),
+    ];
+    if let (Some(weighted), Some(arithmetic)) = (snapshot.averages.weighted, snapshot.averages.arithmetic) {
+        lines.push(format!("- 加权平均分：{}", locale.format_decimal(weighted)));
+        lines.push(format!("- 算术平均分：{}", locale.format_decimal(arithmetic)));
+    }
+    let total_credit: Decimal = snapshot.courses.iter().map(|c| c.credit).sum();
+    lines.push(format!("- 课程数：{}，总学分：{}", snapshot.courses.len(), locale.format_decimal(total_credit)));
+
+    lines.push(String::new());
+    lines.push("## 课程成绩".to_string());
+    lines.push(String::new());
+    lines.push("| 学期 | 课程 | 课程性质 | 学分 | 成绩 | 绩点 |".to_string());
+    lines.push("| --- | --- | --- | ---: | ---: | ---: |".to_string());
+    for course in &snapshot.courses {
+        let cells = [
+            course.semester.clone(),
+            course.name.clone(),
+            course.nature.to_string(),
+            locale.format_decimal(course.credit),
+            course.score.to_string(),
+            locale.format_decimal(course.grade),
+        ];
+        lines.push(format!("| {} |", cells.iter().map(|c| escape_cell(c)).collect::<Vec<_>>().join(" | ")));
+    }
+
+    lines.push(String::new());
+    lines.push(format!("> 导出时间：{}", locale.format_datetime(&snapshot.generated_at)));
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// 单元格中的 | 会被当作列分隔符, 换行会截断表格
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
@@ -0,0 +1,204 @@
+// 导出层 - 把计算结果转换为可下载的文件
+// 每种导出格式是一个实现了 Exporter 的子模块, 在 ExporterRegistry::new 中注册后即可通过 /export/{format} 下载
+mod csv;
+mod jobs;
+mod json;
+mod markdown;
+mod pdf;
+mod signature;
+mod xlsx;
+
+pub use jobs::{render, ExportFile, ExportJobs};
+pub use pdf::PdfExporter;
+pub use signature::ExportSigner;
+
+use crate::{business::{ProcessedGPAResults, Provenance, ScoreAverages}, models::{Course, StudentProfile}};
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, sync::Arc};
+
+// 导出文件中校验码与签名时间所在行的标签
+pub const INTEGRITY_LABEL: &str = "校验码";
+pub const SIGNED_AT_LABEL: &str = "签名时间";
+
+// 导出时使用的地区格式, 决定小数点与日期的写法
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum ExportLocale {
+    #[default]
+    #[serde(rename = "zh-CN")]
+    ZhCn,   // 2024-01-31 13:45, 小数点为 .
+    #[serde(rename = "en-US")]
+    EnUs,   // 01/31/2024 01:45 PM, 小数点为 .
+    #[serde(rename = "de-DE")]
+    DeDe,   // 31.01.2024 13:45, 小数点为 , (多数欧洲地区的 Excel 使用此格式)
+}
+
+impl ExportLocale {
+    /// 小数点符号
+    pub fn decimal_separator(&self) -> char {
+        match self {
+            ExportLocale::ZhCn | ExportLocale::EnUs => '.',
+            ExportLocale::DeDe => ','
+        }
+    }
+
+    /// 日期时间格式
+    pub fn datetime_pattern(&self) -> &'static str {
+        match self {
+            ExportLocale::ZhCn => "%Y-%m-%d %H:%M:%S",
+            ExportLocale::EnUs => "%m/%d/%Y %I:%M:%S %p",
+            ExportLocale::DeDe => "%d.%m.%Y %H:%M:%S"
+        }
+    }
+
+    /// 按地区格式化小数
+    pub fn format_decimal(&self, d: Decimal) -> String {
+        self.localize(d.normalize().to_string())
+    }
+
+    /// 按地区格式化小数, 保留固定的小数位数, 如绩点 4.00
+    pub fn format_fixed(&self, d: Decimal, dp: usize) -> String {
+        self.localize(format!("{:.*}", dp, d))
+    }
+
+    fn localize(&self, text: String) -> String {
+        match self.decimal_separator() {
+            '.' => text,
+            sep => text.replace('.', &sep.to_string())
+        }
+    }
+
+    /// 按地区格式化时间
+    pub fn format_datetime(&self, t: &DateTime<Local>) -> String {
+        t.format(self.datetime_pattern()).to_string()
+    }
+}
+
+// 导出选项, 来自请求的查询参数
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExportOptions {
+    #[serde(default)]
+    pub mode: String,   // default、all 或 compulsory, 与结果页的计算模式一致
+    #[serde(default)]
+    pub locale: ExportLocale,   // 只影响给人阅读的格式, JSON 始终使用标准格式
+}
+
+// 一次导出所需的全部数据
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub gpa: Decimal,
+    pub averages: ScoreAverages,
+    pub courses: Vec<Course>,
+    pub mode: String,
+    pub generated_at: DateTime<Local>,
+    pub locale: ExportLocale,
+    pub provenance: Option<Provenance>, // 计算依据, 写入导出文件以便复现
+    pub student: Option<StudentProfile>,    // 学生信息, 免登录模式下为空
+    pub results: ProcessedGPAResults,   // 全部模式的计算结果, 上面的绩点与课程为其中当前模式的部分
+    pub signer: Arc<ExportSigner>,  // 为导出文件计算校验码
+}
+
+// 一种导出格式
+pub trait Exporter: Send + Sync {
+    /// 格式名称, 即 /export/{format} 中的 format
+    fn name(&self) -> &'static str;
+
+    /// 下载时的 Content-Type
+    fn mime(&self) -> &'static str;
+
+    /// 下载文件的扩展名
+    fn extension(&self) -> &'static str;
+
+    /// 生成文件内容
+    fn render(&self, snapshot: &Snapshot) -> Result<Vec<u8>>;
+}
+
+// 所有可用的导出格式, 以格式名称查找
+pub struct ExporterRegistry {
+    exporters: BTreeMap<&'static str, Arc<dyn Exporter>>,
+    signer: Arc<ExportSigner>,
+}
+
+impl ExporterRegistry {
+    /// 内置的全部导出格式, 导出文件以给定的密钥签名
+    pub fn new(signer: ExportSigner) -> Self {
+        let mut registry = Self { exporters: BTreeMap::new(), signer: Arc::new(signer) };
+        registry.register(csv::CsvExporter);
+        registry.register(json::JsonExporter);
+        registry.register(markdown::MarkdownExporter);
+        registry.register(pdf::PdfExporter::default());
+        registry.register(xlsx::XlsxExporter);
+        registry
+    }
+
+    /// 注册一种导出格式, 同名格式会被替换
+    pub fn register(&mut self, exporter: impl Exporter + 'static) {
+        self.exporters.insert(exporter.name(), Arc::new(exporter));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Exporter>> {
+        self.exporters.get(name).cloned()
+    }
+
+    /// 已注册的格式名称, 按字母顺序
+    pub fn names(&self) -> Vec<&'static str> {
+        self.exporters.keys().copied().collect()
+    }
+
+    pub fn signer(&self) -> Arc<ExportSigner> {
+        self.signer.clone()
+    }
+}
+
+// 导出文件的校验结果
+// 不返回按文件内容重新计算的校验码, 否则修改文件后上传一次就能得到新的校验码
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyResult {
+    pub valid: bool,    // 内容与校验码是否一致
+    pub format: &'static str,   // 按文件内容识别的格式: csv、xlsx 或 pdf
+    pub recorded_hash: Option<String>,  // 文件中记录的校验码, 找不到时为空
+    pub signed_at: Option<String>,  // 文件中记录的签名时间
+    pub signed_content: Option<String>, // PDF 中签名的成绩摘要, 校验通过时可与页面上的文字对照
+}
+
+impl VerifyResult {
+    /// 文件中没有找到校验码
+    fn unsigned(format: &'static str) -> Self {
+        Self { valid: false, format, recorded_hash: None, signed_at: None, signed_content: None }
+    }
+}
+
+/// 计算内容的短哈希值(SHA-256 的前 16 位十六进制), 用于区分内容, 如账号或重复上传的文件
+/// 不带密钥, 导出文件的校验码由 ExportSigner 计算
+pub fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 校验导出的文件, 按文件头识别 xlsx 与 PDF, 其余按 CSV 校验
+pub fn verify_export(data: &[u8], signer: &ExportSigner) -> VerifyResult {
+    if data.starts_with(b"%PDF") {
+        pdf::verify(data, signer)
+    } else if data.starts_with(b"PK\x03\x04") {
+        xlsx::verify(data, signer)
+    } else {
+        csv::verify(data, signer)
+    }
+}
+
+/// 计算模式的中文名称
+pub fn mode_label(mode: &str) -> &'static str {
+    match mode {
+        "all" => "全部课程",
+        "compulsory" => "仅必修课",
+        "filter" => "自定义筛选",
+        _ => "默认(排除部分课程)"
+    }
+}
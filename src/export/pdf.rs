@@ -0,0 +1,333 @@
+// PDF 成绩报告, 仿照成绩单的版式, 可以直接附在奖学金等申请材料中
+// PDF 的内置字体不含中文, 需要嵌入系统中的中文字体; 找不到时可以用 --pdf-font 指定 TrueType 字体文件
+// PDF 中的文字无法可靠地提取, 校验码只覆盖一份成绩摘要; 摘要以注释的形式写在文件末尾, 校验时返回摘要供与页面上的文字对照
+use super::{mode_label, ExportSigner, Exporter, Snapshot, VerifyResult, INTEGRITY_LABEL, SIGNED_AT_LABEL};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use printpdf::{IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point};
+use std::{fs::File, path::{Path, PathBuf}};
+
+// 常见系统中的中文 TrueType 字体, 按顺序使用第一个存在的字体
+// printpdf 只能嵌入 TrueType 轮廓的字体, 思源黑体等 OpenType(CFF) 字体无法使用
+const SYSTEM_FONTS: [&str; 7] = [
+    r"C:\Windows\Fonts\simhei.ttf",
+    r"C:\Windows\Fonts\msyh.ttc",
+    r"C:\Windows\Fonts\simsun.ttc",
+    "/System/Library/Fonts/STHeiti Medium.ttc",
+    "/Library/Fonts/Arial Unicode.ttf",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/wenquanyi/wqy-microhei/wqy-microhei.ttc",
+];
+
+// A4 纸张与页边距(毫米)
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+
+// 字号(磅)与行高(毫米)
+const TITLE_SIZE: f32 = 18.0;
+const TEXT_SIZE: f32 = 10.0;
+const TABLE_SIZE: f32 = 9.0;
+const ROW_HEIGHT: f32 = 6.0;
+
+// 课程表格的列标题与列宽(毫米), 总宽度为页面宽度减去两侧页边距
+const COLUMNS: [(&str, f32); 8] = [
+    ("序号", 10.0), ("学期", 26.0), ("课程", 60.0), ("课程性质", 22.0),
+    ("学分", 12.0), ("成绩", 16.0), ("绩点", 12.0), ("考试性质", 22.0),
+];
+
+// 1 磅 = 0.3528 毫米
+const PT_TO_MM: f32 = 0.3528;
+
+// 文件末尾签名注释的前缀, 之后为 base64 编码的成绩摘要与校验码, 以空格分隔
+const SIGNATURE_MARKER: &[u8] = b"%YIT-GPA-SIGNATURE ";
+
+#[derive(Default)]
+pub struct PdfExporter {
+    font: Option<PathBuf>,  // 指定的字体文件, 为空时在系统字体中查找
+}
+
+impl PdfExporter {
+    pub fn new(font: Option<PathBuf>) -> Self {
+        Self { font }
+    }
+
+    /// 使用的字体文件
+    fn font_path(&self) -> Result<PathBuf> {
+        if let Some(font) = &self.font {
+            return Ok(font.clone());
+        }
+        SYSTEM_FONTS
+            .iter()
+            .map(Path::new)
+            .find(|path| path.is_file())
+            .map(Path::to_path_buf)
+            .ok_or_else(|| anyhow!("未找到可用的中文字体, 请使用 --pdf-font 指定 TrueType 字体文件(.ttf/.ttc)"))
+    }
+}
+
+impl Exporter for PdfExporter {
+    fn name(&self) -> &'static str {
+        "pdf"
+    }
+
+    fn mime(&self) -> &'static str {
+        "application/pdf"
+    }
+
+    fn extension(&self) -> &'static str {
+        "pdf"
+    }
+
+    fn render(&self, snapshot: &Snapshot) -> Result<Vec<u8>> {
+        let font_path = self.font_path()?;
+        let (doc, page, layer) = PdfDocument::new("成绩报告", Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "成绩");
+        let font = doc
+            .add_external_font(File::open(&font_path).map_err(|e| anyhow!("无法打开字体文件 {}: {}", font_path.display(), e))?)?;
+
+        let summary = signed_summary(snapshot);
+        let hash = snapshot.signer.sign(summary.as_bytes());
+
+        let mut report = Report { doc: &doc, layer: doc.get_page(page).get_layer(layer), font, y: PAGE_HEIGHT - MARGIN, page: 1 };
+        report.write_page_number();
+        report.write_header(snapshot);
+        report.write_courses(snapshot);
+        report.write_footer(snapshot, &hash);
+
+        Ok(embed_signature(doc.save_to_bytes()?, &summary, &hash))
+    }
+}
+
+/// 校验码覆盖的成绩摘要: 签名时间、学生、各模式平均绩点与每门课程的成绩
+fn signed_summary(snapshot: &Snapshot) -> String {
+    let mut lines = vec![format!("{}：{}", SIGNED_AT_LABEL, snapshot.generated_at.to_rfc3339())];
+    if let Some(student) = &snapshot.student {
+        lines.push(format!("学生：{} {}", student.name, student.student_id));
+    }
+    lines.push(format!("计算模式：{}", mode_label(&snapshot.mode)));
+
+    let results = &snapshot.results;
+    let mode_gpas = results.default.iter().map(|r| ("default", r.gpa))
+        .chain([("all", results.all.gpa), ("compulsory", results.compulsory.gpa)]);
+    for (mode, gpa) in mode_gpas {
+        lines.push(format!("平均绩点 · {}：{}", mode_label(mode), gpa));
+    }
+
+    lines.push(format!("课程数：{}", snapshot.courses.len()));
+    for course in &snapshot.courses {
+        lines.push(format!(
+            "{} {} {} 学分 {} {:.2}", course.semester, course.name, course.credit.normalize(), course.score, course.grade
+        ));
+    }
+    lines.join("\n")
+}
+
+/// 把签名注释插入到 startxref 之前, PDF 阅读器忽略注释, 也不影响交叉引用表中的偏移量
+fn embed_signature(mut pdf: Vec<u8>, summary: &str, hash: &str) -> Vec<u8> {
+    let mut line = SIGNATURE_MARKER.to_vec();
+    line.extend_from_slice(format!("{} {}\n", STANDARD.encode(summary), hash).as_bytes());
+
+    let position = pdf.windows(b"startxref".len()).rposition(|w| w == b"startxref").unwrap_or(pdf.len());
+    pdf.splice(position..position, line);
+    pdf
+}
+
+/// 校验导出的 PDF 文件, 校验通过时返回签名的成绩摘要
+pub(super) fn verify(data: &[u8], signer: &ExportSigner) -> VerifyResult {
+    let Some(start) = data.windows(SIGNATURE_MARKER.len()).rposition(|w| w == SIGNATURE_MARKER) else {
+        return VerifyResult::unsigned("pdf");
+    };
+    let rest = &data[start + SIGNATURE_MARKER.len()..];
+    let line = String::from_utf8_lossy(&rest[..rest.iter().position(|&b| b == b'\n' || b == b'\r').unwrap_or(rest.len())]);
+    let Some((encoded, recorded_hash)) = line.split_once(' ') else {
+        return VerifyResult::unsigned("pdf");
+    };
+
+    let summary = STANDARD.decode(encoded).ok().and_then(|bytes| String::from_utf8(bytes).ok());
+    let valid = summary.as_ref().is_some_and(|summary| signer.verify(summary.as_bytes(), recorded_hash));
+    let signed_at = summary.as_ref()
+        .and_then(|summary| summary.lines().next()?.strip_prefix(SIGNED_AT_LABEL)?.strip_prefix('：').map(str::to_string));
+
+    VerifyResult {
+        valid,
+        format: "pdf",
+        recorded_hash: Some(recorded_hash.trim().to_string()),
+        signed_at,
+        signed_content: summary.filter(|_| valid),   // 校验未通过的摘要可能是伪造的, 不返回
+    }
+}
+
+// 正在排版的报告, y 为下一行文字的纵坐标(PDF 的原点在页面左下角)
+struct Report<'a> {
+    doc: &'a PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: IndirectFontRef,
+    y: f32,
+    page: usize,
+}
+
+impl Report<'_> {
+    /// 标题、学生信息与各计算模式的平均绩点
+    fn write_header(&mut self, snapshot: &Snapshot) {
+        self.text("成绩报告", TITLE_SIZE, MARGIN);
+        self.y -= 12.0;
+
+        if let Some(student) = &snapshot.student {
+            let fields = [
+                ("姓名", student.name.clone()),
+                ("学号", student.student_id.clone()),
+                ("专业", student.major.clone()),
+                ("班级", student.class_name.clone()),
+                ("入学年份", student.enrollment_year.map(|year| year.to_string()).unwrap_or_default()),
+            ];
+            let line = fields
+                .iter()
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(label, value)| format!("{}：{}", label, value))
+                .collect::<Vec<_>>()
+                .join("    ");
+            self.text(&line, TEXT_SIZE, MARGIN);
+            self.y -= ROW_HEIGHT + 2.0;
+        }
+
+        // 当前模式之外的平均绩点也一并列出, 申请材料要求的口径可能不同
+        let locale = snapshot.locale;
+        let results = &snapshot.results;
+        let mode_gpas = results.default.iter().map(|r| ("default", r.gpa))
+            .chain([("all", results.all.gpa), ("compulsory", results.compulsory.gpa)]);
+        for (mode, gpa) in mode_gpas {
+            let marker = if mode == snapshot.mode { "（本报告课程）" } else { "" };
+            self.text(&format!("平均绩点 · {}：{}{}", mode_label(mode), locale.format_decimal(gpa), marker), TEXT_SIZE, MARGIN);
+            self.y -= ROW_HEIGHT;
+        }
+        if let (Some(weighted), Some(arithmetic)) = (snapshot.averages.weighted, snapshot.averages.arithmetic) {
+            self.text(
+                &format!(
+                    "加权平均分：{}    算术平均分：{}    课程数：{}",
+                    locale.format_decimal(weighted), locale.format_decimal(arithmetic), snapshot.courses.len()
+                ),
+                TEXT_SIZE, MARGIN
+            );
+            self.y -= ROW_HEIGHT;
+        }
+        self.y -= 4.0;
+    }
+
+    /// 课程表格, 一页写不下时换页并重复表头
+    fn write_courses(&mut self, snapshot: &Snapshot) {
+        let locale = snapshot.locale;
+        self.write_table_header();
+
+        for (index, course) in snapshot.courses.iter().enumerate() {
+            if self.y < MARGIN + ROW_HEIGHT * 2.0 {
+                self.new_page();
+                self.write_table_header();
+            }
+
+            let cells = [
+                (index + 1).to_string(),
+                course.semester.clone(),
+                course.name.clone(),
+                course.nature.to_string(),
+                locale.format_decimal(course.credit),
+                course.score.to_string(),
+                locale.format_fixed(course.grade, 2),
+                course.exam_type.clone(),
+            ];
+            self.table_row(&cells);
+        }
+        self.rule(0.3);
+        self.y -= ROW_HEIGHT;
+    }
+
+    /// 导出时间、计算依据与校验码
+    fn write_footer(&mut self, snapshot: &Snapshot, hash: &str) {
+        let locale = snapshot.locale;
+        let mut lines = vec![format!("导出时间：{}", locale.format_datetime(&snapshot.generated_at))];
+        if let Some(provenance) = &snapshot.provenance {
+            lines.push(format!(
+                "数据来源：{}（{}）{}",
+                provenance.source, locale.format_datetime(&provenance.fetched_at), if provenance.edited { "，课程经过手动修改" } else { "" }
+            ));
+            lines.push(format!(
+                "学校规则：{}    换算标准：{}    取整规则：{}    重复课程：{}",
+                provenance.profile, provenance.scale, provenance.rounding, provenance.dedup
+            ));
+        }
+        lines.push(format!("{}：{}", INTEGRITY_LABEL, hash));
+
+        for line in lines {
+            if self.y < MARGIN + ROW_HEIGHT {
+                self.new_page();
+            }
+            self.text(&line, TABLE_SIZE, MARGIN);
+            self.y -= ROW_HEIGHT - 1.0;
+        }
+    }
+
+    fn write_table_header(&mut self) {
+        self.rule(0.6);
+        let titles = COLUMNS.map(|(title, _)| title.to_string());
+        self.table_row(&titles);
+        self.rule(0.3);
+    }
+
+    /// 表格的一行, 超出列宽的文字截断
+    fn table_row(&mut self, cells: &[String]) {
+        self.y -= 1.2;
+        let mut x = MARGIN;
+        for (cell, (_, width)) in cells.iter().zip(COLUMNS) {
+            self.text(&fit_width(cell, width - 1.5, TABLE_SIZE), TABLE_SIZE, x);
+            x += width;
+        }
+        self.y -= ROW_HEIGHT - 1.2;
+    }
+
+    /// 当前位置的横线
+    fn rule(&self, thickness: f32) {
+        self.layer.set_outline_thickness(thickness);
+        self.layer.add_line(Line {
+            points: vec![(Point::new(Mm(MARGIN), Mm(self.y)), false), (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(self.y)), false)],
+            is_closed: false,
+        });
+    }
+
+    /// 以 y 为文字顶端写一行文字
+    fn text(&self, text: &str, size: f32, x: f32) {
+        self.layer.use_text(text, size, Mm(x), Mm(self.y - size * PT_TO_MM), &self.font);
+    }
+
+    /// 换到新的一页
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "成绩");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.page += 1;
+        self.y = PAGE_HEIGHT - MARGIN;
+        self.write_page_number();
+    }
+
+    /// 页码写在页面底部居中
+    fn write_page_number(&self) {
+        self.layer.use_text(format!("第 {} 页", self.page), TABLE_SIZE, Mm(PAGE_WIDTH / 2.0 - 5.0), Mm(MARGIN / 2.0), &self.font);
+    }
+}
+
+/// 按估算的字宽截断文字, 中文按一个字号宽, 英文与数字按半个字号宽
+fn fit_width(text: &str, width: f32, size: f32) -> String {
+    let char_width = |c: char| if c.is_ascii() { 0.55 } else { 1.0 } * size * PT_TO_MM;
+    if text.chars().map(char_width).sum::<f32>() <= width {
+        return text.to_string();
+    }
+
+    let mut used = char_width('…');
+    let mut result: String = text
+        .chars()
+        .take_while(|c| {
+            used += char_width(*c);
+            used <= width
+        })
+        .collect();
+    result.push('…');
+    result
+}
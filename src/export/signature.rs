@@ -0,0 +1,76 @@
+// 导出文件签名 - 以数据目录中随机生成的密钥计算 HMAC-SHA256 校验码
+// 没有密钥就无法为修改后的内容重新计算校验码, 因此只有导出文件的这份程序(同一个数据目录)能校验通过
+use anyhow::{anyhow, Context, Result};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+use std::{fs, io::ErrorKind, path::Path};
+
+const KEY_FILE: &str = "export.key";
+const KEY_LEN: usize = 32;
+
+pub struct ExportSigner {
+    key: [u8; KEY_LEN],
+}
+
+impl ExportSigner {
+    /// 读取数据目录中的签名密钥, 第一次运行时生成新的密钥
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(KEY_FILE);
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                let key = decode_hex(text.trim())
+                    .and_then(|bytes| <[u8; KEY_LEN]>::try_from(bytes).ok())
+                    .ok_or_else(|| anyhow!("签名密钥文件 {} 已损坏, 删除后会重新生成(之前导出的文件将无法校验)", path.display()))?;
+                Ok(Self { key })
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let key = rand::rng().random::<[u8; KEY_LEN]>();
+                fs::write(&path, encode_hex(&key)).with_context(|| format!("无法写入签名密钥文件 {}", path.display()))?;
+                // 密钥只需要本程序读取
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+                        .with_context(|| format!("无法设置签名密钥文件 {} 的权限", path.display()))?;
+                }
+                Ok(Self { key })
+            }
+            Err(e) => Err(e).with_context(|| format!("无法读取签名密钥文件 {}", path.display()))
+        }
+    }
+
+    /// 内容的校验码, 为 HMAC-SHA256 的十六进制
+    pub fn sign(&self, content: &[u8]) -> String {
+        encode_hex(&self.mac(content).finalize().into_bytes())
+    }
+
+    /// 校验码是否与内容一致, 按固定时间比较
+    pub fn verify(&self, content: &[u8], recorded: &str) -> bool {
+        decode_hex(recorded).is_some_and(|tag| self.mac(content).verify_slice(&tag).is_ok())
+    }
+
+    fn mac(&self, content: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC 接受任意长度的密钥");
+        mac.update(content);
+        mac
+    }
+}
+
+// 不输出密钥, 避免出现在日志中
+impl std::fmt::Debug for ExportSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ExportSigner")
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok()).collect()
+}
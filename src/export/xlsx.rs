@@ -0,0 +1,206 @@
+// Excel 导出, 课程列表与汇总信息分别写入两个工作表, 数字以数值保存以便在 Excel 中继续计算
+// 汇总表的最后两行为签名时间与校验码, 校验码覆盖两个工作表中全部单元格的内容
+use super::{mode_label, ExportSigner, Exporter, Snapshot, VerifyResult, INTEGRITY_LABEL, SIGNED_AT_LABEL};
+use crate::business::letter_grade;
+
+use anyhow::Result;
+use calamine::{Reader, Xlsx};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use rust_xlsxwriter::{Format, Workbook, Worksheet};
+use std::io::Cursor;
+
+const COURSES_SHEET: &str = "课程";
+const SUMMARY_SHEET: &str = "汇总";
+
+// 一个单元格, 先生成全部单元格再写入, 以便按写入的内容计算校验码
+enum Cell {
+    Text(String),
+    Number(f64),
+}
+
+impl Cell {
+    /// 单元格的文字, 与 calamine 读取同一单元格得到的文字相同
+    fn text(&self) -> String {
+        match self {
+            Cell::Text(text) => text.clone(),
+            Cell::Number(number) => number.to_string()
+        }
+    }
+}
+
+pub struct XlsxExporter;
+
+impl Exporter for XlsxExporter {
+    fn name(&self) -> &'static str {
+        "xlsx"
+    }
+
+    fn mime(&self) -> &'static str {
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+    }
+
+    fn extension(&self) -> &'static str {
+        "xlsx"
+    }
+
+    fn render(&self, snapshot: &Snapshot) -> Result<Vec<u8>> {
+        let courses = course_rows(snapshot);
+        let mut summary = summary_rows(snapshot);
+        summary.push(vec![Cell::Text(SIGNED_AT_LABEL.to_string()), Cell::Text(snapshot.generated_at.to_rfc3339())]);
+        let hash = snapshot.signer.sign(signed_text(&[to_text(&courses), to_text(&summary)]).as_bytes());
+        summary.push(vec![Cell::Text(INTEGRITY_LABEL.to_string()), Cell::Text(hash)]);
+
+        let mut workbook = Workbook::new();
+        let header = Format::new().set_bold();
+
+        // 课程表的第一行与汇总表的第一列为标题
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(COURSES_SHEET)?;
+        write_rows(sheet, &courses, |row, _| row == 0, &header)?;
+        sheet.set_column_width(1, 12)?;
+        sheet.set_column_width(3, 24)?;
+
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(SUMMARY_SHEET)?;
+        write_rows(sheet, &summary, |_, col| col == 0, &header)?;
+        sheet.set_column_width(0, 12)?;
+        sheet.set_column_width(1, 40)?;
+
+        Ok(workbook.save_to_buffer()?)
+    }
+}
+
+/// 校验导出的 Excel 文件, 按两个工作表中单元格的文字重新计算校验码
+pub(super) fn verify(data: &[u8], signer: &ExportSigner) -> VerifyResult {
+    let Ok(mut workbook) = Xlsx::new(Cursor::new(data)) else {
+        return VerifyResult::unsigned("xlsx");
+    };
+    let mut read_sheet = |name: &str| -> Option<Vec<Vec<String>>> {
+        let range = workbook.worksheet_range(name).ok()?;
+        Some(range.rows().map(|row| row.iter().map(|c| c.to_string()).collect()).collect())
+    };
+    let (Some(courses), Some(mut summary)) = (read_sheet(COURSES_SHEET), read_sheet(SUMMARY_SHEET)) else {
+        return VerifyResult::unsigned("xlsx");
+    };
+
+    // 校验码为汇总表的最后一行, 不计入校验内容
+    let recorded_hash = match summary.last().map(|row| row.as_slice()) {
+        Some([label, hash, ..]) if label == INTEGRITY_LABEL => hash.trim().to_string(),
+        _ => return VerifyResult::unsigned("xlsx")
+    };
+    summary.pop();
+    let signed_at = summary.iter().rev().find(|row| row.first().is_some_and(|label| label == SIGNED_AT_LABEL)).and_then(|row| row.get(1).cloned());
+
+    VerifyResult {
+        valid: signer.verify(signed_text(&[courses, summary]).as_bytes(), &recorded_hash),
+        format: "xlsx",
+        recorded_hash: Some(recorded_hash),
+        signed_at,
+        signed_content: None,
+    }
+}
+
+/// 计算校验码的文字: 单元格以制表符分隔, 行以换行分隔, 工作表之间空一行
+/// 行末与表格末尾的空单元格不计入, 因为 calamine 读取时不包含全空的列与行
+fn signed_text(sheets: &[Vec<Vec<String>>]) -> String {
+    sheets
+        .iter()
+        .map(|rows| {
+            let lines: Vec<String> = rows.iter().map(|row| row.join("\t").trim_end_matches('\t').to_string()).collect();
+            lines.join("\n").trim_end_matches('\n').to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn to_text(rows: &[Vec<Cell>]) -> Vec<Vec<String>> {
+    rows.iter().map(|row| row.iter().map(Cell::text).collect()).collect()
+}
+
+/// 写入一个工作表, is_header 决定哪些单元格以标题格式写入
+fn write_rows(sheet: &mut Worksheet, rows: &[Vec<Cell>], is_header: impl Fn(u32, u16) -> bool, header: &Format) -> Result<()> {
+    for (row, cells) in rows.iter().enumerate() {
+        let row = row as u32;
+        for (col, cell) in cells.iter().enumerate() {
+            let col = col as u16;
+            match cell {
+                Cell::Text(text) if is_header(row, col) => sheet.write_string_with_format(row, col, text, header)?,
+                Cell::Text(text) => sheet.write_string(row, col, text)?,
+                Cell::Number(number) => sheet.write_number(row, col, *number)?
+            };
+        }
+    }
+    Ok(())
+}
+
+/// 课程列表, 列与 CSV 导出一致
+fn course_rows(snapshot: &Snapshot) -> Vec<Vec<Cell>> {
+    let headers = ["序号", "学期", "课程编号", "课程", "课程性质", "学分", "成绩", "绩点", "加权绩点", "等级", "考试性质", "学时", "考核方式", "任课教师"];
+    let mut rows = vec![headers.iter().map(|title| Cell::Text(title.to_string())).collect()];
+
+    for (index, course) in snapshot.courses.iter().enumerate() {
+        rows.push(vec![
+            Cell::Number((index + 1) as f64),
+            Cell::Text(course.semester.clone()),
+            Cell::Text(course.course_code.clone()),
+            Cell::Text(course.name.clone()),
+            Cell::Text(course.nature.as_str().to_string()),
+            Cell::Number(to_f64(course.credit)),
+            // 百分制成绩写为数值, 等级制等文字成绩原样写入
+            match course.score.numeric() {
+                Some(score) => Cell::Number(to_f64(score)),
+                None => Cell::Text(course.score.to_string())
+            },
+            Cell::Number(to_f64(course.grade)),
+            Cell::Number(to_f64(course.credit_gpa)),
+            Cell::Text(letter_grade(course.grade).to_string()),
+            Cell::Text(course.exam_type.clone()),
+            Cell::Text(course.hours.clone()),
+            Cell::Text(course.assessment.clone()),
+            Cell::Text(course.teacher.clone()),
+        ]);
+    }
+    rows
+}
+
+/// 学生信息、平均绩点与计算依据
+fn summary_rows(snapshot: &Snapshot) -> Vec<Vec<Cell>> {
+    let locale = snapshot.locale;
+    let mut rows = Vec::new();
+    let mut push = |label: &str, value: String| rows.push(vec![Cell::Text(label.to_string()), Cell::Text(value)]);
+
+    if let Some(student) = &snapshot.student {
+        push("姓名", student.name.clone());
+        push("学号", student.student_id.clone());
+        push("专业", student.major.clone());
+        push("班级", student.class_name.clone());
+        push("入学年份", student.enrollment_year.map(|year| year.to_string()).unwrap_or_default());
+    }
+
+    // 课程表中的数值由 Excel 按系统的地区设置显示, 汇总表中的文字按导出选项的地区格式写入
+    push("平均绩点", locale.format_decimal(snapshot.gpa));
+    if let (Some(weighted), Some(arithmetic)) = (snapshot.averages.weighted, snapshot.averages.arithmetic) {
+        push("加权平均分", locale.format_decimal(weighted));
+        push("算术平均分", locale.format_decimal(arithmetic));
+    }
+    push("课程数", snapshot.courses.len().to_string());
+    push("计算模式", mode_label(&snapshot.mode).to_string());
+    push("导出时间", locale.format_datetime(&snapshot.generated_at));
+
+    // 计算依据, 按这些条件可以复现上面的平均绩点
+    if let Some(provenance) = &snapshot.provenance {
+        push("数据来源", provenance.source.clone());
+        push("获取时间", locale.format_datetime(&provenance.fetched_at));
+        push("手动修改", if provenance.edited { "是" } else { "否" }.to_string());
+        push("学校规则", provenance.profile.clone());
+        push("换算标准", provenance.scale.to_string());
+        push("取整规则", provenance.rounding.to_string());
+        push("重复课程", provenance.dedup.to_string());
+    }
+    rows
+}
+
+/// Excel 的数值为双精度浮点数
+fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
@@ -1,30 +1,47 @@
 // 路由控制器
 use crate::{
+    backup::{Backup, BackupStore},
     business::{
-        print_error, print_info, process_scraped_course_results, round_2decimal, score_trans_grade,
-        ProcessedGPAResults, ResultSource, EXCLUDED_COURSES_KEYWORD,
-        NATURE_EXCLUSIONS, PERMANENT_IGNORED_COURSES,
+        academic_years, calculate_evaluation, compare_with_benchmark, compare_with_official, compulsory_mode_courses, course_impacts, default_mode_courses, estimate_ranking, exclude_failed_courses, filter_gpa, gpa_trend, grade_distribution, print_error, print_info, is_academic_year, letter_grades, process_scraped_course_results, regrade_course, rescale_result, scale_comparison, plan_target, simulate_courses, wes_letters, year_gpa,
+        CourseFilter, CreditStats, DataOrigin, EditHistory, EvaluationInput, GPAResult, GradeScale, HypotheticalCourse, MinorMode, ProcessedGPAResults, Provenance, RankingBasis, RetakePolicy, ResultSource, UncountedCourse, COMPULSORY_NATURES, GRADE_SCALES, IMPACT_REFERENCE_GRADE, MAX_COURSE_CREDIT, RETAKE_POLICIES,
     },
-    models::{Course, FileError, WebError},
-    scraping::{AAOWebsite, USER_AGENT},
-    BinaryAsset, TemplateAsset
+    changelog::{Changelog, Release, CURRENT_VERSION},
+    cohort::{CohortBatch, Distribution, BATCH_TTL, MIN_COHORT_SIZE},
+    config::AppConfig,
+    demo::DemoSource,
+    export::{content_hash, mode_label, render, verify_export, ExportFile, ExportJobs, ExportSigner, ExportOptions, Exporter, ExporterRegistry, Snapshot},
+    profile::{ExclusionList, ExclusionRules, GradingTable, ProfileStore, SchoolProfile},
+    progress::{progress_stream, ProgressHub, ProgressReporter},
+    resume::ResumeStore,
+    import::{merge_courses, parse_csv, parse_json, parse_ods, parse_pasted_text, parse_saved_page, parse_with_mapping, parse_xls, parse_xlsx, preview_table, upload_template, ColumnMapping, ImportFormat},
+    notification::notify_grade_diff,
+    models::{Course, FileError, GradeProbe, ParsedCourses, Score, SkippedRow, StudentProfile, WebError},
+    selfcheck::SelfCheckReport,
+    scraping::{AAOWebsite, GradeSource},
+    settings::{Benchmark, SettingsBundle, SettingsStore},
+    state::AppState,
+    storage::{GradeDiff, SnapshotInfo, SnapshotStore},
+    summary::{Summary, SummaryStore},
+    watcher::GradeWatcher,
+    TemplateAsset
 };
 
 use axum::{
-    extract::{Form, Multipart, State},
-    http::{header, StatusCode, Uri},
-    response::{Html, IntoResponse, Redirect, Response},
+    extract::{multipart::MultipartError, Form, Multipart, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{sse::{Event, KeepAlive, Sse}, Html, IntoResponse, Redirect, Response},
     Extension,
     Json
 };
-use calamine::{Reader, Xlsx};
-use fake_user_agent::get_rua;
+use chrono::{DateTime, Local};
+use futures::{Stream, StreamExt};
 use mime_guess;
+use rand::Rng;
 use rust_decimal::Decimal;
-use std::io::Cursor;
+use std::{convert::Infallible, sync::Arc};
 
 // 反序列化解析表单数据, 类似隔壁的 request.form
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 // 模板引擎, 类似 Jinja2
@@ -32,17 +49,79 @@ use tera::Tera;
 use tokio::sync::broadcast;
 use tower_sessions::Session;
 
+// 上传的文件全部无法识别时, 错误信息中列出的行数
+const SKIPPED_ROWS_IN_ERROR: usize = 5;
+// 粘贴的文字中无法识别的行所在的位置, 对应文件名
+const PASTED_TEXT_LOCATION: &str = "粘贴的文字";
+
 // 对应前端登录表单的两个字段
 #[derive(Debug, Deserialize)]
 pub struct LoginForm {
     account: String,
-    password: String
+    password: String,
+    #[serde(default)]
+    semesters: String,  // 可选, 以逗号分隔的学期列表, 如 2023-2024-1,2023-2024-2; 为空则查询全部学期
+    #[serde(default)]
+    progress_id: String, // 可选, 前端生成的进度 ID, 用于通过 SSE 接收查询进度
+    #[serde(default)]
+    minor_mode: MinorMode,  // 可选, 辅修成绩的处理方式, 默认不查询
+}
+
+impl LoginForm {
+    /// 是否查询全部学期, 没有指定任何学期时查询全部学期
+    fn queries_all_semesters(&self) -> bool {
+        self.semesters.split(',').all(|s| s.trim().is_empty())
+    }
+}
+
+// 订阅查询进度的参数
+#[derive(Debug, Deserialize)]
+pub struct ProgressQuery {
+    id: String,
+}
+
+// 编辑课程的请求, 以学期、课程名称与考试性质定位课程
+// 重修、补考的课程名称相同, 只按名称定位会改错或删掉其他记录
+#[derive(Debug, Deserialize)]
+pub struct CourseEdit {
+    #[serde(default)]
+    semester: String,
+    name: String,
+    #[serde(default)]
+    exam_type: String,
+    #[serde(default)]
+    credit: Option<Decimal>,    // 新学分, 不修改则为空
+    #[serde(default)]
+    score: Option<String>,      // 新成绩, 不修改则为空
+}
+
+impl CourseEdit {
+    /// 是否为要编辑的那条成绩记录
+    fn matches(&self, course: &Course) -> bool {
+        course.semester == self.semester && course.name == self.name && course.exam_type == self.exam_type
+    }
+
+    /// 用于提示的课程描述, 如 2023-2024-1 高等数学(重修)
+    fn describe(&self) -> String {
+        let exam_type = if self.exam_type.is_empty() { String::new() } else { format!("({})", self.exam_type) };
+        format!("{} {}{}", self.semester, self.name, exam_type).trim().to_string()
+    }
 }
 
 // GPA 计算模式
 #[derive(Debug, Deserialize)]
 pub struct CalculateMode {
-    mode: String,    // default 或 all
+    mode: String,    // default、all、compulsory、year 或 filter
+    #[serde(default)]
+    value: String,   // 学年模式下的学年, 如 2023-2024
+    #[serde(default)]
+    base: String,    // 学年模式下在哪个模式的课程中筛选, default、all 或 compulsory, 为空时与结果页默认显示的模式相同
+    #[serde(default)]
+    scale: GradeScale,  // 绩点换算标准, 默认为本校标准
+    #[serde(default)]
+    exclude_failed: bool,   // 不计入未通过(绩点为 0)的课程
+    #[serde(default)]
+    filter: Option<CourseFilter>,   // 自定义模式的筛选条件, 在全部课程中筛选
 }
 
 /// 用于处理 static 文件夹模板文件
@@ -68,11 +147,21 @@ pub async fn static_file(uri: Uri) -> impl IntoResponse {
 
 
 // 登录页面
-pub async fn login(session: Session, State(tera): State<Tera>) -> Result<Html<String>, WebError> {
+pub async fn login(
+    session: Session, State(tera): State<Arc<Tera>>, State(config): State<Arc<AppConfig>>,
+    State(settings): State<Arc<SettingsStore>>, State(changelog): State<Arc<Changelog>>
+) -> Result<Html<String>, WebError> {
     #[cfg(debug_assertions)]
     print_info("开始渲染登录界面");
 
     let mut context = tera::Context::new();
+    context.insert("read_only", &config.read_only);
+    context.insert("demo", &config.demo);
+    context.insert("new_releases", &unseen_releases(&settings, &changelog));
+
+    // 已有结果时, 免登录模式中可以选择与之合并
+    let origin: Option<DataOrigin> = session.get("data_origin").await?;
+    context.insert("current_source", &origin.map(|o| o.source));
 
     let flash_msg: Option<String> = session.remove("flash_msg").await.map_err(|e| WebError::InternalError(e.to_string()))?;
     if let Some(msg) = flash_msg {
@@ -91,43 +180,215 @@ pub async fn login(session: Session, State(tera): State<Tera>) -> Result<Html<St
     Ok(Html(html))
 }
 
+/// 升级后第一次打开登录页时返回新增的更新内容, 并记录当前版本, 之后不再提示
+/// 首次使用(没有记录过版本)时不提示, 从不记录版本的旧版本升级时启动时已记为旧版本的版本号
+fn unseen_releases(settings: &SettingsStore, changelog: &Changelog) -> Vec<Release> {
+    let last_seen = settings.get().last_seen_version;
+    if last_seen.as_deref() == Some(CURRENT_VERSION) {
+        return Vec::new();
+    }
+
+    if let Err(e) = settings.update(|s| s.last_seen_version = Some(CURRENT_VERSION.to_string())) {
+        print_error(&format!("记录程序版本失败: {}", e));
+    }
+
+    last_seen.map(|version| changelog.since(&version)).unwrap_or_default()
+}
+
+// 返回内嵌的更新日志
+pub async fn api_changelog(State(changelog): State<Arc<Changelog>>) -> Json<serde_json::Value> {
+    Json(json!({"current_version": CURRENT_VERSION, "releases": changelog.releases()}))
+}
+
 // 负责从登录网站中获取数据
-pub async fn score_from_official(session: Session, Form(form): Form<LoginForm>) -> Result<Json<serde_json::Value>, WebError> {
+pub async fn score_from_official(
+    session: Session, State(state): State<AppState>, Form(form): Form<LoginForm>
+) -> Result<Json<serde_json::Value>, WebError> {
     #[cfg(debug_assertions)]
     print_info("准备爬取数据");
 
     #[cfg(not(debug_assertions))]
     print_info("正在登录中...");
 
-    let mut scraper = AAOWebsite::new().map_err(|e| WebError::InternalError(e.to_string()))?;
+    // 前端提供了进度 ID 时才推送进度
+    let progress = (!form.progress_id.is_empty()).then(|| state.progress.register(&form.progress_id));
 
-    // 初始化会话, 获得 Cookie
-    scraper.init().await?;
-    scraper.login(&form.account, &form.password).await?;
+    let result = query_and_save(&session, &state, &form, progress.clone()).await;
 
-    #[cfg(not(debug_assertions))]
-    print_info("登录成功");
+    if let Some(progress) = progress {
+        progress.finish(if result.is_ok() { "查询完成" } else { "查询失败" });
+        state.progress.unregister(form.progress_id.clone());
+    }
+
+    result
+}
 
-    let courses = scraper.get_grades().await?;
+// 试运行: 只登录并读取成绩列表第一页的概况, 不保存任何数据
+pub async fn score_dry_run(
+    State(config): State<Arc<AppConfig>>, State(profile): State<Arc<SchoolProfile>>, Form(form): Form<LoginForm>
+) -> Result<Json<serde_json::Value>, WebError> {
+    print_info("试运行: 正在测试登录...");
+
+    let probe = if config.demo {
+        probe_grades(&mut DemoSource::new(&profile.grading), &form).await?
+    } else {
+        let mut scraper = AAOWebsite::new(&config.scraper, &profile.grading).map_err(|e| WebError::InternalError(e.to_string()))?;
+        probe_grades(&mut scraper, &form).await?
+    };
 
-    #[cfg(debug_assertions)]
-    print_info(&format!("数据爬取成功, 共{}门课程", courses.len()));
+    print_info(&format!("试运行完成: 成绩共{}页, 第一页{}门课程", probe.page_count, probe.first_page_rows));
 
-    let results: ProcessedGPAResults = process_scraped_course_results(&courses, ResultSource::OfficialWebsite);
-    let default_result = results.default.unwrap();   // 因为 ResultSource::OfficialWebsite, 所以在这里总会返回 Some
-    let all_result = results.all;
+    Ok(Json(json!({"success": true, "probe": probe})))
+}
 
+/// 登录并读取成绩列表第一页的概况
+async fn probe_grades(source: &mut impl GradeSource, form: &LoginForm) -> Result<GradeProbe, WebError> {
+    source.sign_in(&form.account, &form.password).await?;
 
-    // Default 模式数据
-    session.insert("gpa_default", default_result.gpa).await.map_err(|e| WebError::InternalError(e.to_string()))?;
-    session.insert("courses_default", default_result.courses).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    Ok(source.probe_grades().await?)
+}
 
-    // All 模式数据
-    session.insert("gpa_all", all_result.gpa).await.map_err(|e| WebError::InternalError(e.to_string()))?;
-    session.insert("courses_all", all_result.courses).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+// 登录并读取成绩查询页面中可选的学期, 供前端生成学期选择框
+pub async fn semester_options(
+    State(config): State<Arc<AppConfig>>, State(profile): State<Arc<SchoolProfile>>, Form(form): Form<LoginForm>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let semesters = if config.demo {
+        fetch_semester_options(&mut DemoSource::new(&profile.grading), &form).await?
+    } else {
+        let mut scraper = AAOWebsite::new(&config.scraper, &profile.grading).map_err(|e| WebError::InternalError(e.to_string()))?;
+        fetch_semester_options(&mut scraper, &form).await?
+    };
 
-    // 数据模式
-    session.insert("result_mode", "login").await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    print_info(&format!("已获取学期列表, 共{}个学期", semesters.len()));
+
+    Ok(Json(json!({"success": true, "semesters": semesters})))
+}
+
+/// 登录并获取可查询的学期
+async fn fetch_semester_options(source: &mut impl GradeSource, form: &LoginForm) -> Result<Vec<String>, WebError> {
+    source.sign_in(&form.account, &form.password).await?;
+
+    Ok(source.fetch_semesters().await?)
+}
+
+// 订阅登录查询的进度, 以 SSE 推送
+pub async fn scrape_progress(
+    State(hub): State<Arc<ProgressHub>>, Query(query): Query<ProgressQuery>
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = progress_stream(hub, query.id).map(|progress| {
+        Ok(Event::default().event("progress").json_data(&progress).unwrap_or_default())
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// 登录查询并把结果存入 Session
+async fn query_and_save(
+    session: &Session, state: &AppState, form: &LoginForm, progress: Option<ProgressReporter>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let AppState { config, snapshots, backups, summary, .. } = state;
+    let profile = state.profile.get();
+
+    // 演示模式使用模拟数据, 不连接教务系统
+    let GradeQuery { mut courses, minor_courses, official_gpa, student, semesters, skipped_rows } = if config.demo {
+        query_grades(&mut DemoSource::new(&profile.grading), form).await?
+    } else {
+        let mut scraper = AAOWebsite::new(&config.scraper, &profile.grading).map_err(|e| WebError::InternalError(e.to_string()))?;
+        scraper.set_progress(progress.clone());
+        scraper.set_resume(Some(state.resume.clone()));
+        query_grades(&mut scraper, form).await?
+    };
+
+    if let Some(progress) = &progress {
+        progress.report("计算绩点", 95);
+    }
+
+    // 开启了出成绩提醒时, 后台成绩监控之后使用这个账号定期查询
+    if state.settings.get().watch_new_grades {
+        state.watcher.remember(&form.account, &form.password);
+        session.insert("watch_account", SnapshotStore::account_key(&form.account)).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    }
+
+    // 新数据会覆盖当前结果, 先备份
+    backup_session(session, backups, "重新登录查询").await?;
+
+    session.insert("official_gpa", official_gpa).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    session.insert("student_profile", student).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    // 保存成绩快照, 演示模式的模拟数据不写入历史
+    // 只保存查询全部学期得到的主修成绩, 只查询部分学期时与上一份完整快照比较会把其余学期的课程误认为变动
+    // 此时辅修成绩尚未合并, courses 中只有主修课程
+    if !config.demo && form.queries_all_semesters() {
+        let key = SnapshotStore::account_key(&form.account);
+        // 数据库访问是阻塞调用, 放到阻塞线程中执行
+        let (latest, saved) = {
+            let (snapshots, key, courses) = (snapshots.clone(), key.clone(), courses.clone());
+            tokio::task::spawn_blocking(move || (snapshots.latest(&key), snapshots.save(&key, &courses)))
+                .await
+                .map_err(|e| WebError::InternalError(e.to_string()))?
+        };
+        // 与上一次查询的快照比较, 第一次查询没有可比较的快照
+        let grade_diff = match latest {
+            Ok(latest) => latest.map(|(taken_at, previous)| GradeDiff::between(taken_at, &previous, &courses)),
+            Err(e) => {
+                print_error(&format!("读取上一次的成绩快照失败: {}", e));
+                None
+            }
+        };
+        if let Err(e) = saved {
+            print_error(&format!("保存成绩快照失败: {}", e));
+        }
+        match grade_diff.filter(|diff| !diff.is_empty()) {
+            Some(diff) => {
+                print_info(&format!("与上一次查询相比新增 {} 门成绩, {} 门成绩有变动", diff.added.len(), diff.changed.len()));
+                if state.settings.get().watch_new_grades {
+                    notify_grade_diff(&diff);
+                }
+                session.insert("grade_diff", diff).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+            }
+            None => { session.remove::<GradeDiff>("grade_diff").await?; }
+        }
+        session.insert("history_key", key).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    } else {
+        session.remove::<GradeDiff>("grade_diff").await?;
+        if config.demo {
+            session.remove::<String>("history_key").await?;
+        } else {
+            // 部分学期的查询不保存快照, 但仍可以搜索该账号之前的成绩历史
+            session.insert("history_key", SnapshotStore::account_key(&form.account)).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+        }
+    }
+
+    // 合并计算时辅修课程与主修课程一起参与计算, 分别计算时辅修课程单独得出一个绩点
+    let minor_result = match form.minor_mode {
+        MinorMode::Ignore => None,
+        MinorMode::Merge => {
+            courses.extend(minor_courses);
+            None
+        }
+        MinorMode::Separate => Some(process_scraped_course_results(&minor_courses, ResultSource::OfficialWebsite, &profile).all)
+    };
+
+    // 登录成功但一门成绩都没有时, 结果页显示"暂无成绩"而不是跳回登录页
+    if courses.is_empty() && minor_result.as_ref().is_none_or(|r| r.courses.is_empty()) {
+        print_info("登录成功, 但教务系统中暂无任何成绩");
+        let empty = EmptyTranscript { semesters, checked_at: Local::now() };
+        session.insert("empty_transcript", empty).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    } else {
+        session.remove::<EmptyTranscript>("empty_transcript").await?;
+    }
+    session.insert("minor_result", minor_result).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    if !skipped_rows.is_empty() {
+        print_error(&format!("成绩页面中有{}行学分或成绩无法识别, 未计入", skipped_rows.len()));
+    }
+
+    let mut results: ProcessedGPAResults = process_scraped_course_results(&courses, ResultSource::OfficialWebsite, &profile);
+    results.skipped_rows = skipped_rows;
+    summary.update(Summary::from_results(&results));
+    save_results(session, results, config.low_memory).await?;
+    save_origin(session, DataOrigin::now(if config.demo { "演示数据" } else { "教务系统登录查询" }), &courses, config.low_memory).await?;
+    session.remove::<EditHistory>("edit_history").await?;   // 新数据不再能撤销到旧数据
 
     #[cfg(debug_assertions)]
     print_info("存入 Session 成功");
@@ -136,68 +397,443 @@ pub async fn score_from_official(session: Session, Form(form): Form<LoginForm>)
     Ok(Json(json!({"success": true})))
 }
 
+// 登录成功但没有任何成绩(如刚入学的新生)时保存在 Session 中, 结果页据此显示"暂无成绩"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EmptyTranscript {
+    semesters: Vec<String>,     // 教务系统中可查询的学期
+    checked_at: DateTime<Local>,
+}
+
+// 一次登录查询得到的数据
+struct GradeQuery {
+    courses: Vec<Course>,
+    minor_courses: Vec<Course>, // 未选择查询辅修时为空
+    official_gpa: Option<Decimal>,
+    student: Option<StudentProfile>,
+    semesters: Vec<String>,     // 只在没有任何成绩时获取, 用于提示用户
+    skipped_rows: Vec<SkippedRow>,  // 学分或成绩无法识别而跳过的行
+}
+
+/// 登录并获取成绩、官方绩点与学生信息
+async fn query_grades(source: &mut impl GradeSource, form: &LoginForm) -> Result<GradeQuery, WebError> {
+    // 初始化会话并登录
+    source.sign_in(&form.account, &form.password).await?;
+
+    #[cfg(not(debug_assertions))]
+    print_info("登录成功");
+
+    let semesters: Vec<String> = form.semesters
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let courses = source.fetch_grades(&semesters).await?;
+
+    #[cfg(debug_assertions)]
+    print_info(&format!("数据爬取成功, 共{}门课程", courses.len()));
+
+    let minor_courses = match form.minor_mode {
+        MinorMode::Ignore => Vec::new(),
+        MinorMode::Merge | MinorMode::Separate => source.fetch_minor_grades(&semesters).await?
+    };
+
+    // 官方绩点只用于对照, 获取失败不影响查询结果
+    let official_gpa = source.fetch_official_gpa().await.unwrap_or_else(|e| {
+        print_error(&format!("获取官方绩点失败: {}", e));
+        None
+    });
+
+    // 学生信息只用于显示, 获取失败同样不影响查询结果
+    let student = source.fetch_student_profile().await.unwrap_or_else(|e| {
+        print_error(&format!("获取学生信息失败: {}", e));
+        None
+    });
+
+    // 没有任何成绩时获取可查询的学期列表, 让用户知道登录是成功的
+    let semesters = if courses.is_empty() && minor_courses.is_empty() {
+        source.fetch_semesters().await.unwrap_or_else(|e| {
+            print_error(&format!("获取学期列表失败: {}", e));
+            Vec::new()
+        })
+    } else {
+        Vec::new()
+    };
+
+    Ok(GradeQuery { courses, minor_courses, official_gpa, student, semesters, skipped_rows: source.skipped_rows() })
+}
+
+// 只读模式下替代登录接口, 直接拒绝请求
+pub async fn read_only_rejected() -> WebError {
+    print_error("只读模式下收到登录请求, 已拒绝");
+
+    WebError::ReadOnly
+}
+
 // 负责从文件中获取数据
-pub async fn score_from_file(session: Session, mut multipart: Multipart) -> Result<Json<serde_json::Value>, WebError> {
+pub async fn score_from_file(
+    session: Session, State(config): State<Arc<AppConfig>>, State(backups): State<Arc<BackupStore>>,
+    State(summary): State<Arc<SummaryStore>>, State(profile): State<Arc<SchoolProfile>>, mut multipart: Multipart
+) -> Result<Json<serde_json::Value>, WebError> {
     let mut courses: Vec<Course> = Vec::new();
-
-    while let Ok(Some(field)) = multipart.next_field().await {
-        if field.name() == Some("gpa_file") {   // 和前端 formData 的键名一致
-            let data = field.bytes().await.map_err(|e| FileError::OpenError(e.to_string()))?;
-            let reader = Cursor::new(data);
-            let mut worksheet: Xlsx<_> = Xlsx::new(reader).map_err(|e| FileError::OpenError(e.to_string()))?;
-
-            if let Ok(range) = worksheet.worksheet_range("Sheet1") {
-                for row in range.rows().skip(3) {
-                    let name = row.get(0).map(|c| c.to_string()).unwrap_or_default().trim().to_string();
-                    let credit_str = row.get(1).map(|c| c.to_string()).unwrap_or_default().trim().to_string();
-                    let score_str = row.get(2).map(|c| c.to_string()).unwrap_or_default().trim().to_string();
-
-                    if name.is_empty() || credit_str.is_empty() || score_str.is_empty() { continue; }
-                    if let Ok(credit) = credit_str.parse::<Decimal>() {
-                        if let Some(grade) = score_trans_grade(&score_str) {
-                            let credit_gpa = round_2decimal(grade * credit);
-                            courses.push(Course {
-                                name,
-                                nature: "".to_string(),
-                                score: score_str,
-                                credit,
-                                grade,
-                                credit_gpa,
-                            });
-                        }
-                    }
+    let mut skipped_rows: Vec<SkippedRow> = Vec::new();
+    let mut source = ResultSource::InputFile;
+    let mut file_names: Vec<String> = Vec::new();
+    let mut mapping: Option<ColumnMapping> = None;
+    let mut merge = false;
+    let mut files: Vec<serde_json::Value> = Vec::new();   // 各文件计入的课程数, 返回给前端
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| upload_error(e, &config))? {
+        // 用户在预览中指定的各列, 前端放在文件之前提交
+        if field.name() == Some("column_mapping") {
+            let text = field.text().await.map_err(|e| upload_error(e, &config))?;
+            mapping = Some(serde_json::from_str(&text).map_err(|e| WebError::InvalidInput(format!("列对应关系格式错误: {}", e)))?);
+        } else if field.name() == Some("merge") {   // 与当前结果合并, 而不是替换
+            merge = field.text().await.map_err(|e| upload_error(e, &config))? == "true";
+        } else if field.name() == Some("gpa_file") {   // 和前端 formData 的键名一致
+            let file_name = field.file_name().unwrap_or_default().to_string();
+            let content_type = field.content_type().unwrap_or_default().to_string();
+            let data = field.bytes().await.map_err(|e| upload_error(e, &config))?;
+
+            let (mut parsed, file_source) = match &mapping {
+                Some(mapping) => {
+                    let format = ImportFormat::detect(&file_name, &content_type, &data);
+                    let parsed = parse_with_mapping(&format, &data, mapping, &profile.grading)?;
+                    let source = file_source(&parsed);
+                    (parsed, source)
                 }
+                None => parse_upload(&file_name, &content_type, &data, &profile.grading)?
+            };
+            for course in &mut parsed.courses {
+                course.source = file_name.clone();
+            }
+            // 多个文件(如每学期一份)合并计算, 与之前文件重复的成绩记录不重复计入
+            let parsed_count = parsed.courses.len();
+            let added = merge_courses(&mut courses, parsed.courses);
+            files.push(json!({
+                "name": file_name, "courses": added, "duplicates": parsed_count - added, "skipped": parsed.skipped.len()
+            }));
+            print_info(&format!("{}: 解析{}门课程, 其中{}门与之前的文件重复", file_name, parsed_count, parsed_count - added));
+
+            skipped_rows.extend(parsed.skipped.into_iter().map(|row| SkippedRow { location: file_name.clone(), ..row }));
+            // 表格文件注明读取的工作表, 显示在计算依据的数据来源中
+            file_names.push(match parsed.sheet {
+                Some(sheet) => format!("{}（工作表“{}”，{}门）", file_name, sheet, added),
+                None => format!("{}（{}门）", file_name, added)
+            });
+            if let ResultSource::SavedPage = file_source {
+                source = ResultSource::SavedPage;
             }
         }
     }
 
     if courses.is_empty() {
-        return Err(FileError::NoValidDataFound.into());
+        // 有数据但全部无法识别时列出前几行的原因, 方便修改文件
+        return Err(match skipped_rows.len() {
+            0 => FileError::NoValidDataFound,
+            count => FileError::NoRowRecognized(count, skipped_rows.iter().take(SKIPPED_ROWS_IN_ERROR).map(describe_skipped_row).collect::<Vec<_>>().join("；"))
+        }.into());
+    }
+
+    print_info(&format!("从文件中成功解析{}门课程", courses.len()));
+    if !skipped_rows.is_empty() {
+        print_error(&format!("文件中有{}行学分或成绩无法识别, 未计入", skipped_rows.len()));
+    }
+
+    backup_session(&session, &backups, "导入文件").await?;
+    let imported = ImportedCourses {
+        parsed: ParsedCourses { courses, skipped: skipped_rows, sheet: None },
+        source,
+        origin: DataOrigin::now(format!("导入文件 {}", file_names.join("、"))),
+    };
+    let saved = save_imported_courses(&session, &config, &summary, &profile, imported, merge).await?;
+
+    Ok(Json(json!({"success": true, "files": files, "skipped_rows": saved.skipped, "merged_with": saved.merged_with})))
+}
+
+// 粘贴的成绩文字
+#[derive(Deserialize)]
+pub struct PastedScores {
+    text: String,
+    #[serde(default)]
+    merge: bool,    // 与当前结果合并, 而不是替换
+}
+
+// 负责从粘贴的文字中获取数据, 如从教务系统成绩页面的表格中复制的几行, 不必先整理成表格文件
+pub async fn score_from_text(
+    session: Session, State(config): State<Arc<AppConfig>>, State(backups): State<Arc<BackupStore>>,
+    State(summary): State<Arc<SummaryStore>>, State(profile): State<Arc<SchoolProfile>>, Form(form): Form<PastedScores>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let mut parsed = parse_pasted_text(&form.text, &profile.grading);
+    let source = file_source(&parsed);
+    for course in &mut parsed.courses {
+        course.source = PASTED_TEXT_LOCATION.to_string();
+    }
+    for row in &mut parsed.skipped {
+        row.location = PASTED_TEXT_LOCATION.to_string();
+    }
+
+    if parsed.courses.is_empty() {
+        return Err(WebError::InvalidInput(match parsed.skipped.len() {
+            0 => "粘贴的文字中未找到课程, 每行需要包含课程名称、学分与成绩, 如“高等数学 4 92”".to_string(),
+            count => format!(
+                "粘贴的文字中有{}行数据, 但都未能识别: {}",
+                count, parsed.skipped.iter().take(SKIPPED_ROWS_IN_ERROR).map(describe_skipped_row).collect::<Vec<_>>().join("；")
+            )
+        }));
+    }
+
+    let count = parsed.courses.len();
+    print_info(&format!("从粘贴的文字中成功解析{}门课程", count));
+    if !parsed.skipped.is_empty() {
+        print_error(&format!("粘贴的文字中有{}行学分或成绩无法识别, 未计入", parsed.skipped.len()));
     }
 
-    print_info(&format!("从 Excel 文件中成功解析{}门课程", courses.len()));
+    backup_session(&session, &backups, "粘贴成绩").await?;
+    let imported = ImportedCourses { parsed, source, origin: DataOrigin::now(format!("粘贴的文字（{}门）", count)) };
+    let saved = save_imported_courses(&session, &config, &summary, &profile, imported, form.merge).await?;
+
+    Ok(Json(json!({"success": true, "courses": count, "skipped_rows": saved.skipped, "merged_with": saved.merged_with})))
+}
+
+// 从文件或粘贴的文字中导入的课程
+struct ImportedCourses {
+    parsed: ParsedCourses,
+    source: ResultSource,
+    origin: DataOrigin,
+}
 
-    // 只关心 All 模式的数据
-    let (gpa, courses_for_use) = {
-        let results: ProcessedGPAResults = process_scraped_course_results(&courses, ResultSource::InputFile);
+// 导入后存入 Session 的结果, 合并时跳过的行包括原有数据中的
+struct SavedImport {
+    skipped: Vec<SkippedRow>,
+    merged_with: Option<String>,    // 合并的原有数据的来源, 没有合并时为空
+}
 
-        (results.all.gpa, results.all.courses)
+/// 计算导入的课程并存入 Session
+/// 替换之前的结果时, 导入的数据没有官方绩点、学生信息等登录查询才有的内容, 一并清除;
+/// 与当前结果合并时(如补充教务系统中没有的转学分课程)保留学生信息、辅修成绩与成绩历史, 只有官方绩点不再可比
+async fn save_imported_courses(
+    session: &Session, config: &AppConfig, summary: &SummaryStore, profile: &SchoolProfile, imported: ImportedCourses, merge: bool
+) -> Result<SavedImport, WebError> {
+    let base = if merge { load_merge_base(session).await? } else { None };
+    let merged_with = base.as_ref().map(|base| base.origin.source.clone());
+    let ImportedCourses { parsed, source, origin } = match base {
+        Some(base) => merge_imported(base, imported),
+        None => imported
     };
 
-    session.insert("courses_all", courses_for_use).await.map_err(|e| WebError::InternalError(e.to_string()))?;
-    session.insert("gpa_all", gpa).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    let courses = parsed.courses;
+    let mut results: ProcessedGPAResults = process_scraped_course_results(&courses, source, profile);
+    results.skipped_rows = parsed.skipped.clone();
 
-    // 数据模式
-    session.insert("result_mode", "file").await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    for warning in &results.credit_warnings {
+        print_error(&format!("学分检查: {}", warning));
+    }
+
+    summary.update(Summary::from_results(&results));
+    save_results(session, results, config.low_memory).await?;
+    save_origin(session, origin, &courses, config.low_memory).await?;
+    session.remove::<EditHistory>("edit_history").await?;
+    session.remove::<Option<Decimal>>("official_gpa").await?;  // 文件数据没有官方绩点可对照, 合并后的课程也与官方绩点不一致
+    session.remove::<EmptyTranscript>("empty_transcript").await?;
+    session.remove::<GradeDiff>("grade_diff").await?;   // 新增与变动的提示只针对登录查询得到的原始结果
+    if merged_with.is_none() {
+        session.remove::<Option<StudentProfile>>("student_profile").await?;
+        session.remove::<Option<GPAResult>>("minor_result").await?;
+        session.remove::<String>("history_key").await?;
+    }
 
     #[cfg(debug_assertions)]
     print_info("计算结果已存入 Session");
 
-    Ok(Json(json!({"success": true})))
+    Ok(SavedImport { skipped: parsed.skipped, merged_with })
+}
+
+// 合并导入时当前结果中的数据
+struct MergeBase {
+    courses: Vec<Course>,
+    skipped: Vec<SkippedRow>,
+    origin: DataOrigin,
+    login: bool,    // 是否可以计算 Default 模式, 如登录查询的结果
+}
+
+/// 读取当前结果去重前的课程, 低内存模式下没有保存, 改用当前的全部课程与未计入绩点的课程; 没有结果时返回 None
+/// 尚未标注来源的课程(如登录查询的课程)标为原有数据的来源
+async fn load_merge_base(session: &Session) -> Result<Option<MergeBase>, WebError> {
+    let Some(origin) = session.get::<DataOrigin>("data_origin").await? else {
+        return Ok(None);
+    };
+
+    let mut courses: Vec<Course> = session.get("courses_raw").await?.unwrap_or_default();
+    if courses.is_empty() {
+        let uncounted: Vec<UncountedCourse> = session.get("uncounted_courses").await?.unwrap_or_default();
+        courses = session.get::<Vec<Course>>("courses_all").await?.unwrap_or_default();
+        courses.extend(uncounted.into_iter().map(|u| u.course));
+    }
+    if courses.is_empty() {
+        return Ok(None);
+    }
+
+    for course in courses.iter_mut().filter(|c| c.source.is_empty()) {
+        course.source = origin.source.clone();
+    }
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+
+    Ok(Some(MergeBase {
+        courses,
+        skipped: session.get("skipped_rows").await?.unwrap_or_default(),
+        origin,
+        login: result_mode == "login",
+    }))
+}
+
+/// 把导入的课程并入当前结果, 与原有数据完全相同的成绩记录只保留一条
+/// 原有数据可以计算 Default 模式时合并后仍然可以, 导入的课程没有课程性质时与其他课程一样计入
+fn merge_imported(base: MergeBase, imported: ImportedCourses) -> ImportedCourses {
+    let MergeBase { mut courses, mut skipped, origin, login } = base;
+    let parsed_count = imported.parsed.courses.len();
+    let added = merge_courses(&mut courses, imported.parsed.courses);
+    skipped.extend(imported.parsed.skipped);
+    print_info(&format!("与当前结果合并: 新增{}门课程, {}门与原有数据重复", added, parsed_count - added));
+
+    let source = match (login, imported.source) {
+        (true, _) => ResultSource::OfficialWebsite,
+        (false, source) => source
+    };
+
+    ImportedCourses {
+        parsed: ParsedCourses { courses, skipped, sheet: None },
+        source,
+        origin: DataOrigin::now(format!("{} + {}", origin.source, imported.origin.source)),
+    }
+}
+
+/// 一行无法识别的数据, 如"成绩.xlsx 第 5 行 高等数学: 学分“四”无法识别"
+fn describe_skipped_row(row: &SkippedRow) -> String {
+    let location = if row.location.is_empty() { String::new() } else { format!("{} ", row.location) };
+    let name = if row.name.is_empty() { String::new() } else { format!(" {}", row.name) };
+    format!("{}第 {} 行{}: {}", location, row.row, name, row.reason)
+}
+
+// 预览上传的表格文件, 返回表头、前几行数据与自动识别的各列, 供用户在上传前指定各列
+pub async fn upload_preview(State(config): State<Arc<AppConfig>>, mut multipart: Multipart) -> Result<Json<serde_json::Value>, WebError> {
+    while let Some(field) = multipart.next_field().await.map_err(|e| upload_error(e, &config))? {
+        if field.name() == Some("gpa_file") {
+            let file_name = field.file_name().unwrap_or_default().to_string();
+            let content_type = field.content_type().unwrap_or_default().to_string();
+            let data = field.bytes().await.map_err(|e| upload_error(e, &config))?;
+
+            let preview = preview_table(&ImportFormat::detect(&file_name, &content_type, &data), &data)?;
+            return Ok(Json(json!(preview)));
+        }
+    }
+
+    Err(WebError::InvalidInput("请先选择成绩文件".to_string()))
+}
+
+/// 读取上传内容失败时的错误, 请求体超过大小上限时提示文件过大
+fn upload_error(e: MultipartError, config: &AppConfig) -> FileError {
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        FileError::TooLarge(config.max_upload_size)
+    } else {
+        FileError::OpenError(e.body_text())
+    }
+}
+
+/// 按文件格式解析上传的成绩文件
+fn parse_upload(
+    file_name: &str, content_type: &str, data: &[u8], grading: &GradingTable
+) -> Result<(ParsedCourses, ResultSource), WebError> {
+    let parsed = match ImportFormat::detect(file_name, content_type, data) {
+        // 网页中带有课程性质, 因此可以像登录获取一样计算 Default 模式
+        ImportFormat::SavedPage => return Ok((parse_saved_page(data, grading)?, ResultSource::SavedPage)),
+        ImportFormat::Xlsx => parse_xlsx(data, grading)?,
+        ImportFormat::Xls => parse_xls(data, grading)?,
+        ImportFormat::Ods => parse_ods(data, grading)?,
+        ImportFormat::Csv => parse_csv(data, grading)?,
+        ImportFormat::Json => parse_json(data, grading)?
+    };
+
+    let source = file_source(&parsed);
+    Ok((parsed, source))
+}
+
+/// 课程带有课程性质时(如填写了课程性质的模板、教务系统导出的成绩单、本程序导出的 JSON)与成绩页面一样可以计算 Default 模式
+/// 只有部分课程填写了课程性质时也计算, 未填写的课程与课程性质不在排除规则中的课程一样在 Default 模式中计入
+fn file_source(parsed: &ParsedCourses) -> ResultSource {
+    match parsed.courses.iter().any(|c| !c.nature.is_empty()) {
+        true => ResultSource::SavedPage,
+        false => ResultSource::InputFile
+    }
+}
+
+/// 将计算结果存入 Session, 存在 Default 模式结果时允许在结果页切换模式
+/// 低内存模式下只保存全部课程, Default 与仅必修课模式的课程在读取时再筛选
+async fn save_results(session: &Session, results: ProcessedGPAResults, low_memory: bool) -> Result<(), WebError> {
+    let result_mode = if results.default.is_some() { "login" } else { "file" };
+
+    // Default 模式数据
+    session.remove::<Vec<Course>>("courses_default").await?;
+    if let Some(default_result) = results.default {
+        session.insert("gpa_default", default_result.gpa).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+        if !low_memory {
+            session.insert("courses_default", default_result.courses).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+        }
+    }
+
+    // All 模式数据
+    session.insert("gpa_all", results.all.gpa).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    session.insert("courses_all", results.all.courses).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    // 仅必修课模式数据
+    session.remove::<Vec<Course>>("courses_compulsory").await?;
+    session.insert("gpa_compulsory", results.compulsory.gpa).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    if !low_memory {
+        session.insert("courses_compulsory", results.compulsory.courses).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    }
+
+    // 学分异常提示
+    session.insert("credit_warnings", results.credit_warnings).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    // 学分完成情况
+    session.insert("credit_stats", results.credit_stats).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    // 解析时跳过的行
+    session.insert("skipped_rows", results.skipped_rows).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    // 特殊成绩而未计入绩点的课程
+    session.insert("uncounted_courses", results.uncounted).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    // 数据模式
+    session.insert("result_mode", result_mode).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// 记录新数据的来源与去重前的全部成绩
+/// 计算依据由数据来源得出; 修改重复课程的处理方式时从去重前的成绩重新计算, 低内存模式下不保存, 新的处理方式下次查询时生效
+async fn save_origin(session: &Session, origin: DataOrigin, raw_courses: &[Course], low_memory: bool) -> Result<(), WebError> {
+    session.remove::<Vec<Course>>("courses_raw").await?;
+    if !low_memory {
+        session.insert("courses_raw", raw_courses).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    }
+    session.insert("data_origin", origin).await.map_err(|e| WebError::InternalError(e.to_string()))
+}
+
+/// 当前结果的计算依据, 会话中没有记录数据来源(如旧版本保存的备份)时返回 None
+async fn load_provenance(session: &Session, profile: &SchoolProfile, mode: &str, scale: GradeScale) -> Result<Option<Provenance>, WebError> {
+    let Some(origin) = session.get::<DataOrigin>("data_origin").await? else {
+        return Ok(None);
+    };
+    let edited = session.get::<EditHistory>("edit_history").await?.unwrap_or_default().can_undo();
+
+    Ok(Some(Provenance::new(origin, edited, profile, mode, scale)))
 }
 
 // 负责从 Session 读取 Default 模式数据并返回给前端
-pub async fn first_result(session: Session, State(tera): State<Tera>) -> Result<impl IntoResponse, WebError> {
+pub async fn first_result(
+    session: Session, State(tera): State<Arc<Tera>>, State(settings): State<Arc<SettingsStore>>,
+    State(snapshots): State<Arc<SnapshotStore>>, State(profile): State<Arc<SchoolProfile>>
+) -> Result<impl IntoResponse, WebError> {
     #[cfg(debug_assertions)]
     print_info("正在从 Session 中读取数据...");
 
@@ -207,20 +843,15 @@ pub async fn first_result(session: Session, State(tera): State<Tera>) -> Result<
     let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
 
     // 适配免登录模式
-    let (gpa, courses): (Decimal, Vec<Course>) = match result_mode.as_str() {
-        "login" => {
-            (
-                session.get("gpa_default").await?.unwrap_or_default(),
-                session.get("courses_default").await?.unwrap_or_default()
-            )
-        }
-        _ => {
-            (
-                session.get("gpa_all").await?.unwrap_or_default(),
-                session.get("courses_all").await?.unwrap_or_default()
-            )
-        }
+    let result = match result_mode.as_str() {
+        "login" => load_mode_result(&session, "default", &profile).await?,
+        _ => load_mode_result(&session, "all", &profile).await?
     };
+    let GPAResult { gpa, courses, semesters, averages } = result.clone();
+
+    if courses.is_empty() && let Some(empty) = session.get::<EmptyTranscript>("empty_transcript").await? {
+        return render_empty_transcript(&session, &tera, &settings, &empty).await;
+    }
 
     if courses.is_empty() {
         #[cfg(debug_assertions)]
@@ -234,15 +865,80 @@ pub async fn first_result(session: Session, State(tera): State<Tera>) -> Result<
     #[cfg(debug_assertions)]
     print_info("成功从 Session 中读取到数据, 开始尝试渲染查询页面...");
 
+    let credit_warnings: Vec<String> = session.get("credit_warnings").await?.unwrap_or_default();
+    let credit_stats: CreditStats = session.get("credit_stats").await?.unwrap_or_default();
+    let skipped_rows: Vec<SkippedRow> = session.get("skipped_rows").await?.unwrap_or_default();
+    let uncounted_courses: Vec<UncountedCourse> = session.get("uncounted_courses").await?.unwrap_or_default();
+    let edit_history: EditHistory = session.get("edit_history").await?.unwrap_or_default();
+
+    // 登录查询的数据才有成绩历史
+    let history_key: Option<String> = session.get("history_key").await?;
+    let course_changes = match history_key {
+        Some(key) => tokio::task::spawn_blocking(move || snapshots.course_changes(&key))
+            .await
+            .map_err(|e| WebError::InternalError(e.to_string()))?
+            .unwrap_or_else(|e| {
+                print_error(&format!("读取成绩变动记录失败: {}", e));
+                Default::default()
+            }),
+        None => Default::default()
+    };
+    let grade_diff: Option<GradeDiff> = session.get("grade_diff").await?;
+
+    // 官方绩点只和 Default 模式对照
+    let official_gpa: Option<Decimal> = session.get("official_gpa").await?.flatten();
+    let student: Option<StudentProfile> = session.get("student_profile").await?.flatten();
+    let minor_result: Option<GPAResult> = session.get("minor_result").await?.flatten();
+    let official_comparison = match (result_mode.as_str(), official_gpa) {
+        ("login", Some(official)) => Some(compare_with_official(gpa, official)),
+        _ => None
+    };
+
     let mut context = tera::Context::new();
     context.insert("courses", &courses);
+    context.insert("show_sources", &courses.iter().any(|c| c.source != courses[0].source));   // 合并了多个来源时标注每门课程的来源
+    context.insert("wes_letters", &wes_letters(&courses, &profile.grading));
+    context.insert("letter_grades", &letter_grades(&courses));
     context.insert("gpa", &gpa);
     context.insert("result_mode", &result_mode);
+    context.insert("credit_warnings", &credit_warnings);
+    context.insert("credit_stats", &credit_stats);
+    context.insert("skipped_rows", &skipped_rows);
+    context.insert("uncounted_courses", &uncounted_courses);
+    context.insert("official_comparison", &official_comparison);
+    context.insert("can_undo", &edit_history.can_undo());
+    context.insert("can_redo", &edit_history.can_redo());
+    context.insert("course_changes", &course_changes);
+    context.insert("grade_diff", &grade_diff);
+    context.insert("student", &student);
+    context.insert("minor_result", &minor_result);
+    context.insert("semester_gpas", &semesters);
+    context.insert("averages", &averages);
+    context.insert("rounding", &profile.grading.rounding.label());
+    context.insert("scale_gpas", &scale_comparison(&result, &profile));
+    let mode = if result_mode == "login" { "default" } else { "all" };
+    let provenance = load_provenance(&session, &profile, mode_label(mode), GradeScale::School).await?;
+    context.insert("provenance_note", &provenance.map(|p| p.footnote()));
+    context.insert("grade_scales", &GRADE_SCALES.iter().map(|s| json!({"value": s, "label": s.label()})).collect::<Vec<_>>());
+    let all_courses: Vec<Course> = session.get("courses_all").await?.unwrap_or_default();
+    context.insert("academic_years", &academic_years(&all_courses));
+    context.insert("evaluation_rules", &profile.evaluation);
+    context.insert("max_grade", &profile.grading.max_grade().normalize());
+
+    // 目标绩点对比, 未设置时为空
+    let benchmark = settings.get().benchmark;
+    let comparison = benchmark.as_ref().map(|b| compare_with_benchmark(&result, b, profile.grading.max_grade()));
+    context.insert("benchmark", &benchmark);
+    context.insert("benchmark_comparison", &comparison);
 
     // 将排除的变量也传给前端
-    context.insert("excluded_courses", EXCLUDED_COURSES_KEYWORD);
-    context.insert("permanent_ignored_courses", PERMANENT_IGNORED_COURSES);
-    context.insert("nature_exclusions", NATURE_EXCLUSIONS);
+    context.insert("excluded_courses", &profile.exclusion.keywords);
+    context.insert("permanent_ignored_courses", &profile.exclusion.ignored_courses);
+    context.insert("nature_exclusions", &profile.exclusion.natures);
+    context.insert("min_credit", &profile.exclusion.min_credit);
+    context.insert("compulsory_natures", COMPULSORY_NATURES);
+    context.insert("retake_policy", &profile.retake_policy);
+    context.insert("retake_policies", &RETAKE_POLICIES.iter().map(|p| json!({"value": p, "label": p.label()})).collect::<Vec<_>>());
 
     let html = tera.render("result.html", &context).map_err(|e| WebError::TemplateError(e.to_string()))?;
 
@@ -256,70 +952,1239 @@ pub async fn first_result(session: Session, State(tera): State<Tera>) -> Result<
 }
 
 // 根据前端按钮重新计算 GPA
-pub async fn next_result(session: Session, Json(cal_mode): Json<CalculateMode>) -> Result<Json<serde_json::Value>, WebError> {
+pub async fn next_result(
+    session: Session, State(settings): State<Arc<SettingsStore>>, State(profile): State<Arc<SchoolProfile>>,
+    Json(cal_mode): Json<CalculateMode>
+) -> Result<Json<serde_json::Value>, WebError> {
     print_info("尝试切换计算模式...");
 
-    let (gpa, courses): (Decimal, Vec<Course>) = match cal_mode.mode.as_str() {
-        "all" => (
-            session.get("gpa_all").await?.unwrap_or_default(),
-            session.get("courses_all").await?.unwrap_or_default()
-        ),
-        _ => (
-            session.get("gpa_default").await?.unwrap_or_default(),
-            session.get("courses_default").await?.unwrap_or_default()
-        )
+    let result = match cal_mode.mode.as_str() {
+        "year" => {
+            let year = cal_mode.value.trim();
+            if !is_academic_year(year) {
+                return Err(WebError::InvalidInput(format!("无效的学年: {}, 格式应为 2023-2024", cal_mode.value)));
+            }
+
+            let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+            let base = resolve_mode(&result_mode, &cal_mode.base);
+            let base_result = load_mode_result(&session, base, &profile).await?;
+
+            year_gpa(&base_result.courses, year, &profile)
+        }
+        "filter" => {
+            let filter = cal_mode.filter.as_ref().ok_or_else(|| WebError::InvalidInput("自定义模式需要提供筛选条件".to_string()))?;
+            let all_result = load_mode_result(&session, "all", &profile).await?;
+
+            filter_gpa(&all_result.courses, filter, &profile).map_err(WebError::InvalidInput)?
+        }
+        "all" => load_mode_result(&session, "all", &profile).await?,
+        "compulsory" => load_mode_result(&session, "compulsory", &profile).await?,
+        _ => load_mode_result(&session, "default", &profile).await?
+    };
+    let result = match cal_mode.exclude_failed {
+        true => exclude_failed_courses(&result, &profile),
+        false => result
+    };
+
+    let mut mode = match cal_mode.mode.as_str() {
+        "year" => format!("学年 {}", cal_mode.value.trim()),
+        mode => mode_label(mode).to_string()
+    };
+    if cal_mode.exclude_failed {
+        mode.push_str(", 不计未通过课程");
+    }
+    let provenance = load_provenance(&session, &profile, &mode, cal_mode.scale).await?;
+
+    // 同一组课程在各换算标准下的绩点, 供前端并列显示
+    let scales = scale_comparison(&result, &profile);
+    let result = rescale_result(&result, cal_mode.scale, &profile);
+
+    // 目标绩点针对本校标准的累计绩点, 学年绩点与其他换算标准不做对比
+    let comparison = match (cal_mode.mode.as_str(), cal_mode.scale) {
+        ("year", _) => None,
+        (_, GradeScale::School) => settings.get().benchmark.map(|b| compare_with_benchmark(&result, &b, profile.grading.max_grade())),
+        _ => None
     };
 
     print_info("已切换计算模式");
 
-    Ok(Json(json!({"gpa": gpa, "courses": courses})))
+    Ok(Json(json!({
+        "gpa": result.gpa, "courses": result.courses, "semesters": result.semesters, "averages": result.averages, "benchmark_comparison": comparison, "scales": scales,
+        "wes_letters": wes_letters(&result.courses, &profile.grading), "letter_grades": letter_grades(&result.courses), "provenance": provenance.map(|p| p.footnote()), "rounding": profile.grading.rounding.label()
+    })))
 }
 
-// 关闭服务器
-pub async fn shutdown(Extension(shutdown_tx): Extension<broadcast::Sender<()>>) -> (StatusCode, &'static str) {
-    let _ = shutdown_tx.send(());
+/// 前端请求的计算模式, 未指定时与结果页默认显示的模式相同: 登录获取的数据为 default, 其余为 all
+fn resolve_mode(result_mode: &str, requested: &str) -> &'static str {
+    match requested {
+        "all" => "all",
+        "compulsory" => "compulsory",
+        _ if result_mode == "login" => "default",
+        _ => "all"
+    }
+}
 
-    (StatusCode::OK, "服务器正在关闭...")
+/// 从 Session 读取指定模式(default/all/compulsory)的绩点与课程
+/// 低内存模式下没有单独保存 Default 与仅必修课模式的课程, 此时从全部课程中重新筛选
+async fn load_mode_result(session: &Session, mode: &str, profile: &SchoolProfile) -> Result<GPAResult, WebError> {
+    let gpa: Decimal = session.get(&format!("gpa_{}", mode)).await?.unwrap_or_default();
+    let courses: Option<Vec<Course>> = session.get(&format!("courses_{}", mode)).await?;
+
+    let courses = match courses {
+        Some(courses) => courses,
+        None if mode == "default" => {
+            let all_courses: Vec<Course> = session.get("courses_all").await?.unwrap_or_default();
+            default_mode_courses(&all_courses, profile)
+        }
+        None if mode == "compulsory" => {
+            let all_courses: Vec<Course> = session.get("courses_all").await?.unwrap_or_default();
+            compulsory_mode_courses(&all_courses, profile)
+        }
+        None => Vec::new()
+    };
+
+    Ok(GPAResult::new(gpa, courses, profile))
 }
 
-// 退出登录
-pub async fn logout(session: Session) -> Result<Json<serde_json::Value>, WebError> {
-    session.delete().await.map_err(|e| WebError::InternalError(e.to_string()))?;
+/// 从 Session 读取全部模式的计算结果, 免登录模式没有 Default 模式
+async fn load_results(session: &Session, profile: &SchoolProfile) -> Result<ProcessedGPAResults, WebError> {
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let default = match result_mode.as_str() {
+        "login" => Some(load_mode_result(session, "default", profile).await?),
+        _ => None
+    };
 
-    print_info("用户退出登录, Session 会话已销毁");
+    Ok(ProcessedGPAResults {
+        default,
+        all: load_mode_result(session, "all", profile).await?,
+        compulsory: load_mode_result(session, "compulsory", profile).await?,
+        credit_warnings: session.get("credit_warnings").await?.unwrap_or_default(),
+        credit_stats: session.get("credit_stats").await?.unwrap_or_default(),
+        skipped_rows: session.get("skipped_rows").await?.unwrap_or_default(),
+        uncounted: session.get("uncounted_courses").await?.unwrap_or_default(),
+    })
+}
+
+// 各学期绩点的查询参数
+#[derive(Debug, Deserialize)]
+pub struct SemesterGPAQuery {
+    #[serde(default)]
+    mode: String,   // default、all 或 compulsory, 免登录模式没有 default
+}
 
-    // 创建变量遮蔽来确保锁能被尽快释放
-    {
-        // 获取互斥锁
-        let mut user_agent_guard = USER_AGENT.lock().unwrap();
+// 返回指定模式下各学期的绩点
+pub async fn semester_gpa(
+    session: Session, State(profile): State<Arc<SchoolProfile>>, Query(query): Query<SemesterGPAQuery>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let mode = resolve_mode(&result_mode, &query.mode);
 
-        // 生成新 UA
-        let new_user_agent = get_rua().to_string();
+    let result = load_mode_result(&session, mode, &profile).await?;
 
-        // 使用星号(*)解引用修改在锁保护下的数据
-        *user_agent_guard = new_user_agent.clone();
+    Ok(Json(json!({"mode": mode, "semesters": result.semesters})))
+}
 
-        #[cfg(debug_assertions)]
-        print_info(&format!("UA 已被刷新: {}", new_user_agent.clone()));
-    }
-    // 超出遮蔽区域, 锁被释放
+// 返回指定模式下逐学期的绩点走势, 供结果页绘制走势图
+pub async fn gpa_trend_series(
+    session: Session, State(profile): State<Arc<SchoolProfile>>, Query(query): Query<SemesterGPAQuery>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let mode = resolve_mode(&result_mode, &query.mode);
 
-    Ok(Json(json!({"success": true})))
+    let result = load_mode_result(&session, mode, &profile).await?;
+
+    Ok(Json(json!({"mode": mode, "trend": gpa_trend(&result.courses, &profile)})))
 }
 
-// 下载 xlsx 文件
-pub async fn download_temp() -> Result<impl IntoResponse, WebError> {
-    print_info("正在下载上传模板文件...");
+// 返回指定模式下各分数段与字母等级的课程数, 供结果页绘制成绩分布
+pub async fn grade_distribution_data(
+    session: Session, State(profile): State<Arc<SchoolProfile>>, Query(query): Query<SemesterGPAQuery>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let mode = resolve_mode(&result_mode, &query.mode);
+
+    let result = load_mode_result(&session, mode, &profile).await?;
 
-    match BinaryAsset::get("CoursesList.xlsx") {
-        Some(content) => {
-            let body = content.data;
-            let headers = [
-                (header::CONTENT_TYPE, "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
-                (header::CONTENT_DISPOSITION, "attachment; filename=CoursesList.xlsx")
-            ];
-            Ok((headers, body).into_response())
+    Ok(Json(json!({"mode": mode, "distribution": grade_distribution(&result.courses, &profile.grading)})))
+}
+
+// 返回指定模式下每门课程对平均绩点的影响, 拉低绩点最多的课程排在最前面
+pub async fn course_impact(
+    session: Session, State(profile): State<Arc<SchoolProfile>>, Query(query): Query<SemesterGPAQuery>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let mode = resolve_mode(&result_mode, &query.mode);
+
+    let result = load_mode_result(&session, mode, &profile).await?;
+
+    Ok(Json(json!({"mode": mode, "gpa": result.gpa, "reference_grade": IMPACT_REFERENCE_GRADE, "courses": course_impacts(&result, &profile)})))
+}
+
+// 删除课程
+pub async fn delete_course(
+    session: Session, State(config): State<Arc<AppConfig>>, State(profile): State<Arc<SchoolProfile>>, Json(edit): Json<CourseEdit>
+) -> Result<Json<serde_json::Value>, WebError> {
+    apply_course_edit(&session, config.low_memory, &profile, |courses| {
+        let index = courses.iter()
+            .position(|c| edit.matches(c))
+            .ok_or_else(|| WebError::InvalidInput(format!("未找到课程: {}", edit.describe())))?;
+        courses.remove(index);
+
+        print_info(&format!("已删除课程: {}", edit.describe()));
+        Ok(())
+    }).await
+}
+
+// 修改课程的学分或成绩
+pub async fn update_course(
+    session: Session, State(config): State<Arc<AppConfig>>, State(profile): State<Arc<SchoolProfile>>, Json(edit): Json<CourseEdit>
+) -> Result<Json<serde_json::Value>, WebError> {
+    apply_course_edit(&session, config.low_memory, &profile, |courses| {
+        let course = courses.iter_mut()
+            .find(|c| edit.matches(c))
+            .ok_or_else(|| WebError::InvalidInput(format!("未找到课程: {}", edit.describe())))?;
+
+        let mut edited = course.clone();
+        if let Some(credit) = edit.credit {
+            // 学分过大时计算学分绩点会溢出
+            if credit < Decimal::ZERO || credit > MAX_COURSE_CREDIT {
+                return Err(WebError::InvalidInput(format!("学分应在 0 到 {} 之间", MAX_COURSE_CREDIT)));
+            }
+            edited.credit = credit;
         }
-        None => Err(WebError::InternalError("未找到模板文件".to_string()))
+        if let Some(score) = &edit.score {
+            edited.score = Score::from(score.as_str());
+        }
+
+        *course = regrade_course(edited, &profile.grading).ok_or_else(|| WebError::InvalidInput(format!("无法识别的成绩: {}", edit.score.clone().unwrap_or_default())))?;
+
+        print_info(&format!("已修改课程: {}", edit.describe()));
+        Ok(())
+    }).await
+}
+
+// 撤销上一次课程编辑
+pub async fn undo_course_edit(
+    session: Session, State(config): State<Arc<AppConfig>>, State(profile): State<Arc<SchoolProfile>>
+) -> Result<Json<serde_json::Value>, WebError> {
+    restore_course_edit(&session, config.low_memory, &profile, EditHistory::undo, "没有可以撤销的操作").await
+}
+
+// 重做被撤销的课程编辑
+pub async fn redo_course_edit(
+    session: Session, State(config): State<Arc<AppConfig>>, State(profile): State<Arc<SchoolProfile>>
+) -> Result<Json<serde_json::Value>, WebError> {
+    restore_course_edit(&session, config.low_memory, &profile, EditHistory::redo, "没有可以重做的操作").await
+}
+
+/// 对 Session 中的全部课程执行编辑, 记录编辑前的状态后重新计算各模式结果
+async fn apply_course_edit(
+    session: &Session, low_memory: bool, profile: &SchoolProfile, edit: impl FnOnce(&mut Vec<Course>) -> Result<(), WebError>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let mut courses: Vec<Course> = session.get("courses_all").await?.unwrap_or_default();
+    if courses.is_empty() {
+        return Err(WebError::InvalidInput("没有可编辑的课程, 请先查询成绩或导入文件".to_string()));
     }
-}
\ No newline at end of file
+
+    let before = courses.clone();
+    edit(&mut courses)?;
+
+    let mut history: EditHistory = session.get("edit_history").await?.unwrap_or_default();
+    history.record(before);
+
+    recalc_and_save(session, &courses, low_memory, profile).await?;
+    session.insert("edit_history", &history).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    Ok(Json(json!({"success": true, "can_undo": history.can_undo(), "can_redo": history.can_redo()})))
+}
+
+/// 从编辑历史中恢复课程列表, step 为 EditHistory::undo 或 EditHistory::redo
+async fn restore_course_edit(
+    session: &Session, low_memory: bool, profile: &SchoolProfile, step: fn(&mut EditHistory, Vec<Course>) -> Option<Vec<Course>>, empty_msg: &str
+) -> Result<Json<serde_json::Value>, WebError> {
+    let current: Vec<Course> = session.get("courses_all").await?.unwrap_or_default();
+    let mut history: EditHistory = session.get("edit_history").await?.unwrap_or_default();
+
+    let restored = step(&mut history, current).ok_or_else(|| WebError::InvalidInput(empty_msg.to_string()))?;
+
+    recalc_and_save(session, &restored, low_memory, profile).await?;
+    session.insert("edit_history", &history).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    Ok(Json(json!({"success": true, "can_undo": history.can_undo(), "can_redo": history.can_redo()})))
+}
+
+/// 渲染"暂无成绩"页面
+async fn render_empty_transcript(
+    session: &Session, tera: &Tera, settings: &SettingsStore, empty: &EmptyTranscript
+) -> Result<Response, WebError> {
+    let student: Option<StudentProfile> = session.get("student_profile").await?.flatten();
+
+    let mut context = tera::Context::new();
+    context.insert("student", &student);
+    context.insert("semesters", &empty.semesters);
+    context.insert("checked_at", &empty.checked_at.format("%Y-%m-%d %H:%M").to_string());
+    context.insert("watch_new_grades", &settings.get().watch_new_grades);
+
+    let html = tera.render("empty.html", &context).map_err(|e| WebError::TemplateError(e.to_string()))?;
+
+    Ok(Html(html).into_response())
+}
+
+/// 按原有数据来源重新计算并存入 Session, 解析时跳过的行与特殊成绩而未计入的课程仍然保留
+async fn recalc_and_save(session: &Session, courses: &[Course], low_memory: bool, profile: &SchoolProfile) -> Result<(), WebError> {
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let source = match result_mode.as_str() {
+        "login" => ResultSource::OfficialWebsite,
+        _ => ResultSource::InputFile
+    };
+
+    let uncounted: Vec<UncountedCourse> = session.get("uncounted_courses").await?.unwrap_or_default();
+    let courses: Vec<Course> = courses.iter().cloned().chain(uncounted.into_iter().map(|u| u.course)).collect();
+
+    let mut results = process_scraped_course_results(&courses, source, profile);
+    results.skipped_rows = session.get("skipped_rows").await?.unwrap_or_default();
+    save_results(session, results, low_memory).await
+}
+
+// 设置或清除目标绩点, 请求体为 null 时清除
+pub async fn set_benchmark(
+    State(settings): State<Arc<SettingsStore>>, State(profile): State<Arc<SchoolProfile>>, Json(benchmark): Json<Option<Benchmark>>
+) -> Result<Json<serde_json::Value>, WebError> {
+    if let Some(b) = &benchmark {
+        let max_grade = profile.grading.max_grade();
+        if b.gpa <= Decimal::ZERO || b.gpa > max_grade {
+            return Err(WebError::InvalidInput(format!("目标绩点需在 0 到 {} 之间", max_grade.normalize())));
+        }
+        if b.remaining_semesters > 0 && b.credits_per_semester <= Decimal::ZERO {
+            return Err(WebError::InvalidInput("每学期学分需大于 0".to_string()));
+        }
+    }
+
+    settings.update(|s| s.benchmark = benchmark.clone()).map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    match &benchmark {
+        Some(b) => print_info(&format!("目标绩点已设置为 {}", b.gpa)),
+        None => print_info("目标绩点已清除")
+    }
+
+    Ok(Json(json!({"success": true})))
+}
+
+// 开启或关闭出成绩提醒
+#[derive(Debug, Deserialize)]
+pub struct WatchRequest {
+    enabled: bool,
+}
+
+pub async fn set_watch(
+    State(settings): State<Arc<SettingsStore>>, State(watcher): State<Arc<GradeWatcher>>, Json(request): Json<WatchRequest>
+) -> Result<Json<serde_json::Value>, WebError> {
+    settings.update(|s| s.watch_new_grades = request.enabled).map_err(|e| WebError::InternalError(e.to_string()))?;
+    if !request.enabled {
+        watcher.forget();
+    }
+
+    print_info(if request.enabled { "已开启出成绩提醒" } else { "已关闭出成绩提醒" });
+
+    Ok(Json(json!({"success": true})))
+}
+
+// 后台成绩监控的状态, 包括最近一次检查的时间与发现的变化
+pub async fn watch_status(
+    session: Session, State(settings): State<Arc<SettingsStore>>, State(watcher): State<Arc<GradeWatcher>>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let viewer: Option<String> = session.get("watch_account").await?;
+
+    Ok(Json(json!(watcher.status(settings.get().watch_new_grades, viewer.as_deref()))))
+}
+
+// 导出用户设置与学校规则
+pub async fn export_settings(
+    State(settings): State<Arc<SettingsStore>>, State(profile): State<Arc<SchoolProfile>>
+) -> Result<impl IntoResponse, WebError> {
+    let bundle = SettingsBundle::new(settings.get(), (*profile).clone());
+    let body = serde_json::to_vec_pretty(&bundle).map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    print_info("正在导出设置文件...");
+
+    let headers = [
+        (header::CONTENT_TYPE, "application/json"),
+        (header::CONTENT_DISPOSITION, "attachment; filename=yit-gpa-settings.json")
+    ];
+
+    Ok((headers, body).into_response())
+}
+
+// 导入设置文件, 替换当前的用户设置与学校规则
+pub async fn import_settings(
+    State(config): State<Arc<AppConfig>>, State(settings): State<Arc<SettingsStore>>, State(profiles): State<Arc<ProfileStore>>, mut multipart: Multipart
+) -> Result<Json<serde_json::Value>, WebError> {
+    while let Some(field) = multipart.next_field().await.map_err(|e| upload_error(e, &config))? {
+        if field.name() == Some("settings_file") {
+            let data = field.bytes().await.map_err(|e| upload_error(e, &config))?;
+            let mut bundle = SettingsBundle::parse(&data).map_err(|e| WebError::InvalidInput(e.to_string()))?;
+
+            // 先检查学校规则, 避免设置已经写入而规则被拒绝, 只导入了一半
+            profiles.validate(&mut bundle.profile).map_err(|e| WebError::InvalidInput(format!("设置文件中的学校规则有误: {}", e)))?;
+
+            settings.update(|s| *s = bundle.settings).map_err(|e| WebError::InternalError(e.to_string()))?;
+            profiles.replace(bundle.profile).map_err(|e| WebError::InternalError(e.to_string()))?;
+
+            print_info(&format!("已导入设置文件(导出于 {})", bundle.exported_at.format("%Y-%m-%d %H:%M")));
+
+            return Ok(Json(json!({"success": true})));
+        }
+    }
+
+    Err(WebError::InvalidInput("未找到上传的设置文件".to_string()))
+}
+
+// 增删排除规则的请求
+#[derive(Debug, Deserialize)]
+pub struct ExclusionEdit {
+    list: ExclusionList,    // keyword、nature 或 ignored
+    value: String,
+    #[serde(default)]
+    remove: bool,   // true 为删除, 否则为添加
+}
+
+// 读取当前的排除规则
+pub async fn get_exclusions(State(profile): State<Arc<SchoolProfile>>) -> Json<serde_json::Value> {
+    Json(json!(profile.exclusion))
+}
+
+// 增删一条排除规则, 保存到 profile.json 后按新规则重新计算当前结果
+pub async fn edit_exclusions(
+    session: Session, State(config): State<Arc<AppConfig>>, State(profiles): State<Arc<ProfileStore>>,
+    Json(edit): Json<ExclusionEdit>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let value = edit.value.trim().to_string();
+    if value.is_empty() {
+        return Err(WebError::InvalidInput("排除规则不能为空".to_string()));
+    }
+    if !edit.remove {
+        ExclusionRules::check_value(&value).map_err(|e| WebError::InvalidInput(e.to_string()))?;
+    }
+
+    let mut current = profiles.get().exclusion.clone();
+    let list = current.list_mut(edit.list);
+    match (edit.remove, list.contains(&value)) {
+        (true, false) => return Err(WebError::InvalidInput(format!("排除规则中没有 {}", value))),
+        (false, true) => return Err(WebError::InvalidInput(format!("排除规则中已有 {}", value))),
+        _ => {}
+    }
+
+    let profile = profiles.update(|p| {
+        let list = p.exclusion.list_mut(edit.list);
+        if edit.remove {
+            list.retain(|v| *v != value);
+        } else {
+            list.push(value.clone());
+        }
+    }).map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    recalc_session(&session, config.low_memory, &profile).await?;
+
+    print_info(&format!("已{}排除规则: {}", if edit.remove { "删除" } else { "添加" }, value));
+
+    Ok(Json(json!({"success": true, "exclusion": profile.exclusion})))
+}
+
+// 修改重复课程处理方式的请求
+#[derive(Debug, Deserialize)]
+pub struct RetakePolicyRequest {
+    policy: RetakePolicy,
+}
+
+// 修改补考、重修等重复课程的处理方式, 保存到 profile.json 后从去重前的成绩重新计算当前结果
+// 课程编辑是在旧的去重结果上进行的, 重新计算后无法再撤销
+pub async fn set_retake_policy(
+    session: Session, State(config): State<Arc<AppConfig>>, State(profiles): State<Arc<ProfileStore>>,
+    State(summary): State<Arc<SummaryStore>>, Json(request): Json<RetakePolicyRequest>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let profile = profiles.update(|p| p.retake_policy = request.policy).map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    reprocess_raw_courses(&session, config.low_memory, &summary, &profile).await?;
+
+    print_info(&format!("重复课程的处理方式已改为: {}", profile.retake_policy.label()));
+
+    Ok(Json(json!({"success": true, "policy": profile.retake_policy})))
+}
+
+/// 从去重前的成绩按新规则重新计算当前结果, 低内存模式下没有保存去重前的成绩, 跳过
+/// 课程编辑是在旧结果上进行的, 重新计算后清空撤销记录
+async fn reprocess_raw_courses(session: &Session, low_memory: bool, summary: &SummaryStore, profile: &SchoolProfile) -> Result<(), WebError> {
+    let raw_courses: Vec<Course> = session.get("courses_raw").await?.unwrap_or_default();
+    if raw_courses.is_empty() {
+        return Ok(());
+    }
+
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let source = if result_mode == "login" { ResultSource::OfficialWebsite } else { ResultSource::InputFile };
+
+    let mut results = process_scraped_course_results(&raw_courses, source, profile);
+    results.skipped_rows = session.get("skipped_rows").await?.unwrap_or_default();
+    summary.update(Summary::from_results(&results));
+    save_results(session, results, low_memory).await?;
+    session.remove::<EditHistory>("edit_history").await?;
+
+    Ok(())
+}
+
+// 已注册的计算策略, 以及当前使用的策略
+pub async fn list_gpa_policies(State(profiles): State<Arc<ProfileStore>>) -> Json<serde_json::Value> {
+    let policies: Vec<serde_json::Value> = profiles.policies().list().iter().map(|p| json!({"name": p.name(), "label": p.label()})).collect();
+
+    Json(json!({"current": profiles.get().policy, "policies": policies}))
+}
+
+// 切换计算策略的请求
+#[derive(Debug, Deserialize)]
+pub struct GPAPolicyRequest {
+    name: String,
+}
+
+// 切换计算策略, 保存到 profile.json 后从去重前的成绩重新计算当前结果
+pub async fn set_gpa_policy(
+    session: Session, State(config): State<Arc<AppConfig>>, State(profiles): State<Arc<ProfileStore>>,
+    State(summary): State<Arc<SummaryStore>>, Json(request): Json<GPAPolicyRequest>
+) -> Result<Json<serde_json::Value>, WebError> {
+    if profiles.policies().get(&request.name).is_none() {
+        return Err(WebError::InvalidInput(format!("未知的计算策略: {}", request.name)));
+    }
+    let profile = profiles.update(|p| p.policy = request.name.clone()).map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    reprocess_raw_courses(&session, config.low_memory, &summary, &profile).await?;
+
+    print_info(&format!("计算策略已改为: {}", profile.policy().label()));
+
+    Ok(Json(json!({"success": true, "policy": profile.policy})))
+}
+
+// 把排除列表恢复为程序内置的列表, 学分下限保持不变
+pub async fn reset_exclusions(
+    session: Session, State(config): State<Arc<AppConfig>>, State(profiles): State<Arc<ProfileStore>>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let profile = profiles.update(|p| {
+        p.exclusion = ExclusionRules { min_credit: p.exclusion.min_credit, ..ExclusionRules::default() };
+    }).map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    recalc_session(&session, config.low_memory, &profile).await?;
+
+    print_info("排除规则已恢复为内置列表");
+
+    Ok(Json(json!({"success": true, "exclusion": profile.exclusion})))
+}
+
+/// 规则修改后按新规则重新计算当前 Session 中的结果, 没有数据时跳过
+async fn recalc_session(session: &Session, low_memory: bool, profile: &SchoolProfile) -> Result<(), WebError> {
+    let courses: Vec<Course> = session.get("courses_all").await?.unwrap_or_default();
+    if courses.is_empty() {
+        return Ok(());
+    }
+
+    recalc_and_save(session, &courses, low_memory, profile).await
+}
+
+// 按学校规则计算综测总分, 智育部分使用与结果页默认显示相同的课程
+pub async fn calculate_comprehensive(
+    session: Session, State(profile): State<Arc<SchoolProfile>>, Json(input): Json<EvaluationInput>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let mode = if result_mode == "login" { "default" } else { "all" };
+    let GPAResult { courses, .. } = load_mode_result(&session, mode, &profile).await?;
+
+    if courses.is_empty() {
+        return Err(WebError::InvalidInput("没有可用于计算的数据, 请先查询成绩或导入文件".to_string()));
+    }
+
+    let result = calculate_evaluation(&courses, &profile, &input).map_err(WebError::InvalidInput)?;
+
+    print_info(&format!("综测总分计算完成: {}", result.total));
+
+    Ok(Json(json!(result)))
+}
+
+// 目标绩点规划的请求
+#[derive(Debug, Deserialize)]
+pub struct PlanRequest {
+    target: Decimal,    // 目标累计绩点
+    remaining_credits: Decimal, // 之后还要修读的学分
+    #[serde(default)]
+    mode: String,   // default、all 或 compulsory, 免登录模式没有 default
+}
+
+// 计算剩余课程至少需要达到的平均绩点, 以及目标是否还能达到
+pub async fn plan_target_gpa(
+    session: Session, State(profile): State<Arc<SchoolProfile>>, Json(request): Json<PlanRequest>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let mode = resolve_mode(&result_mode, &request.mode);
+    let result = load_mode_result(&session, mode, &profile).await?;
+
+    if result.courses.is_empty() {
+        return Err(WebError::InvalidInput("没有可用于计算的数据, 请先查询成绩或导入文件".to_string()));
+    }
+
+    let plan = plan_target(&result, request.target, request.remaining_credits, profile.grading.max_grade()).map_err(WebError::InvalidInput)?;
+
+    print_info(&format!("目标绩点规划完成: 目标 {}, 剩余课程需平均 {:?}", plan.target, plan.required_gpa));
+
+    Ok(Json(json!(plan)))
+}
+
+// 排名估算的请求
+#[derive(Debug, Deserialize)]
+pub struct RankingRequest {
+    #[serde(default)]
+    basis: RankingBasis,
+    class_average: Decimal, // 班级平均绩点或平均分
+    #[serde(default)]
+    std_dev: Option<Decimal>,   // 班级标准差, 不知道时留空使用默认值
+    #[serde(default)]
+    class_size: Option<u32>,
+    #[serde(default)]
+    mode: String,   // default、all 或 compulsory, 免登录模式没有 default
+}
+
+// 根据班级平均值估算本人大约位于前百分之几
+pub async fn estimate_class_ranking(
+    session: Session, State(profile): State<Arc<SchoolProfile>>, Json(request): Json<RankingRequest>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let mode = resolve_mode(&result_mode, &request.mode);
+    let result = load_mode_result(&session, mode, &profile).await?;
+
+    if result.courses.is_empty() {
+        return Err(WebError::InvalidInput("没有可用于计算的数据, 请先查询成绩或导入文件".to_string()));
+    }
+
+    let estimate = estimate_ranking(&result, request.basis, request.class_average, request.std_dev, request.class_size)
+        .map_err(WebError::InvalidInput)?;
+
+    print_info(&format!("排名估算完成: 约前 {}%", estimate.top_percent));
+
+    Ok(Json(json!(estimate)))
+}
+
+// 模拟计算的请求
+#[derive(Debug, Deserialize)]
+pub struct WhatIfRequest {
+    #[serde(default)]
+    mode: String,   // default、all 或 compulsory, 免登录模式没有 default
+    courses: Vec<HypotheticalCourse>,
+}
+
+// 在当前结果中加入假设的课程, 返回模拟的绩点, 不修改 Session 中的数据
+pub async fn simulate_what_if(
+    session: Session, State(profile): State<Arc<SchoolProfile>>, Json(request): Json<WhatIfRequest>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let mode = resolve_mode(&result_mode, &request.mode);
+    let result = load_mode_result(&session, mode, &profile).await?;
+
+    if result.courses.is_empty() {
+        return Err(WebError::InvalidInput("没有可用于计算的数据, 请先查询成绩或导入文件".to_string()));
+    }
+
+    let what_if = simulate_courses(&result, &request.courses, mode == "default", &profile).map_err(WebError::InvalidInput)?;
+
+    print_info(&format!("模拟计算完成: 加入 {} 门假设课程后绩点为 {}", what_if.courses.len(), what_if.simulated_gpa));
+
+    Ok(Json(json!(what_if)))
+}
+
+/// 备份 Session 中当前的结果, 没有数据时跳过
+/// 备份失败只记录日志, 不阻止用户继续操作
+async fn backup_session(session: &Session, backups: &BackupStore, reason: &str) -> Result<(), WebError> {
+    let courses: Vec<Course> = session.get("courses_all").await?.unwrap_or_default();
+    if courses.is_empty() {
+        return Ok(());
+    }
+
+    let backup = Backup::new(
+        reason,
+        session.get("result_mode").await?.unwrap_or("file".to_string()),
+        courses,
+        session.get::<Option<Decimal>>("official_gpa").await?.flatten(),
+        session.get("history_key").await?,
+        session.get("data_origin").await?,
+        backup_owners(session).await?.swap_remove(0),
+    );
+
+    match backups.create(&backup) {
+        Ok(()) => print_info(&format!("{}前已自动备份当前结果: {}", reason, backup.id)),
+        Err(e) => print_error(&format!("自动备份失败: {}", e))
+    }
+
+    Ok(())
+}
+
+/// 当前会话可以查看与恢复的备份的所有者, 第一个为新建备份时使用的所有者
+/// 登录查询的结果属于该账号, 换了浏览器再登录同一账号也能找回; 其他结果属于当前会话, 第一次备份时生成会话的随机标识
+async fn backup_owners(session: &Session) -> Result<Vec<String>, WebError> {
+    let token = match session.get::<String>("backup_owner").await? {
+        Some(token) => token,
+        None => {
+            let token = format!("{:032x}", rand::rng().random::<u128>());
+            session.insert("backup_owner", &token).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+            token
+        }
+    };
+
+    Ok(match session.get::<String>("history_key").await? {
+        Some(key) => vec![key, token],
+        None => vec![token]
+    })
+}
+
+// 一种计算模式的绩点, 用于列出历史快照
+#[derive(Debug, Serialize)]
+struct ModeGpa {
+    label: &'static str,
+    gpa: Decimal,
+    course_count: usize,
+}
+
+/// 按当前的学校规则计算快照各模式的绩点, 没有课程性质时只有全部课程模式
+fn snapshot_gpas(courses: &[Course], profile: &SchoolProfile) -> (ProcessedGPAResults, Vec<ModeGpa>) {
+    let results = process_scraped_course_results(courses, ResultSource::OfficialWebsite, profile);
+
+    let mut modes = Vec::new();
+    if let Some(default) = &results.default {
+        modes.push(ModeGpa { label: mode_label("default"), gpa: default.gpa, course_count: default.courses.len() });
+    }
+    modes.push(ModeGpa { label: mode_label("all"), gpa: results.all.gpa, course_count: results.all.courses.len() });
+    if !results.compulsory.courses.is_empty() {
+        modes.push(ModeGpa { label: mode_label("compulsory"), gpa: results.compulsory.gpa, course_count: results.compulsory.courses.len() });
+    }
+
+    (results, modes)
+}
+
+// 备份记录页面, 登录查询的结果另外列出该账号历次查询保存的快照
+// 列表只读取快照的查询时间与课程数, 绩点在打开快照时才计算
+pub async fn history_page(
+    session: Session, State(tera): State<Arc<Tera>>, State(backups): State<Arc<BackupStore>>,
+    State(snapshots): State<Arc<SnapshotStore>>
+) -> Result<Html<String>, WebError> {
+    let history_key: Option<String> = session.get("history_key").await?;
+
+    // 快照读取失败时只记录日志, 不影响备份记录的显示
+    let snapshot_rows: Vec<SnapshotInfo> = match history_key.clone() {
+        // 数据库访问是阻塞调用, 放到阻塞线程中执行
+        Some(key) => tokio::task::spawn_blocking(move || snapshots.list(&key))
+            .await
+            .map_err(|e| WebError::InternalError(e.to_string()))?
+            .unwrap_or_else(|e| {
+                print_error(&format!("读取成绩快照失败: {}", e));
+                Vec::new()
+            }),
+        None => Vec::new()
+    };
+
+    let mut context = tera::Context::new();
+    context.insert("backups", &backups.list(&backup_owners(&session).await?));
+    context.insert("can_search", &history_key.is_some());   // 只有登录查询的结果才有成绩历史
+    context.insert("snapshots", &snapshot_rows);
+
+    let html = tera.render("history.html", &context).map_err(|e| WebError::TemplateError(e.to_string()))?;
+
+    Ok(Html(html))
+}
+
+// 以只读方式查看一份历史快照, 绩点按当前的学校规则重新计算, 不影响当前结果
+pub async fn snapshot_page(
+    session: Session, State(tera): State<Arc<Tera>>, State(snapshots): State<Arc<SnapshotStore>>,
+    State(profile): State<Arc<SchoolProfile>>, Path(id): Path<i64>
+) -> Result<Html<String>, WebError> {
+    let key: String = session.get("history_key").await?
+        .ok_or_else(|| WebError::InvalidInput("当前结果不是登录查询得到的, 没有成绩历史".to_string()))?;
+
+    let (taken_at, courses) = tokio::task::spawn_blocking(move || snapshots.load(&key, id))
+        .await
+        .map_err(|e| WebError::InternalError(e.to_string()))?
+        .map_err(|e| WebError::InternalError(e.to_string()))?
+        .ok_or_else(|| WebError::InvalidInput("成绩快照不存在".to_string()))?;
+    let (results, modes) = snapshot_gpas(&courses, &profile);
+
+    let mut context = tera::Context::new();
+    context.insert("id", &id);
+    context.insert("taken_at", &taken_at);
+    context.insert("modes", &modes);
+    context.insert("courses", &results.all.courses);
+    context.insert("uncounted_courses", &results.uncounted);
+
+    let html = tera.render("snapshot.html", &context).map_err(|e| WebError::TemplateError(e.to_string()))?;
+
+    Ok(Html(html))
+}
+
+// 搜索历史成绩的请求
+#[derive(Debug, Deserialize)]
+pub struct HistorySearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+// 在当前账号的成绩历史中按课程名称搜索, 如"我什么时候修的离散数学"
+pub async fn search_history(
+    session: Session, State(snapshots): State<Arc<SnapshotStore>>, Query(query): Query<HistorySearchQuery>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let key: String = session.get("history_key").await?
+        .ok_or_else(|| WebError::InvalidInput("当前结果不是登录查询得到的, 没有成绩历史".to_string()))?;
+
+    let q = query.q.clone();
+    let matches = tokio::task::spawn_blocking(move || snapshots.search(&key, &q))
+        .await
+        .map_err(|e| WebError::InternalError(e.to_string()))?
+        .map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    #[cfg(debug_assertions)]
+    print_info(&format!("搜索成绩历史 \"{}\", 找到 {} 条", query.q, matches.len()));
+
+    Ok(Json(json!({"matches": matches})))
+}
+
+// 恢复备份的请求
+#[derive(Debug, Deserialize)]
+pub struct RestoreRequest {
+    id: String,
+}
+
+// 从备份恢复结果, 当前结果会先被备份
+pub async fn restore_backup(
+    session: Session, State(config): State<Arc<AppConfig>>, State(backups): State<Arc<BackupStore>>,
+    State(summary): State<Arc<SummaryStore>>, State(profile): State<Arc<SchoolProfile>>, Json(request): Json<RestoreRequest>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let backup = backups.load(&request.id).map_err(|e| WebError::InvalidInput(e.to_string()))?;
+    // 只能恢复自己的备份, 与不存在的备份给出同样的提示
+    if !backup.is_owned_by(&backup_owners(&session).await?) {
+        return Err(WebError::InvalidInput(format!("找不到备份 {}", request.id)));
+    }
+
+    backup_session(&session, &backups, "恢复备份").await?;
+
+    let source = if backup.result_mode == "login" { ResultSource::OfficialWebsite } else { ResultSource::InputFile };
+    let results = process_scraped_course_results(&backup.courses, source, &profile);
+    summary.update(Summary::from_results(&results));
+    save_results(&session, results, config.low_memory).await?;
+    session.remove::<EditHistory>("edit_history").await?;
+    session.remove::<Option<StudentProfile>>("student_profile").await?;   // 备份中不含学生信息与辅修绩点
+    session.remove::<Option<GPAResult>>("minor_result").await?;
+    session.remove::<EmptyTranscript>("empty_transcript").await?;
+    session.remove::<GradeDiff>("grade_diff").await?;
+    session.insert("official_gpa", backup.official_gpa).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+    // 旧版本的备份没有记录数据来源, 以备份时间代替获取时间
+    let origin = backup.origin.unwrap_or_else(|| DataOrigin { source: format!("备份 {}", backup.id), fetched_at: backup.created_at });
+    save_origin(&session, origin, &backup.courses, config.low_memory).await?;
+    match backup.history_key {
+        Some(key) => session.insert("history_key", key).await.map_err(|e| WebError::InternalError(e.to_string()))?,
+        None => { session.remove::<String>("history_key").await?; }
+    }
+
+    print_info(&format!("已从备份 {} 恢复 {} 门课程", backup.id, backup.courses.len()));
+
+    Ok(Json(json!({"success": true})))
+}
+
+// 关闭服务器
+pub async fn shutdown(Extension(shutdown_tx): Extension<broadcast::Sender<()>>) -> (StatusCode, &'static str) {
+    let _ = shutdown_tx.send(());
+
+    (StatusCode::OK, "服务器正在关闭...")
+}
+
+// 退出登录
+pub async fn logout(
+    session: Session, State(backups): State<Arc<BackupStore>>, State(watcher): State<Arc<GradeWatcher>>
+) -> Result<Json<serde_json::Value>, WebError> {
+    backup_session(&session, &backups, "注销会话").await?;
+    // 注销后不再使用这个账号查询; 监控的是其他人登录的账号时不受影响
+    if let Some(key) = session.get::<String>("watch_account").await? {
+        watcher.forget_account(&key);
+    }
+
+    session.delete().await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    print_info("用户退出登录, Session 会话已销毁");
+
+    Ok(Json(json!({"success": true})))
+}
+
+// 下载 xlsx 模板文件, 按当前的解析规则与换算表生成
+pub async fn download_temp(State(profile): State<Arc<SchoolProfile>>) -> Result<impl IntoResponse, WebError> {
+    print_info("正在生成上传模板文件...");
+
+    let body = upload_template(&profile).map_err(|e| WebError::InternalError(e.to_string()))?;
+    let headers = [
+        (header::CONTENT_TYPE, "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        (header::CONTENT_DISPOSITION, "attachment; filename=CoursesList.xlsx")
+    ];
+    Ok((headers, body).into_response())
+}
+
+// 按指定格式导出结果
+pub async fn export_file(
+    session: Session, State(exporters): State<Arc<ExporterRegistry>>, State(profile): State<Arc<SchoolProfile>>,
+    Path(format): Path<String>, Query(options): Query<ExportOptions>
+) -> Result<impl IntoResponse, WebError> {
+    let exporter = find_exporter(&exporters, &format)?;
+    let snapshot = load_snapshot(&session, &options, &profile, exporters.signer()).await?;
+
+    print_info(&format!("正在导出 {} 文件, 共{}门课程...", format.to_uppercase(), snapshot.courses.len()));
+
+    let file = render(exporter, snapshot).await.map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    Ok(download_response(file))
+}
+
+// 创建导出任务, 文件在后台生成, 前端轮询任务状态后再下载
+pub async fn start_export_job(
+    session: Session, State(exporters): State<Arc<ExporterRegistry>>, State(jobs): State<Arc<ExportJobs>>,
+    State(profile): State<Arc<SchoolProfile>>, Path(format): Path<String>, Query(options): Query<ExportOptions>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let exporter = find_exporter(&exporters, &format)?;
+    let snapshot = load_snapshot(&session, &options, &profile, exporters.signer()).await?;
+
+    let job_id = jobs.start(exporter, snapshot);
+
+    print_info(&format!("已创建 {} 导出任务 {}", format.to_uppercase(), job_id));
+
+    Ok(Json(json!({"job_id": job_id})))
+}
+
+// 查询导出任务的状态
+pub async fn export_job_status(
+    State(jobs): State<Arc<ExportJobs>>, Path(id): Path<String>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let status = jobs.status(&id).ok_or_else(|| WebError::InvalidInput("导出任务不存在或已过期".to_string()))?;
+
+    Ok(Json(json!(status)))
+}
+
+// 下载已完成的导出文件, 每个任务只能下载一次
+pub async fn download_export_job(
+    State(jobs): State<Arc<ExportJobs>>, Path(id): Path<String>
+) -> Result<Response, WebError> {
+    let file = jobs.take(&id).ok_or_else(|| WebError::InvalidInput("导出文件尚未生成完成或已过期".to_string()))?;
+
+    Ok(download_response(file))
+}
+
+/// 按名称查找导出格式
+fn find_exporter(exporters: &ExporterRegistry, format: &str) -> Result<Arc<dyn Exporter>, WebError> {
+    exporters.get(format).ok_or_else(|| WebError::InvalidInput(format!(
+        "不支持的导出格式 {}, 可用格式: {}", format, exporters.names().join(", ")
+    )))
+}
+
+/// 以附件形式返回导出文件
+fn download_response(file: ExportFile) -> Response {
+    let headers = [
+        (header::CONTENT_TYPE, file.mime.to_string()),
+        (header::CONTENT_DISPOSITION, format!("attachment; filename={}", file.file_name))
+    ];
+
+    (headers, file.body).into_response()
+}
+
+/// 从 Session 读取指定模式的计算结果用于导出, 免登录模式只有 All 模式数据
+async fn load_snapshot(
+    session: &Session, options: &ExportOptions, profile: &SchoolProfile, signer: Arc<ExportSigner>
+) -> Result<Snapshot, WebError> {
+    let result_mode: String = session.get("result_mode").await?.unwrap_or("file".to_string());
+    let mode = resolve_mode(&result_mode, &options.mode);
+
+    let GPAResult { gpa, courses, averages, .. } = load_mode_result(session, mode, profile).await?;
+    let provenance = load_provenance(session, profile, mode_label(mode), GradeScale::School).await?;
+    let student: Option<StudentProfile> = session.get("student_profile").await?.flatten();
+
+    let results = load_results(session, profile).await?;
+
+    if courses.is_empty() {
+        return Err(WebError::InvalidInput("没有可导出的数据, 请先查询成绩或导入文件".to_string()));
+    }
+
+    Ok(Snapshot {
+        gpa, averages, courses, mode: mode.to_string(), generated_at: Local::now(), locale: options.locale, provenance, student, results, signer
+    })
+}
+
+// 校验上传的导出文件是否被修改过
+pub async fn verify(
+    State(config): State<Arc<AppConfig>>, State(exporters): State<Arc<ExporterRegistry>>, mut multipart: Multipart
+) -> Result<Json<serde_json::Value>, WebError> {
+    while let Some(field) = multipart.next_field().await.map_err(|e| upload_error(e, &config))? {
+        if field.name() == Some("export_file") {
+            let data = field.bytes().await.map_err(|e| upload_error(e, &config))?;
+            let result = verify_export(&data, &exporters.signer());
+
+            print_info(&format!("导出文件校验完成: {}", if result.valid { "通过" } else { "未通过" }));
+
+            return Ok(Json(json!(result)));
+        }
+    }
+
+    Err(WebError::InvalidInput("未找到上传的导出文件".to_string()))
+}
+
+// 班级统计页面
+pub async fn cohort_page(State(tera): State<Arc<Tera>>) -> Result<Html<String>, WebError> {
+    let mut context = tera::Context::new();
+    context.insert("min_cohort_size", &MIN_COHORT_SIZE);
+
+    let html = tera.render("cohort.html", &context).map_err(|e| WebError::TemplateError(e.to_string()))?;
+
+    Ok(Html(html))
+}
+
+// 班级绩点分布的参数
+#[derive(Debug, Deserialize)]
+pub struct CohortQuery {
+    #[serde(default)]
+    mode: String,   // default 或 all, 为空时按 all 计算
+    #[serde(default)]
+    format: String, // 为 csv 时直接下载统计结果, 否则返回 JSON
+}
+
+// 辅导员批量上传全班的成绩文件, 每个文件对应一名学生, 只返回全班的绩点分布
+// 文件只在内存中解析, 不写入 Session 与数据目录; 无法解析的文件以序号提示, 不回显文件名
+pub async fn cohort_distribution(
+    State(config): State<Arc<AppConfig>>, State(profile): State<Arc<SchoolProfile>>, Query(query): Query<CohortQuery>, mut multipart: Multipart
+) -> Result<Response, WebError> {
+    let default_mode = cohort_default_mode(&query.mode)?;
+
+    let mut gpas: Vec<Decimal> = Vec::new();
+    let mut skipped: Vec<String> = Vec::new();
+    let mut index = 0;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| upload_error(e, &config))? {
+        if field.name() != Some("gpa_file") {
+            continue;
+        }
+        index += 1;
+
+        let file_name = field.file_name().unwrap_or_default().to_string();
+        let content_type = field.content_type().unwrap_or_default().to_string();
+        let data = field.bytes().await.map_err(|e| upload_error(e, &config))?;
+
+        match cohort_gpa(&file_name, &content_type, &data, default_mode, &profile) {
+            Ok(gpa) => gpas.push(gpa),
+            Err(reason) => skipped.push(format!("第 {} 个文件{}", index, reason))
+        }
+    }
+
+    let distribution = cohort_report(Distribution::from_gpas(gpas.clone(), profile.grading.max_grade()), gpas.len())?;
+
+    print_info(&format!("班级绩点分布统计完成: {} 人, 跳过 {} 个文件", distribution.count, skipped.len()));
+
+    if query.format == "csv" {
+        return cohort_csv(&distribution);
+    }
+
+    Ok(Json(json!({"distribution": distribution, "skipped": skipped})).into_response())
+}
+
+// 创建分批上传的班级统计, 前端逐个上传文件, 中断后凭 ID 继续
+pub async fn create_cohort_batch(
+    State(resume): State<Arc<ResumeStore>>, Query(query): Query<CohortQuery>
+) -> Result<Json<serde_json::Value>, WebError> {
+    cohort_default_mode(&query.mode)?;
+
+    let id = format!("{:032x}", rand::rng().random::<u128>());
+    resume.update(&cohort_batch_name(&id), BATCH_TTL, |batch: &mut CohortBatch| batch.mode = query.mode.clone())
+        .map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    Ok(Json(json!({"id": id})))
+}
+
+// 分批统计的进度: 已统计的文件哈希与当前分布, 人数不足时分布为空
+pub async fn cohort_batch_status(
+    State(resume): State<Arc<ResumeStore>>, State(profile): State<Arc<SchoolProfile>>, Path(id): Path<String>
+) -> Result<Json<serde_json::Value>, WebError> {
+    let batch = load_cohort_batch(&resume, &id)?;
+
+    Ok(Json(json!({
+        "mode": batch.mode,
+        "count": batch.gpas.len(),
+        "done": batch.gpas.keys().collect::<Vec<_>>(),
+        "distribution": batch.distribution(profile.grading.max_grade()),
+        "min_cohort_size": MIN_COHORT_SIZE,
+    })))
+}
+
+// 向分批统计上传一个文件, 已统计过的文件(内容相同)不重复计入
+pub async fn add_cohort_file(
+    State(config): State<Arc<AppConfig>>, State(resume): State<Arc<ResumeStore>>, State(profile): State<Arc<SchoolProfile>>, Path(id): Path<String>, mut multipart: Multipart
+) -> Result<Json<serde_json::Value>, WebError> {
+    let batch = load_cohort_batch(&resume, &id)?;
+    let default_mode = cohort_default_mode(&batch.mode)?;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| upload_error(e, &config))? {
+        if field.name() != Some("gpa_file") {
+            continue;
+        }
+
+        let file_name = field.file_name().unwrap_or_default().to_string();
+        let content_type = field.content_type().unwrap_or_default().to_string();
+        let data = field.bytes().await.map_err(|e| upload_error(e, &config))?;
+        let hash = content_hash(&data);
+
+        if batch.gpas.contains_key(&hash) {
+            return Ok(Json(json!({"counted": false, "duplicate": true, "count": batch.gpas.len()})));
+        }
+
+        return match cohort_gpa(&file_name, &content_type, &data, default_mode, &profile) {
+            Ok(gpa) => {
+                let batch = resume.update(&cohort_batch_name(&id), BATCH_TTL, |batch: &mut CohortBatch| {
+                    batch.gpas.insert(hash, gpa);
+                }).map_err(|e| WebError::InternalError(e.to_string()))?;
+                Ok(Json(json!({"counted": true, "count": batch.gpas.len()})))
+            }
+            Err(reason) => Ok(Json(json!({"counted": false, "skipped": format!("文件{}", reason), "count": batch.gpas.len()})))
+        };
+    }
+
+    Err(WebError::InvalidInput("未找到上传的成绩文件".to_string()))
+}
+
+// 导出分批统计的结果
+pub async fn export_cohort_batch(
+    State(resume): State<Arc<ResumeStore>>, State(profile): State<Arc<SchoolProfile>>, Path(id): Path<String>
+) -> Result<Response, WebError> {
+    let batch = load_cohort_batch(&resume, &id)?;
+
+    cohort_csv(&cohort_report(batch.distribution(profile.grading.max_grade()), batch.gpas.len())?)
+}
+
+// 统计完成后删除分批统计的进度
+pub async fn delete_cohort_batch(
+    State(resume): State<Arc<ResumeStore>>, Path(id): Path<String>
+) -> Result<Json<serde_json::Value>, WebError> {
+    resume.remove(&cohort_batch_name(&id));
+
+    Ok(Json(json!({"success": true})))
+}
+
+/// 班级统计的计算模式, true 表示 Default 模式
+fn cohort_default_mode(mode: &str) -> Result<bool, WebError> {
+    match mode {
+        "default" => Ok(true),
+        "all" | "" => Ok(false),
+        other => Err(WebError::InvalidInput(format!("未知的计算模式: {}", other)))
+    }
+}
+
+/// 计算单个学生文件的绩点, 无法计入时返回原因
+fn cohort_gpa(
+    file_name: &str, content_type: &str, data: &[u8], default_mode: bool, profile: &SchoolProfile
+) -> Result<Decimal, String> {
+    let (courses, source) = match parse_upload(file_name, content_type, data, &profile.grading) {
+        Ok((parsed, source)) if !parsed.courses.is_empty() => (parsed.courses, source),
+        Ok(_) => return Err("中没有可识别的课程".to_string()),
+        Err(e) => return Err(format!("无法解析: {}", e))
+    };
+
+    let results = process_scraped_course_results(&courses, source, profile);
+    match (default_mode, results.default) {
+        (true, Some(default_result)) => Ok(default_result.gpa),
+        (true, None) => Err("不含课程性质, 无法按 Default 模式计算".to_string()),
+        (false, _) => Ok(results.all.gpa)
+    }
+}
+
+/// 人数不足时返回错误, 以免反推出个人绩点
+fn cohort_report(distribution: Option<Distribution>, student_count: usize) -> Result<Distribution, WebError> {
+    distribution.ok_or_else(|| WebError::InvalidInput(
+        format!("有效的成绩文件只有 {} 份, 至少需要 {} 份才能统计, 以免反推出个人绩点", student_count, MIN_COHORT_SIZE)
+    ))
+}
+
+/// 以 CSV 下载班级分布
+fn cohort_csv(distribution: &Distribution) -> Result<Response, WebError> {
+    let body = distribution.to_csv().map_err(|e| WebError::InternalError(e.to_string()))?;
+
+    Ok(download_response(ExportFile {
+        body,
+        mime: "text/csv; charset=utf-8",
+        file_name: "class-gpa-distribution.csv".to_string(),
+    }))
+}
+
+fn cohort_batch_name(id: &str) -> String {
+    format!("cohort-{}", id)
+}
+
+/// 读取分批统计, 不存在或已过期时返回错误
+fn load_cohort_batch(resume: &ResumeStore, id: &str) -> Result<CohortBatch, WebError> {
+    resume.load(&cohort_batch_name(id), BATCH_TTL)
+        .ok_or_else(|| WebError::InvalidInput("班级统计不存在或已过期, 请重新开始".to_string()))
+}
+
+// 摘要 API 的查询参数
+#[derive(Debug, Deserialize)]
+pub struct SummaryQuery {
+    #[serde(default)]
+    token: Option<String>,
+}
+
+// 返回最近一次查询或导入结果的摘要, 供个人看板等外部页面嵌入
+// 设置了 API 令牌时需携带令牌(查询参数 token 或 Authorization: Bearer), 并允许任意网页跨域读取
+pub async fn api_summary(
+    State(config): State<Arc<AppConfig>>, State(summary): State<Arc<SummaryStore>>, headers: HeaderMap,
+    Query(query): Query<SummaryQuery>
+) -> Result<Response, WebError> {
+    let cors = match &config.api_token {
+        Some(expected) => {
+            let provided = query.token.or_else(|| {
+                headers
+                    .get(header::AUTHORIZATION)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("Bearer "))
+                    .map(|t| t.trim().to_string())
+            });
+            if provided.as_deref() != Some(expected.as_str()) {
+                return Err(WebError::Unauthorized);
+            }
+            true
+        }
+        // 局域网模式中同一网络的任何设备都能访问, 摘要只在设置了令牌时提供
+        None if config.lan_mode() => return Err(WebError::Unauthorized),
+        None => false
+    };
+
+    let body = match summary.get() {
+        Some(summary) => json!({"available": true, "summary": summary}),
+        None => json!({"available": false})
+    };
+
+    let mut response = Json(body).into_response();
+    if cors {
+        allow_cors(response.headers_mut());
+    }
+
+    Ok(response)
+}
+
+// 摘要 API 的跨域预检请求, 只有设置了 API 令牌时才允许跨域
+pub async fn api_summary_preflight(State(config): State<Arc<AppConfig>>) -> Response {
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    if config.api_token.is_some() {
+        allow_cors(response.headers_mut());
+    }
+
+    response
+}
+
+/// 允许任意来源以 GET 方式跨域读取, 令牌可放在 Authorization 头中
+fn allow_cors(headers: &mut HeaderMap) {
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static("GET, OPTIONS"));
+    headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_static("Authorization"));
+}
+
+// 启动自检未通过时的诊断页面
+pub async fn diagnostic_page(State(report): State<Arc<SelfCheckReport>>) -> (StatusCode, Html<String>) {
+    (StatusCode::SERVICE_UNAVAILABLE, Html(report.render_html()))
+}
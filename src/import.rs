@@ -0,0 +1,801 @@
+// 文件导入层 - 把用户上传的各种文件解析为课程列表
+use crate::{
+    business::{parsed_grade, print_info, score_trans_grade},
+    models::{Course, CourseNature, FileError, ParsedCourses, Score, SkippedRow, WebScrapingError},
+    profile::{GradingTable, SchoolProfile},
+    scraping::parse_grades
+};
+
+use calamine::{Ods, Reader, Xls, Xlsx};
+use encoding_rs::GB18030;
+use rust_decimal::Decimal;
+use rust_xlsxwriter::{DataValidation, DataValidationErrorStyle, Format, Formula, Workbook};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, io::{Cursor, Read, Seek}};
+
+// 英文成绩单中字母等级对应的百分制成绩, 取各等级区间的代表值后再按本校规则换算绩点
+const LETTER_GRADE_SCORES: &[(&str, u32)] = &[
+    ("A+", 97), ("A", 93), ("A-", 90),
+    ("B+", 88), ("B", 85), ("B-", 81),
+    ("C+", 78), ("C", 75), ("C-", 71),
+    ("D+", 68), ("D", 65), ("D-", 61),
+    ("F", 0), ("E", 0),
+];
+
+// 英文表头的别名, 均为小写
+const EN_COURSE_HEADERS: &[&str] = &["course name", "course title", "course", "subject", "module"];
+const EN_CREDIT_HEADERS: &[&str] = &["credit", "credits", "credit hours", "units", "ects"];
+const EN_SCORE_HEADERS: &[&str] = &["score", "grade", "mark", "marks", "result"];
+const EN_TERM_HEADERS: &[&str] = &["term", "semester", "session"];
+
+// 中文表头的别名, 如模板与教务系统导出的成绩单
+const CN_COURSE_HEADERS: &[&str] = &["课程名称", "课程名", "课程"];
+const CN_CREDIT_HEADERS: &[&str] = &["学分"];
+const CN_SCORE_HEADERS: &[&str] = &["总成绩", "成绩", "总评", "分数", "得分"];
+const CN_TERM_HEADERS: &[&str] = &["开课学期", "学年学期", "学期"];
+// 教务系统导出的成绩单中另有以下几列, 有课程性质时可以像成绩页面一样计算 Default 模式
+const CN_CODE_HEADERS: &[&str] = &["课程编号", "课程代码", "课程号"];
+const CN_NATURE_HEADERS: &[&str] = &["课程性质"];
+const CN_EXAM_TYPE_HEADERS: &[&str] = &["考试性质"];
+
+const HEADER_SCAN_ROWS: usize = 10; // 只在前几行中查找表头
+const TEMPLATE_EXAMPLE_HINT: &str = "例子";   // 模板第一行的说明文字, 说明表头的下一行是示例
+
+// 上传模板的各列与示例, 表头使用解析时识别的第一个别名, 模板因此始终能被识别
+// 课程名称、学分、成绩为必填; 开课学期用于计算各学期绩点, 课程性质用于 Default 与仅必修课模式, 可以不填
+const TEMPLATE_COLUMNS: [(&[&str], f64); 5] = [
+    (CN_COURSE_HEADERS, 24.0), (CN_CREDIT_HEADERS, 8.0), (CN_SCORE_HEADERS, 10.0), (CN_TERM_HEADERS, 14.0), (CN_NATURE_HEADERS, 14.0),
+];
+const TEMPLATE_EXAMPLE: (&str, f64, f64, &str, &str) = ("高等数学I", 4.0, 90.0, "2023-2024-1", "必修课");
+// 模板中课程性质的下拉选项, 另外加上排除规则中的课程性质
+const TEMPLATE_NATURES: [CourseNature; 4] = [
+    CourseNature::Compulsory, CourseNature::Elective, CourseNature::PublicElective, CourseNature::GeneralElective,
+];
+const TEMPLATE_HEADER_ROW: u32 = 1;     // 第一行为说明, 第二行为表头, 第三行为示例
+const TEMPLATE_MAX_CREDIT: u32 = 16;    // 学分的上限, 只用于模板中的输入校验
+const EXCEL_MAX_ROW: u32 = 1_048_575;
+const EXCEL_MAX_FORMULA_LEN: usize = 255;  // 数据验证公式的长度上限
+
+// 旧版 Excel(.xls)使用的 OLE 复合文档的文件头
+const OLE_MAGIC: &[u8] = &[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+// xlsx 与 ODS 使用的 zip 压缩包的文件头
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+// ODS 与 xlsx 都是 zip 压缩包, ODS 的第一个文件固定为不压缩的 mimetype, 内容为以下类型
+const ODS_MIME: &str = "application/vnd.oasis.opendocument.spreadsheet";
+
+// 上传文件的格式
+pub enum ImportFormat {
+    Xlsx,       // 模板文件或英文成绩单
+    Xls,        // 旧版 Excel 文件, 如教务系统导出的成绩单
+    Ods,        // WPS、LibreOffice 另存的 OpenDocument 表格
+    SavedPage,  // 浏览器另存为的教务系统成绩页面
+    Csv,        // 逗号/分号/制表符分隔的文本表格
+    Json,       // 课程数组, 字段与 Course 相同, 如本程序导出的数据或其他脚本生成的文件
+}
+
+impl ImportFormat {
+    /// 根据文件名、浏览器上报的 MIME 类型与文件内容判断格式, 扩展名优先
+    pub fn detect(file_name: &str, content_type: &str, data: &[u8]) -> Self {
+        let file_name = file_name.to_lowercase();
+        if file_name.ends_with(".html") || file_name.ends_with(".htm") {
+            return ImportFormat::SavedPage;
+        }
+        if file_name.ends_with(".csv") || file_name.ends_with(".txt") {
+            return ImportFormat::Csv;
+        }
+        if file_name.ends_with(".xlsx") {
+            return ImportFormat::Xlsx;
+        }
+        if file_name.ends_with(".json") {
+            return ImportFormat::Json;
+        }
+        if file_name.ends_with(".ods") || content_type.starts_with(ODS_MIME) || is_ods_archive(data) {
+            return ImportFormat::Ods;
+        }
+        // .xls 只有真正的旧版 Excel 文件才按 Excel 读取, 部分系统导出的 .xls 实际是网页或制表符分隔的文本
+        if data.starts_with(OLE_MAGIC) {
+            return ImportFormat::Xls;
+        }
+
+        // 没有扩展名时先看 MIME 类型, 再看内容: 以 < 开头的文本视为网页, xlsx 是以 PK 开头的压缩包
+        let content_type = content_type.to_lowercase();
+        let head = String::from_utf8_lossy(&data[..data.len().min(256)]).trim_start().to_lowercase();
+        let disguised_page = file_name.ends_with(".xls") && head.starts_with('<');
+        if content_type.starts_with("text/html") || head.starts_with("<!doctype html") || head.starts_with("<html") || disguised_page {
+            ImportFormat::SavedPage
+        } else if content_type.starts_with("application/json") || head.starts_with('[') || head.starts_with('{') {
+            ImportFormat::Json
+        } else if content_type.starts_with("text/csv") || content_type.starts_with("text/plain")
+            || (!data.starts_with(b"PK") && !data.contains(&0)) {
+            ImportFormat::Csv   // 不含空字节的文件视为文本表格
+        } else {
+            ImportFormat::Xlsx
+        }
+    }
+}
+
+// JSON 文件中的一门课程, 字段与 Course 相同
+// 绩点与加权绩点按当前换算表重新计算, 文件中即使有也不使用, 因此可以省略
+#[derive(Deserialize)]
+struct JsonCourse {
+    #[serde(default)]
+    course_code: String,
+    #[serde(default)]
+    semester: String,
+    name: String,
+    #[serde(default)]
+    nature: CourseNature,
+    score: Score,
+    credit: Decimal,
+    #[serde(default)]
+    exam_type: String,
+    #[serde(default)]
+    teacher: String,
+    #[serde(default)]
+    hours: String,
+    #[serde(default)]
+    assessment: String,
+    #[serde(default)]
+    minor: bool,
+}
+
+// 表格中各列的位置, 由中文或英文表头识别得到, 或由用户在上传前的预览中指定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    pub name: usize,    // 课程名称所在列, 从 0 开始
+    pub credit: usize,  // 学分所在列
+    pub score: usize,   // 成绩所在列
+    #[serde(default)]
+    pub semester: Option<usize>,    // 学期所在列, 可以没有
+    #[serde(default)]
+    pub course_code: Option<usize>, // 课程编号所在列, 以下几列只在教务系统导出的成绩单中有
+    #[serde(default)]
+    pub nature: Option<usize>,  // 课程性质所在列
+    #[serde(default)]
+    pub exam_type: Option<usize>,   // 考试性质所在列
+    #[serde(default)]
+    pub start_row: usize,   // 第一行数据, 从 0 开始
+}
+
+impl ColumnMapping {
+    /// 课程名称、学分与成绩需要对应不同的列
+    pub fn validate(&self) -> Result<(), FileError> {
+        if self.name == self.credit || self.name == self.score || self.credit == self.score {
+            return Err(FileError::OpenError("课程名称、学分与成绩需要对应不同的列".to_string()));
+        }
+        Ok(())
+    }
+}
+
+// 上传前预览的表格, 供用户指定各列对应的内容
+#[derive(Debug, Clone, Serialize)]
+pub struct TablePreview {
+    pub sheet: Option<String>,  // 读取的工作表, CSV 文件没有
+    pub header_row: usize,      // 表头所在行, 从 0 开始
+    pub headers: Vec<String>,   // 表头各列的文字, 与数据列数对齐
+    pub samples: Vec<Vec<String>>,  // 表头之后的前几行数据
+    pub mapping: Option<ColumnMapping>, // 自动识别的对应关系, 无法识别时为空
+}
+
+const PREVIEW_SAMPLE_ROWS: usize = 5;   // 预览显示的数据行数
+
+// 在表格中找到的表头
+struct DetectedHeader {
+    row: usize,     // 表头所在行, 表头占两行时为第一行
+    headers: Vec<String>,   // 各列的表头文字, 两行表头合并后的结果
+    mapping: ColumnMapping,
+}
+
+/// 解析 Excel 文件
+/// 带英文表头的按英文成绩单解析, 否则按模板解析(前3行为说明与表头)
+pub fn parse_xlsx(data: &[u8], grading: &GradingTable) -> Result<ParsedCourses, FileError> {
+    let mut workbook = open_workbook(data, ZIP_MAGIC, "Excel", Xlsx::new)?;
+    Ok(parse_workbook(&mut workbook, grading))
+}
+
+/// 解析旧版 Excel 文件(.xls), 规则与 xlsx 相同
+pub fn parse_xls(data: &[u8], grading: &GradingTable) -> Result<ParsedCourses, FileError> {
+    let mut workbook = open_workbook(data, OLE_MAGIC, "Excel", Xls::new)?;
+    Ok(parse_workbook(&mut workbook, grading))
+}
+
+/// 解析 OpenDocument 表格(.ods), 规则与 xlsx 相同
+pub fn parse_ods(data: &[u8], grading: &GradingTable) -> Result<ParsedCourses, FileError> {
+    let mut workbook = open_workbook(data, ZIP_MAGIC, "ODS", Ods::new)?;
+    Ok(parse_workbook(&mut workbook, grading))
+}
+
+/// 先按文件头检查文件类型再交给 calamine 打开, 打开失败时只提示文件无效, calamine 的原始报错记录在日志中
+fn open_workbook<'a, W, E: std::fmt::Display>(
+    data: &'a [u8],
+    magic: &[u8],
+    kind: &'static str,
+    open: impl FnOnce(Cursor<&'a [u8]>) -> Result<W, E>
+) -> Result<W, FileError> {
+    if !data.starts_with(magic) {
+        return Err(FileError::InvalidSpreadsheet(kind));
+    }
+    open(Cursor::new(data)).map_err(|e| {
+        print_info(&format!("无法打开{}文件: {}", kind, e));
+        FileError::InvalidSpreadsheet(kind)
+    })
+}
+
+/// zip 压缩包的第一个文件是否为 ODS 的 mimetype, 文件名从第 30 个字节开始
+fn is_ods_archive(data: &[u8]) -> bool {
+    data.starts_with(b"PK") && data.get(30..).is_some_and(|rest| {
+        rest.starts_with(b"mimetype") && rest[8..].starts_with(ODS_MIME.as_bytes())
+    })
+}
+
+/// 读取工作簿中的成绩表格
+fn parse_workbook<RS: Read + Seek, R: Reader<RS>>(workbook: &mut R, grading: &GradingTable) -> ParsedCourses {
+    let Some((name, rows)) = workbook_rows(workbook) else {
+        return ParsedCourses::default();
+    };
+
+    print_info(&format!("读取工作表“{}”", name));
+    ParsedCourses { sheet: Some(name), ..parse_rows(&rows, grading) }
+}
+
+/// 找到表头时按表头中的各列解析表头之后的行, 没有表头时从第一行开始按模板的列顺序(课程、学分、成绩)解析
+fn parse_rows(rows: &[Vec<String>], grading: &GradingTable) -> ParsedCourses {
+    match detect_header(rows) {
+        Some(header) => parse_mapped_rows(rows, &header.mapping, grading),
+        None => parse_template_rows(rows, 0, grading)
+    }
+}
+
+/// 选出成绩所在的工作表, 返回工作表名称与各行文字
+/// 工作表可能被改名(如 WPS 中的"工作表1"), 因此依次查找第一个带有可识别表头的工作表, 都没有时使用第一个工作表
+fn workbook_rows<RS: Read + Seek, R: Reader<RS>>(workbook: &mut R) -> Option<(String, Vec<Vec<String>>)> {
+    let mut sheets: Vec<(String, Vec<Vec<String>>)> = Vec::new();
+    for name in workbook.sheet_names() {
+        let Ok(range) = workbook.worksheet_range(&name) else { continue };
+        let rows: Vec<Vec<String>> = range
+            .rows()
+            .map(|row| row.iter().map(|c| c.to_string().trim().to_string()).collect())
+            .collect();
+        sheets.push((name, rows));
+    }
+
+    let index = sheets
+        .iter()
+        .position(|(_, rows)| detect_header(rows).is_some())
+        .unwrap_or_default();
+
+    sheets.into_iter().nth(index)
+}
+
+/// 解析 CSV 文件, 自动识别 UTF-8 与 GBK 编码, 以及逗号、分号与制表符分隔
+/// 按中文或英文表头中的各列解析, 没有表头时按模板的列顺序(课程、学分、成绩)解析, 无法识别的行跳过并记录
+pub fn parse_csv(data: &[u8], grading: &GradingTable) -> Result<ParsedCourses, FileError> {
+    Ok(parse_rows(&csv_rows(data)?, grading))
+}
+
+/// 解析粘贴的文字, 每行一门课程, 如从教务系统成绩页面的表格中直接复制的几行
+/// 含有制表符时按制表符分列(网页表格复制后的格式), 可以连同表头一起复制, 按表头中的各列解析;
+/// 否则按空白分列, 最后两项为学分与成绩, 其余为课程名称(课程名称中可以有空格), 如"高等数学 4 92"
+pub fn parse_pasted_text(text: &str, grading: &GradingTable) -> ParsedCourses {
+    let lines: Vec<&str> = text.trim_start_matches('\u{feff}').lines().collect();
+    let tabbed = lines.iter().any(|line| line.contains('\t'));
+
+    let rows: Vec<Vec<String>> = lines
+        .iter()
+        .map(|line| if tabbed {
+            line.split('\t').map(|cell| cell.trim().to_string()).collect()
+        } else {
+            split_pasted_line(line)
+        })
+        .collect();
+
+    parse_rows(&rows, grading)
+}
+
+/// 按空白分开一行文字, 末尾两项之前的部分合并为课程名称
+fn split_pasted_line(line: &str) -> Vec<String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.len() <= 3 {
+        return words.into_iter().map(str::to_string).collect();
+    }
+
+    let (name, rest) = words.split_at(words.len() - 2);
+    std::iter::once(name.join(" ")).chain(rest.iter().map(|w| w.to_string())).collect()
+}
+
+/// 读取 CSV 文件的各行文字
+fn csv_rows(data: &[u8]) -> Result<Vec<Vec<String>>, FileError> {
+    let text = decode_text(data);
+    let text = text.trim_start_matches('\u{feff}');    // Excel 另存的 UTF-8 CSV 带有 BOM
+
+    // 按前几行中出现最多的分隔符识别, 第一行可能是标题等说明文字; 出现次数相同时优先使用逗号
+    let head: Vec<&str> = text.lines().take(HEADER_SCAN_ROWS).collect();
+    let delimiter = [b'\t', b';', b',']
+        .into_iter()
+        .max_by_key(|d| head.iter().map(|line| line.matches(*d as char).count()).sum::<usize>())
+        .unwrap_or(b',');
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)     // 允许各行列数不同, 如末尾的汇总行
+        .delimiter(delimiter)
+        .from_reader(text.as_bytes());
+
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| FileError::OpenError(e.to_string()))?;
+        rows.push(record.iter().map(|field| field.trim().to_string()).collect());
+    }
+
+    Ok(rows)
+}
+
+/// 读取表格文件(Excel、ODS 与 CSV)的各行文字, 返回读取的工作表与各行
+fn read_table(format: &ImportFormat, data: &[u8]) -> Result<(Option<String>, Vec<Vec<String>>), FileError> {
+    let sheet = match format {
+        ImportFormat::Xlsx => workbook_rows(&mut open_workbook(data, ZIP_MAGIC, "Excel", Xlsx::new)?),
+        ImportFormat::Xls => workbook_rows(&mut open_workbook(data, OLE_MAGIC, "Excel", Xls::new)?),
+        ImportFormat::Ods => workbook_rows(&mut open_workbook(data, ZIP_MAGIC, "ODS", Ods::new)?),
+        ImportFormat::Csv => return Ok((None, csv_rows(data)?)),
+        ImportFormat::SavedPage | ImportFormat::Json => {
+            return Err(FileError::OpenError("网页与 JSON 文件的格式是固定的, 不需要指定各列".to_string()))
+        }
+    };
+
+    let (name, rows) = sheet.ok_or_else(|| FileError::OpenError("文件中没有可读取的工作表".to_string()))?;
+    Ok((Some(name), rows))
+}
+
+/// 预览表格文件, 找出表头并尝试识别各列, 识别不了时由用户指定
+pub fn preview_table(format: &ImportFormat, data: &[u8]) -> Result<TablePreview, FileError> {
+    let (sheet, rows) = read_table(format, data)?;
+
+    let (header_row, mut headers, mapping) = match detect_header(&rows) {
+        Some(header) => (header.row, header.headers, Some(header.mapping)),
+        None => (0, rows.first().cloned().unwrap_or_default(), None)
+    };
+
+    let column_count = rows.iter().map(Vec::len).max().unwrap_or_default();
+    headers.resize(column_count, String::new());
+    let samples_start = mapping.as_ref().map_or(header_row + 1, |mapping| mapping.start_row);
+    let samples = rows.iter().skip(samples_start).take(PREVIEW_SAMPLE_ROWS).cloned().collect();
+
+    Ok(TablePreview { sheet, header_row, headers, samples, mapping })
+}
+
+/// 按用户指定的各列解析表格文件
+pub fn parse_with_mapping(
+    format: &ImportFormat, data: &[u8], mapping: &ColumnMapping, grading: &GradingTable
+) -> Result<ParsedCourses, FileError> {
+    mapping.validate()?;
+    let (sheet, rows) = read_table(format, data)?;
+
+    Ok(ParsedCourses { sheet, ..parse_mapped_rows(&rows, mapping, grading) })
+}
+
+/// 解析 JSON 文件, 内容为课程数组, 也可以是带有 courses 数组的对象
+/// 某门课程字段缺失或成绩无法识别时跳过并记录, 行号为该课程在数组中的序号
+pub fn parse_json(data: &[u8], grading: &GradingTable) -> Result<ParsedCourses, FileError> {
+    let text = decode_text(data);
+    let value: serde_json::Value = serde_json::from_str(text.trim_start_matches('\u{feff}'))
+        .map_err(|e| FileError::OpenError(format!("JSON 格式错误: {}", e)))?;
+
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Object(mut object) => match object.remove("courses") {
+            Some(serde_json::Value::Array(items)) => items,
+            _ => return Err(FileError::OpenError("JSON 文件中没有课程数组".to_string()))
+        },
+        _ => return Err(FileError::OpenError("JSON 文件中没有课程数组".to_string()))
+    };
+
+    let mut parsed = ParsedCourses::default();
+    for (index, item) in items.into_iter().enumerate() {
+        let name = item.get("name").and_then(|n| n.as_str()).unwrap_or_default().to_string();
+        let course: JsonCourse = match serde_json::from_value(item) {
+            Ok(course) => course,
+            Err(e) => {
+                parsed.skipped.push(skipped_row(index, &name, format!("字段无法识别: {}", e)));
+                continue;
+            }
+        };
+        let Some(grade) = parsed_grade(&course.score, grading) else {
+            parsed.skipped.push(unrecognized_cell(index, &course.name, "成绩", &course.score.to_string()));
+            continue;
+        };
+
+        parsed.courses.push(Course {
+            course_code: course.course_code,
+            semester: course.semester,
+            name: course.name,
+            nature: course.nature,
+            score: course.score,
+            credit: course.credit,
+            grade,
+            credit_gpa: grading.rounding.round(grade * course.credit),
+            exam_type: course.exam_type,
+            teacher: course.teacher,
+            hours: course.hours,
+            assessment: course.assessment,
+            minor: course.minor,
+            source: String::new(),
+        });
+    }
+
+    Ok(parsed)
+}
+
+/// 把一个文件的课程并入已有的课程, 返回新加入的门数
+/// 按学期分开保存的文件可能有重叠, 与之前文件中完全相同的成绩记录(同一课程、学期、成绩、学分与考试性质)只保留一条;
+/// 同一文件中的重复记录仍然保留, 交给重复课程的处理方式决定
+pub fn merge_courses(merged: &mut Vec<Course>, courses: Vec<Course>) -> usize {
+    let existing = merged.len();
+    for course in courses {
+        let duplicated = merged[..existing].iter().any(|c| {
+            c.name == course.name && c.minor == course.minor && c.semester == course.semester
+                && c.score == course.score && c.credit == course.credit && c.exam_type == course.exam_type
+        });
+        if !duplicated {
+            merged.push(course);
+        }
+    }
+
+    merged.len() - existing
+}
+
+/// 把文本文件解码为字符串, 不是有效的 UTF-8 时按 GBK 解码
+/// 中文版 Excel 与 WPS 另存的 CSV、浏览器另存的网页都可能是 GBK 编码, GB18030 兼容 GBK
+fn decode_text(data: &[u8]) -> Cow<'_, str> {
+    match std::str::from_utf8(data) {
+        Ok(text) => Cow::Borrowed(text),
+        Err(_) => GB18030.decode_without_bom_handling(data).0
+    }
+}
+
+/// 生成上传模板, 表头与解析规则一致, 成绩的输入校验按当前换算表中的等级制与特殊成绩生成
+/// 课程性质的下拉选项包括排除规则中的课程性质, 以便 Default 模式按规则排除
+pub fn upload_template(profile: &SchoolProfile) -> anyhow::Result<Vec<u8>> {
+    let grading = &profile.grading;
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("成绩")?;
+
+    let example_row = TEMPLATE_HEADER_ROW + 1;
+    let last_col = TEMPLATE_COLUMNS.len() as u16 - 1;
+    let max_score = grading.max_score.normalize();
+    // 等级制成绩按绩点从高到低排列
+    let mut levels: Vec<(&String, &Decimal)> = grading.levels.iter().collect();
+    levels.sort_by(|a, b| b.1.cmp(a.1));
+    let levels: Vec<&str> = levels.into_iter().map(|(level, _)| level.as_str()).collect();
+    let specials: Vec<&str> = grading.special_scores.keys().map(String::as_str).collect();
+
+    // 说明中需要包含"例子", 解析时据此跳过示例行
+    let note = format!(
+        "第{}行为{}，请从第{}行开始填入数据。开课学期与课程性质可以不填，填写课程性质后可以计算 Default 模式，未填写的课程在 Default 模式中计入。\n成绩填入 0-{} 的分数或等级制成绩（{}），缓考、免修等特殊成绩照原样填写（{}）",
+        example_row + 1, TEMPLATE_EXAMPLE_HINT, example_row + 2, max_score, levels.join("、"), specials.join("、")
+    );
+    sheet.merge_range(0, 0, 0, last_col, &note, &Format::new().set_text_wrap())?;
+    sheet.set_row_height(0, 48)?;
+
+    let header = Format::new().set_bold();
+    for (col, (aliases, width)) in TEMPLATE_COLUMNS.iter().enumerate() {
+        sheet.write_string_with_format(TEMPLATE_HEADER_ROW, col as u16, aliases[0], &header)?;
+        sheet.set_column_width(col as u16, *width)?;
+    }
+
+    let (name, credit, score, semester, nature) = TEMPLATE_EXAMPLE;
+    sheet.write_string(example_row, 0, name)?;
+    sheet.write_number(example_row, 1, credit)?;
+    sheet.write_number(example_row, 2, score)?;
+    sheet.write_string(example_row, 3, semester)?;
+    sheet.write_string(example_row, 4, nature)?;
+
+    // 学分与成绩的输入校验, 公式中的单元格为校验区域的第一格
+    let credit_cell = format!("B{}", example_row + 1);
+    let credit_rule = DataValidation::new()
+        .allow_custom(Formula::new(format!(
+            "AND(ISNUMBER({c}),{c}>=0,{c}<={max},INT({c}*2)={c}*2)", c = credit_cell, max = TEMPLATE_MAX_CREDIT
+        )))
+        .set_input_message(format!("不超过{}的正整数或0.5的倍数", TEMPLATE_MAX_CREDIT))?
+        .set_error_title("学分格式错误")?
+        .set_error_message(format!("请填入不超过{}的正整数或0.5的倍数", TEMPLATE_MAX_CREDIT))?;
+    sheet.add_data_validation(example_row, 1, EXCEL_MAX_ROW, 1, &credit_rule)?;
+
+    let score_cell = format!("C{}", example_row + 1);
+    let conditions: Vec<String> = std::iter::once(format!("AND(ISNUMBER({c}),{c}>=0,{c}<={max})", c = score_cell, max = max_score))
+        .chain(levels.iter().chain(&specials).map(|text| format!("{}=\"{}\"", score_cell, text)))
+        .collect();
+    let formula = format!("OR({})", conditions.join(","));
+    let score_rule = DataValidation::new().set_input_message(format!("0-{} 的分数，或第1行列出的等级制成绩", max_score))?;
+    // 等级制成绩较多时公式超过 Excel 的长度上限, 此时只显示提示而不校验
+    let score_rule = match formula.chars().count() <= EXCEL_MAX_FORMULA_LEN {
+        true => score_rule
+            .allow_custom(Formula::new(formula))
+            .set_error_title("成绩格式不符")?
+            .set_error_message(format!("请填入0-{}的分数，或第1行列出的等级制成绩", max_score))?,
+        false => score_rule
+    };
+    sheet.add_data_validation(example_row, 2, EXCEL_MAX_ROW, 2, &score_rule)?;
+
+    // 课程性质提供下拉选项, 学科基础课等其他性质也可以填写, 只提示而不阻止
+    let mut natures: Vec<String> = TEMPLATE_NATURES.iter().map(CourseNature::to_string).collect();
+    for nature in &profile.exclusion.natures {
+        let nature = CourseNature::from(nature.as_str()).to_string();
+        if !natures.contains(&nature) {
+            natures.push(nature);
+        }
+    }
+    let nature_rule = DataValidation::new()
+        .allow_list_strings(&natures)?
+        .set_error_style(DataValidationErrorStyle::Warning)
+        .set_input_message("从下拉列表中选择，或按成绩单填写")?
+        .set_error_title("课程性质不在列表中")?
+        .set_error_message("不在列表中的课程性质在 Default 模式中计入，确定要填写吗？")?;
+    sheet.add_data_validation(example_row, 4, EXCEL_MAX_ROW, 4, &nature_rule)?;
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+/// 解析另存为的教务系统成绩页面, 与在线爬取使用同一套解析逻辑
+/// 浏览器另存的页面保留教务系统原来的编码, 可能是 GBK
+pub fn parse_saved_page(data: &[u8], grading: &GradingTable) -> Result<ParsedCourses, WebScrapingError> {
+    let html = decode_text(data);
+    parse_grades(&html, grading)
+}
+
+/// 按模板的列顺序解析: 课程名称、学分、成绩, 以及可以不填的开课学期与课程性质, 从第 start 行(从 0 开始)开始
+/// 整行为空的行跳过, 缺少课程名称或学分、成绩无法识别的行记录后跳过
+fn parse_template_rows(rows: &[Vec<String>], start: usize, grading: &GradingTable) -> ParsedCourses {
+    let mut parsed = ParsedCourses::default();
+
+    for (index, row) in rows.iter().enumerate().skip(start) {
+        let name = row.first().cloned().unwrap_or_default();
+        let credit_str = row.get(1).cloned().unwrap_or_default();
+        let score_str = row.get(2).map(String::as_str).unwrap_or_default();
+        let score = Score::from(score_str);
+
+        if name.is_empty() {
+            parsed.skipped.extend(missing_name(index, &credit_str, score_str));
+            continue;
+        }
+        let Ok(credit) = credit_str.parse::<Decimal>() else {
+            parsed.skipped.push(unrecognized_cell(index, &name, "学分", &credit_str));
+            continue;
+        };
+        let Some(grade) = parsed_grade(&score, grading) else {
+            parsed.skipped.push(unrecognized_cell(index, &name, "成绩", &score.to_string()));
+            continue;
+        };
+
+        let semester = row.get(3).cloned().unwrap_or_default();
+        let nature = row.get(4).map(|text| CourseNature::from(text.as_str())).unwrap_or_default();
+        parsed.courses.push(Course { nature, ..new_course(name, semester, score, credit, grade, grading) });
+    }
+
+    parsed
+}
+
+/// 在前几行中查找中文或英文表头, 至少需要课程、学分、成绩三列
+/// 数据从表头的下一行开始; 模板的表头之后还有一行示例, 第一行的说明中提到示例时跳过这一行
+/// 教务系统导出的成绩单前面有合并单元格的标题行, 表头也可能占两行(如"成绩"下分为平时、期末、总评), 此时按合并后的表头识别
+fn detect_header(rows: &[Vec<String>]) -> Option<DetectedHeader> {
+    let mut header = rows.iter().take(HEADER_SCAN_ROWS).enumerate().find_map(|(index, row)| {
+        let mapping = header_mapping(row, index + 1)?;
+
+        // 下一行在课程名称列为空而其他列有文字时是第二行表头, 合并单元格只有左上角的格子有内容
+        let Some(next) = rows.get(index + 1).filter(|next| {
+            next.get(mapping.name).is_none_or(String::is_empty) && next.iter().any(|cell| !cell.is_empty())
+        }) else {
+            return Some(DetectedHeader { row: index, headers: row.clone(), mapping });
+        };
+
+        let merged = merge_header_rows(row, next);
+        let mapping = header_mapping(&merged, index + 2).unwrap_or(ColumnMapping { start_row: index + 2, ..mapping });
+        Some(DetectedHeader { row: index, headers: merged, mapping })
+    })?;
+
+    let has_example = header.row > 0 && rows[0].iter().any(|cell| cell.contains(TEMPLATE_EXAMPLE_HINT));
+    if has_example {
+        header.mapping.start_row += 1;
+    }
+
+    Some(header)
+}
+
+/// 按中文或英文表头识别各列, 数据从 start_row 行开始
+fn header_mapping(row: &[String], start_row: usize) -> Option<ColumnMapping> {
+    let aliases = [
+        (CN_COURSE_HEADERS, CN_CREDIT_HEADERS, CN_SCORE_HEADERS, CN_TERM_HEADERS, CN_CODE_HEADERS, CN_NATURE_HEADERS, CN_EXAM_TYPE_HEADERS),
+        (EN_COURSE_HEADERS, EN_CREDIT_HEADERS, EN_SCORE_HEADERS, EN_TERM_HEADERS, &[], &[], &[]),
+    ];
+
+    let headers: Vec<String> = row.iter().map(|h| h.to_lowercase()).collect();
+    aliases.iter().find_map(|(course, credit, score, term, code, nature, exam_type)| {
+        let mapping = ColumnMapping {
+            name: find_header(&headers, course)?,
+            credit: find_header(&headers, credit)?,
+            score: find_header(&headers, score)?,
+            semester: find_header(&headers, term),
+            course_code: find_header(&headers, code),
+            nature: find_header(&headers, nature),
+            exam_type: find_header(&headers, exam_type),
+            start_row,
+        };
+        // 课程名称等别名较短, 同一列同时匹配多项时视为不是表头
+        mapping.validate().is_ok().then_some(mapping)
+    })
+}
+
+/// 合并两行表头: 第二行有文字的列使用第二行的文字, 其余的列是上下合并的单元格, 使用第一行的文字
+fn merge_header_rows(top: &[String], bottom: &[String]) -> Vec<String> {
+    (0..top.len().max(bottom.len()))
+        .map(|i| match bottom.get(i).filter(|cell| !cell.is_empty()) {
+            Some(cell) => cell.clone(),
+            None => top.get(i).cloned().unwrap_or_default()
+        })
+        .collect()
+}
+
+/// 查找表头所在列, 先完全匹配再包含匹配
+fn find_header(headers: &[String], aliases: &[&str]) -> Option<usize> {
+    aliases
+        .iter()
+        .find_map(|alias| headers.iter().position(|h| h == alias))
+        .or_else(|| aliases.iter().find_map(|alias| headers.iter().position(|h| h.contains(alias))))
+}
+
+/// 按各列的位置解析带表头或用户指定了各列的表格, 成绩可以是字母等级或百分制, 从 start_row 行开始
+fn parse_mapped_rows(rows: &[Vec<String>], mapping: &ColumnMapping, grading: &GradingTable) -> ParsedCourses {
+    let mut parsed = ParsedCourses::default();
+
+    for (index, row) in rows.iter().enumerate().skip(mapping.start_row) {
+        let name = row.get(mapping.name).cloned().unwrap_or_default();
+        let credit_str = row.get(mapping.credit).cloned().unwrap_or_default();
+        let score = row.get(mapping.score).cloned().unwrap_or_default();
+        let column = |index: Option<usize>| index.and_then(|i| row.get(i).cloned()).unwrap_or_default();
+        let term = column(mapping.semester);
+
+        if name.is_empty() {
+            parsed.skipped.extend(missing_name(index, &credit_str, &score));
+            continue;
+        }
+        let Ok(credit) = credit_str.parse::<Decimal>() else {
+            parsed.skipped.push(unrecognized_cell(index, &name, "学分", &credit_str));
+            continue;
+        };
+        let Some(grade) = english_score_to_grade(&score, grading) else {
+            parsed.skipped.push(unrecognized_cell(index, &name, "成绩", &score.to_string()));
+            continue;
+        };
+
+        parsed.courses.push(Course {
+            course_code: column(mapping.course_code),
+            nature: CourseNature::from(column(mapping.nature).as_str()),
+            exam_type: column(mapping.exam_type),
+            ..new_course(name, term, Score::from(score.as_str()), credit, grade, grading)
+        });
+    }
+
+    parsed
+}
+
+/// 英文成绩单的成绩换算为本校绩点, 支持 A-/B+ 等字母等级与 85、85% 等百分制成绩
+/// 换算表中设置了处理方式的特殊成绩与模板一样保留, Pass/Fail 等不计绩点的成绩返回 None
+fn english_score_to_grade(score: &str, grading: &GradingTable) -> Option<Decimal> {
+    let normalized = score.trim().to_uppercase();
+
+    if let Some(&(_, percentage)) = LETTER_GRADE_SCORES.iter().find(|(letter, _)| *letter == normalized) {
+        return score_trans_grade(&Score::Numeric(Decimal::from(percentage)), grading);
+    }
+
+    score_trans_grade(&Score::from(normalized.trim_end_matches('%')), grading).or_else(|| parsed_grade(&Score::from(score), grading))
+}
+
+/// 表格中跳过的一行, index 从 0 开始, 显示时换算为表格的行号; 所在文件由调用方补充
+fn skipped_row(index: usize, name: &str, reason: String) -> SkippedRow {
+    SkippedRow { location: String::new(), row: index + 1, name: name.to_string(), column: String::new(), value: String::new(), reason }
+}
+
+/// 因某一列无法识别而跳过的一行
+fn unrecognized_cell(index: usize, name: &str, column: &str, value: &str) -> SkippedRow {
+    SkippedRow {
+        column: column.to_string(),
+        value: value.to_string(),
+        ..skipped_row(index, name, format!("{}“{}”无法识别", column, value))
+    }
+}
+
+/// 没有课程名称的行: 整行为空时是空行, 不记录; 有学分或成绩时是漏填了课程名称, 记录后跳过
+fn missing_name(index: usize, credit: &str, score: &str) -> Option<SkippedRow> {
+    (!credit.is_empty() || !score.is_empty()).then(|| SkippedRow {
+        column: "课程名称".to_string(),
+        ..skipped_row(index, "", "缺少课程名称".to_string())
+    })
+}
+
+/// 导入文件中的课程没有课程编号、课程性质、考试性质与任课教师等信息, 教务系统导出的成绩单中有的列由调用方补充
+fn new_course(name: String, semester: String, score: Score, credit: Decimal, grade: Decimal, grading: &GradingTable) -> Course {
+    Course {
+        course_code: String::new(),
+        semester,
+        name,
+        nature: CourseNature::default(),
+        score,
+        credit,
+        grade,
+        credit_gpa: grading.rounding.round(grade * credit),
+        exam_type: String::new(),
+        teacher: String::new(),
+        hours: String::new(),
+        assessment: String::new(),
+        minor: false,
+        source: String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(cells: &[&[&str]]) -> Vec<Vec<String>> {
+        cells.iter().map(|row| row.iter().map(|cell| cell.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn header_columns_in_any_order() {
+        let rows = rows(&[
+            &["成绩", "课程名称", "学期", "学分"],
+            &["90", "高等数学", "2023-2024-1", "4"],
+        ]);
+        let header = detect_header(&rows).unwrap();
+        let mapping = &header.mapping;
+        assert_eq!((mapping.name, mapping.credit, mapping.score, mapping.semester, mapping.start_row), (1, 3, 0, Some(2), 1));
+
+        let parsed = parse_rows(&rows, &GradingTable::default());
+        assert_eq!(parsed.courses.len(), 1);
+        assert_eq!(parsed.courses[0].name, "高等数学");
+        assert_eq!(parsed.courses[0].semester, "2023-2024-1");
+        assert_eq!(parsed.courses[0].grade, Decimal::new(433, 2));
+    }
+
+    #[test]
+    fn english_header_columns_in_any_order() {
+        let rows = rows(&[
+            &["Credits", "Grade", "Course Title"],
+            &["3", "A-", "Linear Algebra"],
+        ]);
+        let mapping = detect_header(&rows).unwrap().mapping;
+        assert_eq!((mapping.name, mapping.credit, mapping.score, mapping.semester), (2, 0, 1, None));
+    }
+
+    #[test]
+    fn two_row_header_after_title() {
+        // 教务系统导出的成绩单: 标题行, "成绩"下分为平时、期末、总评
+        let rows = rows(&[
+            &["2023-2024学年成绩单"],
+            &["课程名称", "学分", "成绩", "", ""],
+            &["", "", "平时", "期末", "总评"],
+            &["高等数学", "4", "80", "92", "90"],
+        ]);
+        let header = detect_header(&rows).unwrap();
+        assert_eq!(header.row, 1);
+        assert_eq!(header.headers, ["课程名称", "学分", "平时", "期末", "总评"]);
+        assert_eq!((header.mapping.score, header.mapping.start_row), (4, 3));
+
+        let parsed = parse_rows(&rows, &GradingTable::default());
+        assert_eq!(parsed.courses.len(), 1);
+        assert_eq!(parsed.courses[0].score, Score::from("90"));
+        assert!(parsed.skipped.is_empty());
+    }
+
+    #[test]
+    fn gbk_csv() {
+        let (data, _, _) = GB18030.encode("课程名称,学分,成绩\n高等数学,4,90\n大学英语,2,良\n");
+        assert!(std::str::from_utf8(&data).is_err());
+
+        let parsed = parse_csv(&data, &GradingTable::default()).unwrap();
+        let names: Vec<&str> = parsed.courses.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["高等数学", "大学英语"]);
+        assert_eq!(parsed.courses[1].score, Score::from("良"));
+    }
+}
@@ -0,0 +1,105 @@
+// 邮件通知层 - 后台成绩监控发现新成绩时, 通过用户设置的 SMTP 服务器发送一封成绩摘要邮件
+// 适合在家里的小服务器上长期运行本程序、平时不在电脑前的同学
+use crate::{config::SmtpConfig, notification::describe, storage::GradeDiff};
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor
+};
+use rust_decimal::Decimal;
+use std::time::Duration;
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+const IMPLICIT_TLS_PORT: u16 = 465;
+
+// 一种计算模式的绩点变化, 之前没有该模式的绩点时 before 为空
+#[derive(Debug, Clone)]
+pub struct GpaChange {
+    pub label: &'static str,
+    pub before: Option<Decimal>,
+    pub after: Decimal,
+}
+
+// 邮件通知, 启动时检查发件人与收件人地址
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailNotifier {
+    pub fn new(config: &SmtpConfig) -> Result<Self> {
+        let from = config.from.parse::<Mailbox>().with_context(|| format!("发件人地址无效: {}", config.from))?;
+        let to = config.to.iter()
+            .map(|to| to.parse::<Mailbox>().with_context(|| format!("收件人地址无效: {}", to)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let builder = if config.port == IMPLICIT_TLS_PORT {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+        }.with_context(|| format!("SMTP 服务器地址无效: {}", config.host))?;
+        let transport = builder
+            .port(config.port)
+            .credentials(Credentials::new(config.username.clone(), config.password.clone()))
+            .timeout(Some(SEND_TIMEOUT))
+            .build();
+
+        Ok(Self { transport, from, to })
+    }
+
+    /// 发送成绩摘要邮件, 包括新增与变动的成绩以及各模式绩点的变化
+    pub async fn send_grade_diff(&self, diff: &GradeDiff, gpas: &[GpaChange]) -> Result<()> {
+        let (title, _) = describe(diff);
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(format!("【GPA查询】{}", title))
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message.body(render_body(&title, diff, gpas)).context("邮件内容生成失败")?;
+
+        self.transport.send(message).await.context("邮件发送失败")?;
+
+        Ok(())
+    }
+}
+
+/// 邮件正文, 纯文本
+fn render_body(title: &str, diff: &GradeDiff, gpas: &[GpaChange]) -> String {
+    let mut lines = vec![title.to_string(), String::new()];
+
+    if !diff.added.is_empty() {
+        lines.push(format!("新增 {} 门成绩：", diff.added.len()));
+        for entry in &diff.added {
+            lines.push(format!("  {} {}：{}", entry.semester, entry.name, entry.to));
+        }
+        lines.push(String::new());
+    }
+    if !diff.changed.is_empty() {
+        lines.push(format!("{} 门成绩有变动：", diff.changed.len()));
+        for entry in &diff.changed {
+            lines.push(format!("  {} {}：{} → {}", entry.semester, entry.name, entry.from.as_deref().unwrap_or("空"), entry.to));
+        }
+        lines.push(String::new());
+    }
+
+    lines.push("平均绩点：".to_string());
+    for gpa in gpas {
+        match gpa.before {
+            Some(before) if before != gpa.after => lines.push(format!("  {}：{} → {}", gpa.label, before, gpa.after)),
+            _ => lines.push(format!("  {}：{}", gpa.label, gpa.after))
+        }
+    }
+
+    lines.push(String::new());
+    lines.push(format!("上一次查询：{}", diff.previous_at.format("%Y-%m-%d %H:%M")));
+    lines.push(format!("本次查询：{}", Local::now().format("%Y-%m-%d %H:%M")));
+    lines.push(String::new());
+    lines.push("—— 由 GPA查询 的后台成绩监控自动发送".to_string());
+    lines.join("\n")
+}
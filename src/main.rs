@@ -1,4 +1,21 @@
-use crate::business::{format_log_msg, print_info};
+use crate::{
+    access::{check_origin, AccessPolicy},
+    backup::BackupStore,
+    business::{format_log_msg, print_error, print_info},
+    changelog::{Changelog, UNTRACKED_VERSION},
+    config::AppConfig,
+    export::{ExportJobs, ExportSigner, ExporterRegistry, PdfExporter},
+    mail::EmailNotifier,
+    profile::ProfileStore,
+    progress::ProgressHub,
+    resume::ResumeStore,
+    selfcheck::{check_assets, check_data_dir, load_templates, SelfCheckReport},
+    settings::{SettingsStore, SETTINGS_FILE},
+    state::AppState,
+    storage::{SnapshotStore, DATABASE_FILE},
+    summary::SummaryStore,
+    watcher::GradeWatcher
+};
 
 use anyhow::{Context, Result};
 use axum::{
@@ -9,18 +26,41 @@ use axum::{
 };
 use rand::Rng;
 use rust_embed::RustEmbed;
-use std::net::SocketAddr;
-use tera::Tera;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc
+};
 use tokio::{net::TcpListener, sync::broadcast};
 use tower_cookies::{CookieManagerLayer, Key};
 use tower_sessions::{MemoryStore, SessionManagerLayer};
 use webbrowser;
 
+mod access;
+mod backup;
+mod changelog;
+mod cohort;
+mod config;
+mod demo;
 mod models;
+mod notification;
 mod business;
 mod scraping;
+mod settings;
+mod export;
 mod handler;
+mod import;
+mod mail;
+mod policy;
+mod profile;
+mod progress;
+mod resume;
 mod router;
+mod selfcheck;
+mod state;
+mod storage;
+mod summary;
+mod watcher;
 
 // 使用 RustEmbed 宏来嵌入整个 templates 文件夹
 // folder 路径是相对于 Cargo.toml 文件的
@@ -38,28 +78,85 @@ pub struct BinaryAsset; // 持有二进制模板文件
 async fn main() -> Result<()> {
     print_info("初始化服务器中...");
 
-    let (shutdown_tx, mut shutdown_rx) = broadcast::channel::<()>(1);
+    // 启动自检: 逐项检查运行环境, 任一项失败都只提供诊断页面
+    let mut report = SelfCheckReport::default();
 
-    // 初始化模板引擎
-    let mut tera = Tera::default();
-
-    // 遍历所有嵌入的文件
-    for file_path in TemplateAsset::iter() {
-        // 获取文件内容
-        if let Some(embedded_file) = TemplateAsset::get(&file_path) {
-            // embedded_file.data 是文件内容, 类型为 Vec<u8>
-            // embedded_file.metadata 是文件元数据, 比如说是否为目录
-            // 将 Vec<u8> 转换为 &str
-            let content = std::str::from_utf8(embedded_file.data.as_ref())?;
-
-            // 将 HTML 模板添加到 Tera 实例
-            // 这里的 content 已经是借用的形式了(类型 &str), 因此可以不需要借用符号(&)
-            tera.add_raw_template(&file_path, content).with_context(|| format_log_msg(&format!("导入嵌入文件失败: {}", file_path)))?;
+    // 读取启动配置
+    let config = report.record("启动配置", AppConfig::load());
+    if let Some(config) = &config {
+        if config.read_only {
+            print_info("已启用只读模式: 登录查询功能已禁用, 仅可导入文件计算");
+        }
+        if config.demo {
+            print_info("已启用演示模式: 不会连接教务系统, 任意账号密码均返回模拟成绩");
+        }
+        if config.low_memory {
+            print_info("已启用低内存模式: 将逐个获取成绩网页, 并减少缓存的数据");
+        }
+        if config.api_token.is_some() {
+            print_info("已设置 API 令牌: 摘要接口 /api/v1/summary 允许携带令牌跨域访问");
+        }
+        if config.lan_mode() {
+            print_info("已启用局域网模式: 同一网络中的设备也可以访问本程序, 只接受公布的主机名");
+            if config.api_token.is_none() {
+                print_info("局域网模式下未设置 API 令牌: 摘要接口 /api/v1/summary 已停用");
+            }
+        }
+        if let Some(smtp) = &config.smtp {
+            print_info(&format!("已设置 SMTP 服务器 {}: 后台成绩监控发现新成绩时会发送邮件到 {}", smtp.host, smtp.to.join(", ")));
         }
     }
+    let policy = Arc::new(AccessPolicy::new(config.as_ref().unwrap_or(&AppConfig::default())));
 
-    // 构建 Tera 的继承链
-    tera.build_inheritance_chains().with_context(|| format_log_msg("构建Tera继承链失败"))?;
+    // 初始化模板引擎
+    let tera = report.record("模板编译", load_templates());
+    report.record("资源文件", check_assets());
+    let changelog = report.record("更新日志", Changelog::load());
+    // 设置了 SMTP 时检查发件人与收件人地址
+    let mailer = match config.as_ref().and_then(|c| c.smtp.as_ref()) {
+        Some(smtp) => report.record("邮件通知", EmailNotifier::new(smtp)).map(Some),
+        None => Some(None)
+    };
+
+    // 检查数据目录并加载用户设置、成绩数据库、导出签名密钥与学校规则
+    let stores = match &config {
+        Some(config) => report.record(
+            "数据目录",
+            check_data_dir(&config.data_dir).and_then(|_| {
+                // 旧版本不记录版本号, 数据目录中已有设置或成绩数据库时视为从旧版本升级, 以便提示新增的功能
+                let upgraded = [SETTINGS_FILE, DATABASE_FILE].iter().any(|f| config.data_dir.join(f).exists());
+                let settings = SettingsStore::load(&config.data_dir)?;
+                if upgraded && settings.get().last_seen_version.is_none() {
+                    settings.update(|s| s.last_seen_version = Some(UNTRACKED_VERSION.to_string()))?;
+                }
+                Ok((
+                    settings,
+                    SnapshotStore::open(&config.data_dir)?,
+                    ExportSigner::load(&config.data_dir)?,
+                    ProfileStore::load(&config.data_dir)?
+                ))
+            })
+        ),
+        None => None
+    };
+
+    // 绑定地址到 TCP 监听器, 端口被占用时改用随机端口显示诊断页面
+    let bind_ip = config.as_ref().and_then(|c| c.bind).unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    let addr = SocketAddr::new(bind_ip, 8080);
+    let listener = match report.record(
+        "监听端口",
+        TcpListener::bind(addr).await.with_context(|| format!("无法绑定到地址 {}, 可能已有程序正在运行", addr))
+    ) {
+        Some(listener) => listener,
+        None => TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .with_context(|| format_log_msg("无法绑定到任何端口"))?
+    };
+    let addr = listener.local_addr().with_context(|| format_log_msg("获取监听地址失败"))?;
+
+    report.print();
+
+    let (shutdown_tx, mut shutdown_rx) = broadcast::channel::<()>(1);
 
     // 创建 Session 存储
     let store = MemoryStore::default();
@@ -71,21 +168,63 @@ async fn main() -> Result<()> {
     let key = Key::from(&rand::rng().random::<[u8; 64]>());
 
     // 创建路由
-    let app = router::create_router(tera)
+    let router = match (config, tera, stores, changelog, mailer) {
+        (Some(config), Some(mut tera), Some((settings, snapshots, signer, profile)), Some(changelog), Some(mailer)) if report.passed() => {
+            // 每个页面顶部显示监听模式
+            let binding = policy.binding_info();
+            tera.register_function("binding_mode", move |_: &HashMap<String, tera::Value>| Ok(tera::to_value(&binding)?));
+
+            let backups = BackupStore::new(&config.data_dir);
+            let resume = ResumeStore::new(&config.data_dir);
+            // PDF 导出使用启动参数指定的字体
+            let mut exporters = ExporterRegistry::new(signer);
+            exporters.register(PdfExporter::new(config.pdf_font.clone()));
+            let watcher = GradeWatcher::new(config.watch_interval, mailer);
+            let state = AppState {
+                tera: Arc::new(tera),
+                config: Arc::new(config),
+                settings: Arc::new(settings),
+                snapshots: Arc::new(snapshots),
+                progress: Arc::new(ProgressHub::default()),
+                profile: Arc::new(profile),
+                backups: Arc::new(backups),
+                exporters: Arc::new(exporters),
+                export_jobs: Arc::new(ExportJobs::default()),
+                summary: Arc::new(SummaryStore::default()),
+                changelog: Arc::new(changelog),
+                resume: Arc::new(resume),
+                watcher: Arc::new(watcher)
+            };
+            // 只读模式下不登录教务系统, 也就不需要后台成绩监控
+            if !state.config.read_only {
+                tokio::spawn(state.watcher.clone().run(state.clone(), shutdown_tx.subscribe()));
+            }
+            router::create_router(state)
+        }
+        _ => {
+            print_error("启动自检未通过, 浏览器中将显示诊断页面");
+            router::create_diagnostic_router(Arc::new(report))
+        }
+    };
+
+    let app = router
         .layer(Extension(shutdown_tx))  // 增加关闭服务器的扩展
         .layer(middleware::from_fn(move |mut req: Request, next: Next| {
             req.extensions_mut().insert(key.clone());
             async move { next.run(req).await }
         })).layer(session_layer)
-        .layer(CookieManagerLayer::new());
-
-    // 绑定地址到 TCP 监听器
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
-    let listener = TcpListener::bind(addr).await.with_context(|| format_log_msg(&format!("无法绑定到地址 {}", addr)))?;
-    print_info(&format!("服务器将运行于 http://{} ，如不小心关闭浏览器，重新打开浏览器输入该网址即可", addr));
+        .layer(CookieManagerLayer::new())
+        .layer(middleware::from_fn_with_state(policy.clone(), check_origin));  // 局域网模式下检查主机名与请求来源
+
+    // 监听所有地址时, 本机通过 127.0.0.1 访问
+    let local_addr = SocketAddr::new(if addr.ip().is_unspecified() { IpAddr::V4(Ipv4Addr::LOCALHOST) } else { addr.ip() }, addr.port());
+    print_info(&format!("服务器将运行于 http://{} ，如不小心关闭浏览器，重新打开浏览器输入该网址即可", local_addr));
+    if !addr.ip().is_loopback() {
+        print_info(&format!("局域网中的设备可以通过以下主机名访问(端口 {}): {}", addr.port(), policy.advertised_hosts().join(", ")));
+    }
 
     // 自动打开浏览器
-    let _ = webbrowser::open(&format!("http://{}", addr));
+    let _ = webbrowser::open(&format!("http://{}", local_addr));
 
     print_info("服务器启动成功！注意：请勿关闭此窗口，否则程序将终止运行");
 
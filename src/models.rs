@@ -4,19 +4,297 @@ use axum::{
 };
 // 结构体与自定义异常
 use rust_decimal::Decimal;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
 use thiserror::Error;
 use tower_sessions::session::Error as SessionError;
 
 // 课程信息结构体
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Course {
+    #[serde(default)]
+    pub course_code: String,    // 课程编号, 导入文件时可能为空
+    #[serde(default)]
+    pub semester: String,   // 开课学期, 如 2023-2024-1, 导入文件时可能为空或为其他格式, 按学期计算时用 parsed_semester 解析
     pub name: String,       // 课程名称
-    pub nature: String,     // 课程性质
-    pub score: String,      // 总分
+    pub nature: CourseNature,   // 课程性质, 导入文件时可能为空
+    pub score: Score,       // 总分
     pub credit: Decimal,    // 学分
     pub grade: Decimal,     // 绩点
-    pub credit_gpa: Decimal // 加权绩点, 学分 × 绩点
+    pub credit_gpa: Decimal, // 加权绩点, 学分 × 绩点
+    #[serde(default)]
+    pub exam_type: String,  // 考试性质, 如 正常考试/补考/重修, 导入文件时可能为空
+    #[serde(default)]
+    pub teacher: String,    // 任课教师, 成绩页面没有这一列或导入文件时为空
+    #[serde(default)]
+    pub hours: String,      // 学时, 如 48 或 32+16, 原样保留
+    #[serde(default)]
+    pub assessment: String, // 考核方式, 如 考试/考查
+    #[serde(default)]
+    pub minor: bool,        // 是否为辅修(第二学位)课程
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub source: String,     // 课程的来源, 如导入的文件名; 登录查询的课程为空, 与其他来源合并时标为原有数据的来源
+}
+
+// 教务系统中的等级制成绩
+pub const SCORE_LEVELS: &[&str] = &["优", "良", "中", "及格", "不及格", "合格", "不合格"];
+
+// 课程成绩, 解析一次后按类型使用, 换算绩点、显示与导出不再各自解析文字
+// 序列化为成绩的原始文字, 与旧版本保存在会话、备份与历史中的数据兼容
+#[derive(Debug, Clone, PartialEq)]
+pub enum Score {
+    Numeric(Decimal),   // 百分制, 如 85
+    Level(String),      // 等级制, 如 优、良、合格
+    Special(String),    // 其他文字成绩, 如 缓考、免修, 以及换算表中另外设置的等级
+}
+
+impl Score {
+    /// 百分制成绩的分数, 文字成绩返回 None
+    pub fn numeric(&self) -> Option<Decimal> {
+        match self {
+            Score::Numeric(value) => Some(*value),
+            Score::Level(_) | Score::Special(_) => None
+        }
+    }
+
+    /// 文字成绩的文字, 百分制成绩返回 None
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Score::Numeric(_) => None,
+            Score::Level(text) | Score::Special(text) => Some(text)
+        }
+    }
+}
+
+impl From<&str> for Score {
+    /// 解析成绩文字, 能转换为数字的视为百分制
+    fn from(text: &str) -> Self {
+        let text = text.trim();
+        match text.parse::<Decimal>() {
+            Ok(value) => Score::Numeric(value),
+            Err(_) if SCORE_LEVELS.contains(&text) => Score::Level(text.to_string()),
+            Err(_) => Score::Special(text.to_string())
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Score::Numeric(value) => write!(f, "{}", value),
+            Score::Level(text) | Score::Special(text) => f.write_str(text)
+        }
+    }
+}
+
+impl Serialize for Score {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Score {
+    /// 除文字外也接受数字, 如其他脚本生成的 JSON 中的 "score": 85
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawScore {
+            Text(String),
+            Number(Decimal),
+        }
+
+        match RawScore::deserialize(deserializer)? {
+            RawScore::Text(text) => Ok(Score::from(text.as_str())),
+            RawScore::Number(number) => Ok(Score::from(number.to_string().as_str()))
+        }
+    }
+}
+
+// 课程性质, 解析时忽略空白与末尾的"课"字, 如"公共选修"与"公共选修课 "视为同一性质
+// 序列化为统一的名称, 其他性质保留原文(去掉空白)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CourseNature {
+    Compulsory,         // 必修课
+    Elective,           // 选修课
+    PublicElective,     // 公共选修课
+    GeneralElective,    // 通识教育选修课
+    Unknown(String),    // 其他性质, 如学科基础课、辅修课; 导入文件时为空
+}
+
+impl CourseNature {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CourseNature::Compulsory => "必修课",
+            CourseNature::Elective => "选修课",
+            CourseNature::PublicElective => "公共选修课",
+            CourseNature::GeneralElective => "通识教育选修课",
+            CourseNature::Unknown(text) => text
+        }
+    }
+
+    /// 没有课程性质, 如导入的模板文件
+    pub fn is_empty(&self) -> bool {
+        matches!(self, CourseNature::Unknown(text) if text.is_empty())
+    }
+
+    /// 是否属于列表中的某一性质, 列表中的名称按同样的规则解析, 如排除规则中的"通识教育选修"也能匹配"通识教育选修课"
+    pub fn is_any_of(&self, names: &[String]) -> bool {
+        names.iter().any(|name| CourseNature::from(name.as_str()) == *self)
+    }
+}
+
+impl Default for CourseNature {
+    fn default() -> Self {
+        CourseNature::Unknown(String::new())
+    }
+}
+
+impl From<&str> for CourseNature {
+    fn from(text: &str) -> Self {
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        match text.strip_suffix('课').unwrap_or(&text) {
+            "必修" => CourseNature::Compulsory,
+            "选修" => CourseNature::Elective,
+            "公共选修" => CourseNature::PublicElective,
+            "通识教育选修" | "通识选修" => CourseNature::GeneralElective,
+            _ => CourseNature::Unknown(text)
+        }
+    }
+}
+
+impl fmt::Display for CourseNature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for CourseNature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for CourseNature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|text| CourseNature::from(text.as_str()))
+    }
+}
+
+// 解析成绩时因学分或成绩无法识别而跳过的一行, 在结果页提示用户有数据未计入, 导入文件时也随上传结果返回
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedRow {
+    #[serde(default)]
+    pub location: String,   // 所在文件或成绩页面, 如 成绩.xlsx、2023-2024-1学期第1页
+    pub row: usize,         // 行号, 从 1 开始; 文件中为表格的行号, 成绩页面中为第几条成绩
+    pub name: String,       // 课程名称
+    #[serde(default)]
+    pub column: String,     // 无法识别的单元格所在列, 如 学分、成绩; 整行无法识别时为空
+    #[serde(default)]
+    pub value: String,      // 无法识别的单元格内容
+    pub reason: String,     // 跳过的原因
+}
+
+// 从成绩页面或文件中解析出的课程, 以及无法解析而跳过的行
+#[derive(Debug, Default)]
+pub struct ParsedCourses {
+    pub courses: Vec<Course>,
+    pub skipped: Vec<SkippedRow>,
+    pub sheet: Option<String>,  // 读取的工作表名称, 只有表格文件才有
+}
+
+impl Course {
+    /// 解析开课学期, 为空或格式不是 2023-2024-1 时返回 None
+    pub fn parsed_semester(&self) -> Option<Semester> {
+        self.semester.parse().ok()
+    }
+}
+
+// 学期, 如 2023-2024-1 表示 2023-2024 学年第 1 学期, 先按学年再按学期排序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Semester {
+    pub start_year: i32,    // 学年的起始年份, 如 2023
+    pub term: u8,           // 学年中的第几学期, 从 1 开始, 部分学校的小学期为 3
+}
+
+impl Semester {
+    /// 所属学年, 如 2023-2024
+    pub fn academic_year(&self) -> String {
+        format!("{}-{}", self.start_year, self.start_year + 1)
+    }
+
+    /// 学期文字的排序依据: 能识别的学期按时间先后, 无法识别的排在后面并按文字排序
+    pub fn order_key(text: &str) -> (bool, Option<Semester>, &str) {
+        let semester = text.parse::<Semester>().ok();
+        (semester.is_none(), semester, text)
+    }
+}
+
+impl FromStr for Semester {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("无效的学期: {}, 格式应为 2023-2024-1", text);
+
+        let mut parts = text.trim().split('-');
+        let (Some(start), Some(end), Some(term), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        let start_year: i32 = start.parse().map_err(|_| invalid())?;
+        let end_year: i32 = end.parse().map_err(|_| invalid())?;
+        let term: u8 = term.parse().map_err(|_| invalid())?;
+        if end_year != start_year + 1 || term == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self { start_year, term })
+    }
+}
+
+impl fmt::Display for Semester {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}-{}", self.start_year, self.start_year + 1, self.term)
+    }
+}
+
+impl Serialize for Semester {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Semester {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// 学生基本信息, 登录后从教务系统首页获取, 用于在结果页标明成绩属于谁
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StudentProfile {
+    pub name: String,       // 姓名
+    pub student_id: String, // 学号
+    #[serde(default)]
+    pub major: String,      // 专业, 首页未显示时为空
+    #[serde(default)]
+    pub class_name: String, // 班级, 首页未显示时为空
+    #[serde(default)]
+    pub enrollment_year: Option<i32>,   // 入学年份, 首页未显示时由学号推断
+}
+
+impl StudentProfile {
+    /// 由学号前 4 位推断入学年份, 本校学号以入学年份开头, 如 2023000001
+    pub fn enrollment_year_from_id(student_id: &str) -> Option<i32> {
+        let year: i32 = student_id.trim().get(..4)?.parse().ok()?;
+        (1950..=2100).contains(&year).then_some(year)
+    }
+}
+
+// 试运行的结果, 只包含成绩列表第一页的概况, 不含具体成绩
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GradeProbe {
+    pub page_count: usize,      // 成绩列表总页数
+    pub first_page_rows: usize, // 第一页的课程数
+    pub semesters: Vec<String>, // 第一页中出现的学期
 }
 
 // 网页爬取异常
@@ -25,12 +303,21 @@ pub enum WebScrapingError {
     #[error("HTTP 请求失败: {0}")]
     HttpRequest(String),
 
+    #[error("连接教务系统超时, 学校服务器可能繁忙或网络不稳定, 请稍后重试。")]
+    Timeout,
+
     #[error("Cookie无效或不存在。")]
     CookieInvalid,
 
     #[error("登录失败")]
     LoginFailed,
 
+    #[error("教务系统登录状态已过期, 自动重新登录失败, 请重新查询。")]
+    SessionExpired,
+
+    #[error("教务系统提示教学评价未完成, 暂时无法查看成绩。请先登录教务系统完成评教({0}), 然后重新查询。")]
+    EvaluationRequired(String), // 评教页面地址
+
     #[error("解析异常: {0}")]
     ParseError(String)
 }
@@ -43,6 +330,15 @@ pub enum FileError {
 
     #[error("上传的文件中未找到有效的课程数据, 请检查文件内容和格式是否正确。")]
     NoValidDataFound,
+
+    #[error("上传的文件中有{0}行数据, 但都未能识别: {1}")]
+    NoRowRecognized(usize, String),  // 无法识别的行数与前几行的原因
+
+    #[error("文件过大, 单次上传的文件不能超过 {0} MB。")]
+    TooLarge(usize),    // 上传大小上限(MB)
+
+    #[error("不是有效的 {0} 文件, 文件可能已损坏或只是改了扩展名, 请用 Excel 或 WPS 打开后重新另存再上传。")]
+    InvalidSpreadsheet(&'static str),   // 按扩展名期望的文件类型
 }
 
 // 网页服务异常
@@ -60,6 +356,15 @@ pub enum WebError {
     #[error("会话错误: {0}")]
     SessionError(#[from] SessionError),
 
+    #[error("参数错误: {0}")]
+    InvalidInput(String),
+
+    #[error("只读模式下无法登录教务系统, 请使用免登录计算模式。")]
+    ReadOnly,
+
+    #[error("访问令牌无效")]
+    Unauthorized,
+
     #[error("内部错误: {0}")]
     InternalError(String)
 }
@@ -73,15 +378,27 @@ impl IntoResponse for WebError {
                 format!("模板错误: {}", msg)
             ),
             WebError::WebScrapingError(scraper_err) => match scraper_err {
-                WebScrapingError::LoginFailed => (
+                WebScrapingError::LoginFailed | WebScrapingError::SessionExpired => (
                     StatusCode::UNAUTHORIZED,
                     scraper_err.to_string()
                 ),
+                WebScrapingError::Timeout => (
+                    StatusCode::GATEWAY_TIMEOUT,
+                    scraper_err.to_string()
+                ),
+                WebScrapingError::EvaluationRequired(_) => (
+                    StatusCode::FORBIDDEN,
+                    scraper_err.to_string()
+                ),
                 _ => (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     scraper_err.to_string()
                 )
             },
+            WebError::FileError(FileError::TooLarge(limit)) => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                FileError::TooLarge(limit).to_string()
+            ),
             WebError::FileError(msg) => (
                 StatusCode::BAD_REQUEST,
                 msg.to_string()
@@ -90,6 +407,18 @@ impl IntoResponse for WebError {
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("会话错误: {}", msg)
             ),
+            WebError::InvalidInput(msg) => (
+                StatusCode::BAD_REQUEST,
+                format!("参数错误: {}", msg)
+            ),
+            WebError::ReadOnly => (
+                StatusCode::FORBIDDEN,
+                WebError::ReadOnly.to_string()
+            ),
+            WebError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                WebError::Unauthorized.to_string()
+            ),
             WebError::InternalError(msg) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("内部错误: {}", msg)
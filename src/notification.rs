@@ -0,0 +1,47 @@
+// 桌面通知层 - 发现新发布的成绩时弹出系统通知, 不用一直开着结果页也能第一时间知道出成绩了
+use crate::{business::print_error, storage::{DiffEntry, GradeDiff}};
+
+use notify_rust::Notification;
+
+const APP_NAME: &str = "GPA查询";
+const BODY_LIMIT: usize = 5;    // 通知正文最多列出的课程数, 其余只给出数量
+
+/// 弹出成绩变化的桌面通知, 没有变化时不通知
+/// 没有桌面环境(如在服务器上运行)时通知会失败, 只记录日志
+pub fn notify_grade_diff(diff: &GradeDiff) {
+    if diff.is_empty() {
+        return;
+    }
+
+    let (summary, body) = describe(diff);
+    // 部分平台上显示通知是同步调用, 放到阻塞线程中执行, 不占用处理请求的线程
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = Notification::new().appname(APP_NAME).summary(&summary).body(&body).show() {
+            print_error(&format!("桌面通知发送失败: {}", e));
+        }
+    });
+}
+
+/// 通知的标题与正文, 只有一门新成绩时标题直接给出课程名称, 如"高等数学A(一) 出成绩了"; 邮件通知使用同样的标题
+pub fn describe(diff: &GradeDiff) -> (String, String) {
+    let summary = match (diff.added.as_slice(), diff.changed.as_slice()) {
+        ([course], _) => format!("{} 出成绩了", course.name),
+        ([], [course]) => format!("{} 的成绩有变动", course.name),
+        ([], changed) => format!("{} 门成绩有变动", changed.len()),
+        (added, _) => format!("新增 {} 门成绩", added.len())
+    };
+
+    let entries: Vec<&DiffEntry> = diff.added.iter().chain(&diff.changed).collect();
+    let mut lines: Vec<String> = entries.iter()
+        .take(BODY_LIMIT)
+        .map(|entry| match &entry.from {
+            Some(from) => format!("{}：{} → {}", entry.name, from, entry.to),
+            None => format!("{}：{}", entry.name, entry.to)
+        })
+        .collect();
+    if entries.len() > BODY_LIMIT {
+        lines.push(format!("等 {} 门课程", entries.len()));
+    }
+
+    (summary, lines.join("\n"))
+}
@@ -0,0 +1,103 @@
+// 绩点计算策略层 - 课程排除、成绩换算、重复课程与取整的计算方式
+// 关键字、换算表等规则数据保存在 SchoolProfile 中, 策略决定如何使用这些数据; profile.json 的 policy 字段选择使用哪个策略
+use crate::{
+    business::{dedup_courses, score_trans_grade},
+    models::{Course, Score},
+    profile::SchoolProfile
+};
+
+use rust_decimal::Decimal;
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
+
+pub const DEFAULT_POLICY: &str = "yit";
+
+// 一所学校的绩点计算方式
+pub trait GPAPolicy: Send + Sync + Debug {
+    /// 策略名称, 即 profile.json 中的 policy
+    fn name(&self) -> &'static str;
+
+    /// 显示名称
+    fn label(&self) -> &'static str;
+
+    /// 任何模式都不计入的课程
+    fn is_ignored(&self, course: &Course, profile: &SchoolProfile) -> bool;
+
+    /// Default 模式是否计入该课程
+    fn counts_in_default(&self, course: &Course, profile: &SchoolProfile) -> bool;
+
+    /// 把成绩换算为绩点, 无法识别时返回 None
+    fn grade(&self, score: &Score, profile: &SchoolProfile) -> Option<Decimal>;
+
+    /// 同一课程有多条成绩时只保留计入的成绩
+    fn dedup(&self, courses: &[Course], profile: &SchoolProfile) -> Vec<Course>;
+
+    /// 绩点、加权绩点的取整
+    fn round(&self, value: Decimal, profile: &SchoolProfile) -> Decimal;
+}
+
+// 本校(燕京理工学院)的计算方式, 完全按 profile.json 中的规则数据计算
+#[derive(Debug)]
+pub struct YitPolicy;
+
+impl GPAPolicy for YitPolicy {
+    fn name(&self) -> &'static str {
+        DEFAULT_POLICY
+    }
+
+    fn label(&self) -> &'static str {
+        "燕京理工学院"
+    }
+
+    fn is_ignored(&self, course: &Course, profile: &SchoolProfile) -> bool {
+        profile.exclusion.ignored_courses.contains(&course.name)
+    }
+
+    fn counts_in_default(&self, course: &Course, profile: &SchoolProfile) -> bool {
+        let rules = &profile.exclusion;
+        !rules.keywords.iter().any(|k| course.name.contains(k.as_str()))
+            && !course.nature.is_any_of(&rules.natures)
+            && rules.min_credit.is_none_or(|min| course.credit >= min)
+    }
+
+    fn grade(&self, score: &Score, profile: &SchoolProfile) -> Option<Decimal> {
+        score_trans_grade(score, &profile.grading)
+    }
+
+    fn dedup(&self, courses: &[Course], profile: &SchoolProfile) -> Vec<Course> {
+        dedup_courses(courses, profile.retake_policy, &profile.grading.rounding)
+    }
+
+    fn round(&self, value: Decimal, profile: &SchoolProfile) -> Decimal {
+        profile.grading.rounding.round(value)
+    }
+}
+
+// 所有可用的计算策略, 以策略名称查找
+pub struct PolicyRegistry {
+    policies: BTreeMap<&'static str, Arc<dyn GPAPolicy>>,
+}
+
+impl PolicyRegistry {
+    /// 注册一个计算策略, 同名策略会被替换
+    pub fn register(&mut self, policy: impl GPAPolicy + 'static) {
+        self.policies.insert(policy.name(), Arc::new(policy));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn GPAPolicy>> {
+        self.policies.get(name).cloned()
+    }
+
+    /// 已注册的策略, 按名称排列
+    pub fn list(&self) -> Vec<Arc<dyn GPAPolicy>> {
+        self.policies.values().cloned().collect()
+    }
+}
+
+impl Default for PolicyRegistry {
+    /// 内置的全部计算策略
+    fn default() -> Self {
+        let mut registry = Self { policies: BTreeMap::new() };
+        registry.register(YitPolicy);
+        registry
+    }
+}
@@ -0,0 +1,399 @@
+// 学校规则层 - 与具体学校相关、每年可能调整的计算规则
+// 默认内置本校规则, 数据目录中存在 profile.json 时以该文件为准, 便于规则调整后不必重新编译
+use crate::{
+    business::{RetakePolicy, RoundingRule, SpecialScoreRule, EXCLUDED_COURSES_KEYWORD, MAX_ROUNDING_PRECISION, NATURE_EXCLUSIONS, PERMANENT_IGNORED_COURSES},
+    models::Score,
+    policy::{GPAPolicy, PolicyRegistry, YitPolicy, DEFAULT_POLICY}
+};
+
+use anyhow::{bail, Context, Result};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock}
+};
+
+const PROFILE_FILE: &str = "profile.json";
+
+// 综测中需要手动填写的一项, 如德育、体育
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationComponent {
+    pub name: String,
+    pub weight: Decimal,    // 在综测总分中的占比, 如 0.2
+    pub max_score: Decimal, // 该项满分
+}
+
+// 综合素质测评(综测)的计算规则
+// 综测总分 = 智育分 × 智育占比 + Σ(各手填项得分 × 占比) + 加分(不超过上限)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationRules {
+    pub gpa_multiplier: Decimal,    // 智育分 = 绩点 × 该倍数, 超过 100 按 100 计
+    pub gpa_weight: Decimal,    // 智育分在综测总分中的占比
+    #[serde(default)]
+    pub semester_weights: Vec<Decimal>, // 第 1、2、3… 学期绩点的权重, 超出的学期使用最后一个权重; 为空时按学分加权
+    #[serde(default)]
+    pub components: Vec<EvaluationComponent>,
+    pub bonus_cap: Decimal,     // 加分项合计上限
+}
+
+impl Default for EvaluationRules {
+    fn default() -> Self {
+        Self {
+            gpa_multiplier: dec!(20),
+            gpa_weight: dec!(0.7),
+            components: vec![
+                EvaluationComponent { name: "德育".to_string(), weight: dec!(0.2), max_score: dec!(100) },
+                EvaluationComponent { name: "体育".to_string(), weight: dec!(0.1), max_score: dec!(100) },
+            ],
+            semester_weights: Vec::new(),
+            bonus_cap: dec!(5),
+        }
+    }
+}
+
+impl EvaluationRules {
+    /// 检查学期权重: 不能为负数, 也不能全部为 0
+    pub fn validate(&self) -> Result<()> {
+        if let Some(weight) = self.semester_weights.iter().find(|w| w.is_sign_negative()) {
+            bail!("综测的学期权重不能为负数: {}", weight);
+        }
+        if !self.semester_weights.is_empty() && self.semester_weights.iter().all(|w| w.is_zero()) {
+            bail!("综测的学期权重不能全部为 0");
+        }
+
+        Ok(())
+    }
+
+    /// 按学生的第几个学期(从 0 开始)取权重, 未设置学期权重时返回 None
+    pub fn semester_weight(&self, index: usize) -> Option<Decimal> {
+        let last = self.semester_weights.len().checked_sub(1)?;
+        Some(self.semester_weights[index.min(last)])
+    }
+}
+
+// 课程排除规则, 用户可以在结果页增删, 文件中未设置的列表使用程序内置的列表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExclusionRules {
+    #[serde(default = "default_keywords")]
+    pub keywords: Vec<String>,  // Default 模式中, 课程名称包含这些关键字的课程不计入
+    #[serde(default = "default_natures")]
+    pub natures: Vec<String>,   // Default 模式中, 这些课程性质的课程不计入
+    #[serde(default = "default_ignored_courses")]
+    pub ignored_courses: Vec<String>,   // 任何模式都不计入的课程, 需与课程名称完全一致
+    #[serde(default)]
+    pub min_credit: Option<Decimal>,    // 学分低于该值的课程不计入, 如 1 表示排除 0.5 学分的讲座; 为空则不限制
+}
+
+impl Default for ExclusionRules {
+    fn default() -> Self {
+        Self {
+            keywords: default_keywords(),
+            natures: default_natures(),
+            ignored_courses: default_ignored_courses(),
+            min_credit: None,
+        }
+    }
+}
+
+impl ExclusionRules {
+    /// 检查排除规则, 规则会显示在结果页, 不允许控制字符与尖括号
+    pub fn validate(&self) -> Result<()> {
+        self.keywords.iter().chain(&self.natures).chain(&self.ignored_courses).try_for_each(|v| Self::check_value(v))
+    }
+
+    /// 检查单条排除规则
+    pub fn check_value(value: &str) -> Result<()> {
+        if value.chars().any(|c| c.is_control() || c == '<' || c == '>') {
+            bail!("排除规则“{}”中不能含有控制字符或尖括号", value.escape_debug());
+        }
+
+        Ok(())
+    }
+
+    /// 取得指定的排除列表以便增删
+    pub fn list_mut(&mut self, list: ExclusionList) -> &mut Vec<String> {
+        match list {
+            ExclusionList::Keyword => &mut self.keywords,
+            ExclusionList::Nature => &mut self.natures,
+            ExclusionList::Ignored => &mut self.ignored_courses
+        }
+    }
+}
+
+// 可以增删的排除列表
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExclusionList {
+    Keyword,
+    Nature,
+    Ignored,
+}
+
+fn default_keywords() -> Vec<String> {
+    EXCLUDED_COURSES_KEYWORD.iter().map(|s| s.to_string()).collect()
+}
+
+fn default_natures() -> Vec<String> {
+    NATURE_EXCLUSIONS.iter().map(|s| s.to_string()).collect()
+}
+
+fn default_ignored_courses() -> Vec<String> {
+    PERMANENT_IGNORED_COURSES.iter().map(|s| s.to_string()).collect()
+}
+
+// 百分制成绩的一档, 成绩不低于 min 且低于上一档时取该档绩点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreRange {
+    pub min: Decimal,
+    pub grade: Decimal,
+}
+
+// 成绩到绩点的换算表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradingTable {
+    pub max_score: Decimal, // 百分制满分, 超过满分的成绩视为无法识别
+    pub ranges: Vec<ScoreRange>,    // 百分制分档, 顺序不限
+    #[serde(default = "default_levels")]
+    pub levels: BTreeMap<String, Decimal>,  // 等级制成绩, 如 优 → 4.33; 文件中未设置时使用内置的等级
+    #[serde(default = "default_level_scores")]
+    pub level_scores: BTreeMap<String, Decimal>,    // 计算平均分或换算为其他标准时等级制成绩按多少分计, 如 优 → 95
+    #[serde(default)]
+    pub rounding: RoundingRule, // 绩点、加权绩点与平均分的取整方式, 如教务处直接截断到 2 位小数
+    #[serde(default = "default_special_scores")]
+    pub special_scores: BTreeMap<String, SpecialScoreRule>, // 缓考、免修等特殊成绩的处理方式; 文件中未设置时使用内置的处理方式
+}
+
+impl Default for GradingTable {
+    fn default() -> Self {
+        let ranges = [
+            (dec!(95), dec!(4.67)), (dec!(90), dec!(4.33)), (dec!(87), dec!(4.00)), (dec!(83), dec!(3.67)),
+            (dec!(80), dec!(3.33)), (dec!(77), dec!(3.00)), (dec!(74), dec!(2.67)), (dec!(70), dec!(2.33)),
+            (dec!(67), dec!(2.00)), (dec!(64), dec!(1.67)), (dec!(60), dec!(1.33)), (dec!(0), dec!(0)),
+        ];
+
+        Self {
+            max_score: dec!(100),
+            ranges: ranges.into_iter().map(|(min, grade)| ScoreRange { min, grade }).collect(),
+            levels: default_levels(),
+            level_scores: default_level_scores(),
+            rounding: RoundingRule::default(),
+            special_scores: default_special_scores(),
+        }
+    }
+}
+
+fn default_levels() -> BTreeMap<String, Decimal> {
+    let levels = [
+        ("优", dec!(4.33)), ("良", dec!(3.33)), ("中", dec!(2.33)),
+        ("及格", dec!(1)), ("合格", dec!(1)), ("不及格", dec!(0)), ("不合格", dec!(0)),
+    ];
+    levels.into_iter().map(|(name, grade)| (name.to_string(), grade)).collect()
+}
+
+fn default_level_scores() -> BTreeMap<String, Decimal> {
+    let scores = [
+        ("优", dec!(95)), ("良", dec!(85)), ("中", dec!(75)),
+        ("及格", dec!(65)), ("合格", dec!(65)), ("不及格", dec!(0)), ("不合格", dec!(0)),
+    ];
+    scores.into_iter().map(|(name, score)| (name.to_string(), score)).collect()
+}
+
+fn default_special_scores() -> BTreeMap<String, SpecialScoreRule> {
+    let rules = [
+        ("缓考", SpecialScoreRule::Exclude), ("免修", SpecialScoreRule::Pass), ("免考", SpecialScoreRule::Pass),
+        ("缺考", SpecialScoreRule::Zero), ("违纪", SpecialScoreRule::Zero), ("作弊", SpecialScoreRule::Zero),
+    ];
+    rules.into_iter().map(|(score, rule)| (score.to_string(), rule)).collect()
+}
+
+impl GradingTable {
+    /// 单门课程可获得的最高绩点, 即百分制与等级制中最高一档的绩点
+    pub fn max_grade(&self) -> Decimal {
+        self.ranges.iter().map(|r| r.grade).chain(self.levels.values().copied()).max().unwrap_or_default()
+    }
+
+    /// 特殊成绩的处理方式, 不是特殊成绩时返回 None
+    pub fn special_rule(&self, score: &Score) -> Option<SpecialScoreRule> {
+        self.special_scores.get(score.text()?).copied()
+    }
+
+    /// 检查换算表: 0 分到满分都要有对应的档位, 且成绩越高绩点不能越低
+    pub fn validate(&self) -> Result<()> {
+        let mut ranges: Vec<&ScoreRange> = self.ranges.iter().collect();
+        ranges.sort_by_key(|r| r.min);
+
+        if ranges.first().is_none_or(|r| !r.min.is_zero()) {
+            bail!("成绩换算表缺少从 0 分开始的档位");
+        }
+        if let Some(r) = ranges.iter().find(|r| r.min > self.max_score) {
+            bail!("成绩换算表中 {} 分的档位超过了满分 {}", r.min, self.max_score);
+        }
+        if let Some(pair) = ranges.windows(2).find(|pair| pair[0].min == pair[1].min) {
+            bail!("成绩换算表中 {} 分的档位重复", pair[0].min);
+        }
+        if let Some(pair) = ranges.windows(2).find(|pair| pair[0].grade > pair[1].grade) {
+            bail!("成绩换算表中 {} 分的绩点 {} 低于 {} 分的绩点 {}", pair[1].min, pair[1].grade, pair[0].min, pair[0].grade);
+        }
+
+        let negative = ranges.iter().map(|r| r.grade).chain(self.levels.values().copied()).find(|g| g.is_sign_negative());
+        if let Some(grade) = negative {
+            bail!("成绩换算表中的绩点不能为负数: {}", grade);
+        }
+
+        if let Some((name, score)) = self.level_scores.iter().find(|(_, s)| s.is_sign_negative() || **s > dec!(100)) {
+            bail!("等级制成绩“{}”对应的分数 {} 应在 0 到 100 之间", name, score);
+        }
+
+        if let Some(score) = self.special_scores.keys().find(|s| self.levels.contains_key(*s) || s.parse::<Decimal>().is_ok()) {
+            bail!("特殊成绩“{}”与等级制成绩或百分制成绩重复", score);
+        }
+
+        if self.rounding.precision > MAX_ROUNDING_PRECISION {
+            bail!("取整保留的小数位数 {} 不能超过 {}", self.rounding.precision, MAX_ROUNDING_PRECISION);
+        }
+
+        Ok(())
+    }
+}
+
+// 学校规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchoolProfile {
+    pub name: String,
+    #[serde(default)]
+    pub evaluation: EvaluationRules,
+    #[serde(default)]
+    pub exclusion: ExclusionRules,
+    #[serde(default)]
+    pub grading: GradingTable,
+    #[serde(default)]
+    pub retake_policy: RetakePolicy,    // 补考、重修等重复课程的处理方式
+    #[serde(default = "default_policy_name")]
+    pub policy: String,     // 计算策略名称, 决定如何使用上面的规则
+    #[serde(skip, default = "default_policy")]
+    engine: Arc<dyn GPAPolicy>, // 按 policy 查找到的计算策略, 加载规则时设置
+}
+
+fn default_policy_name() -> String {
+    DEFAULT_POLICY.to_string()
+}
+
+fn default_policy() -> Arc<dyn GPAPolicy> {
+    Arc::new(YitPolicy)
+}
+
+impl Default for SchoolProfile {
+    fn default() -> Self {
+        Self {
+            name: "燕京理工学院".to_string(),
+            evaluation: EvaluationRules::default(),
+            exclusion: ExclusionRules::default(),
+            grading: GradingTable::default(),
+            retake_policy: RetakePolicy::default(),
+            policy: default_policy_name(),
+            engine: default_policy(),
+        }
+    }
+}
+
+impl SchoolProfile {
+    /// 从数据目录加载学校规则, 文件不存在时使用内置规则
+    /// 文件存在但格式有误时返回错误, 避免以错误的规则静默计算
+    pub fn load(data_dir: &Path, policies: &PolicyRegistry) -> Result<Self> {
+        let path = data_dir.join(PROFILE_FILE);
+
+        let mut profile: Self = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("学校规则文件 {} 格式有误", path.display()))?,
+            Err(_) => Self::default()
+        };
+        profile.grading.validate().with_context(|| format!("学校规则文件 {} 有误", path.display()))?;
+        profile.evaluation.validate().with_context(|| format!("学校规则文件 {} 有误", path.display()))?;
+        profile.exclusion.validate().with_context(|| format!("学校规则文件 {} 有误", path.display()))?;
+        profile.resolve_policy(policies).with_context(|| format!("学校规则文件 {} 有误", path.display()))?;
+
+        Ok(profile)
+    }
+
+    /// 当前使用的计算策略
+    pub fn policy(&self) -> &dyn GPAPolicy {
+        self.engine.as_ref()
+    }
+
+    /// 按 policy 字段查找计算策略, 未注册的名称返回错误
+    fn resolve_policy(&mut self, policies: &PolicyRegistry) -> Result<()> {
+        match policies.get(&self.policy) {
+            Some(engine) => self.engine = engine,
+            None => {
+                let names: Vec<&str> = policies.list().iter().map(|p| p.name()).collect();
+                bail!("未知的计算策略 {}, 可用的策略: {}", self.policy, names.join("、"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// 学校规则存储, 导入设置文件时可以在运行时替换规则
+pub struct ProfileStore {
+    path: PathBuf,
+    profile: RwLock<Arc<SchoolProfile>>,
+    policies: PolicyRegistry,
+}
+
+impl ProfileStore {
+    /// 从数据目录加载学校规则
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let policies = PolicyRegistry::default();
+        Ok(Self {
+            path: data_dir.join(PROFILE_FILE),
+            profile: RwLock::new(Arc::new(SchoolProfile::load(data_dir, &policies)?)),
+            policies,
+        })
+    }
+
+    /// 可以选择的计算策略
+    pub fn policies(&self) -> &PolicyRegistry {
+        &self.policies
+    }
+
+    /// 获取当前规则, 替换规则不影响正在使用旧规则的请求
+    pub fn get(&self) -> Arc<SchoolProfile> {
+        self.profile.read().unwrap().clone()
+    }
+
+    /// 替换规则并写入 profile.json, 下次启动时继续使用
+    pub fn replace(&self, profile: SchoolProfile) -> Result<()> {
+        self.update(|p| *p = profile).map(|_| ())
+    }
+
+    /// 检查规则能否使用: 成绩换算表、综测规则与排除规则有效且计算策略已注册
+    pub fn validate(&self, profile: &mut SchoolProfile) -> Result<()> {
+        profile.grading.validate()?;
+        profile.evaluation.validate()?;
+        profile.exclusion.validate()?;
+        profile.resolve_policy(&self.policies)
+    }
+
+    /// 修改规则并立即写回 profile.json
+    pub fn update(&self, f: impl FnOnce(&mut SchoolProfile)) -> Result<Arc<SchoolProfile>> {
+        let mut guard = self.profile.write().unwrap();
+        let mut profile = (**guard).clone();
+        f(&mut profile);
+        self.validate(&mut profile)?;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("无法创建数据目录 {}", dir.display()))?;
+        }
+        let content = serde_json::to_string_pretty(&profile)?;
+        fs::write(&self.path, content).with_context(|| format!("无法写入学校规则文件 {}", self.path.display()))?;
+
+        *guard = Arc::new(profile);
+
+        Ok(guard.clone())
+    }
+}
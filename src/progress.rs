@@ -0,0 +1,106 @@
+// 进度通知层 - 登录查询耗时较长, 通过 SSE 把各阶段进度实时推送给前端
+use futures::{stream, Stream};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration
+};
+use tokio::{sync::watch, time::{sleep, Instant}};
+
+const SUBSCRIBE_WAIT: Duration = Duration::from_secs(5);    // 前端可能先于查询请求建立 SSE 连接, 最多等待这么久
+const SUBSCRIBE_POLL: Duration = Duration::from_millis(100);
+const FINISHED_RETAIN: Duration = Duration::from_secs(10);  // 查询结束后保留最终进度的时间, 让晚到的 SSE 连接也能收到结束通知
+
+// 一次进度通知
+#[derive(Debug, Clone, Serialize)]
+pub struct Progress {
+    pub stage: String,  // 当前阶段, 如"登录中"
+    pub percent: u8,    // 0~100
+    pub done: bool,     // 查询结束(无论成功与否)
+}
+
+// 进度发送端, 交给爬虫与处理器使用
+#[derive(Clone)]
+pub struct ProgressReporter {
+    sender: Arc<watch::Sender<Progress>>,
+}
+
+impl ProgressReporter {
+    /// 通知进入新阶段, 没有前端在监听时静默忽略
+    pub fn report(&self, stage: &str, percent: u8) {
+        self.sender.send_replace(Progress { stage: stage.to_string(), percent: percent.min(100), done: false });
+    }
+
+    /// 通知查询结束
+    pub fn finish(&self, stage: &str) {
+        self.sender.send_replace(Progress { stage: stage.to_string(), percent: 100, done: true });
+    }
+}
+
+// 以前端生成的进度 ID 区分正在进行的查询
+#[derive(Default)]
+pub struct ProgressHub {
+    channels: Mutex<HashMap<String, Arc<watch::Sender<Progress>>>>,
+}
+
+impl ProgressHub {
+    /// 开始一次查询, 返回进度发送端
+    pub fn register(&self, id: &str) -> ProgressReporter {
+        let (sender, _) = watch::channel(Progress { stage: "等待开始".to_string(), percent: 0, done: false });
+        let sender = Arc::new(sender);
+        self.channels.lock().unwrap().insert(id.to_string(), sender.clone());
+
+        ProgressReporter { sender }
+    }
+
+    /// 查询结束后延迟移除, 发送端全部释放后 SSE 连接随之结束
+    pub fn unregister(self: Arc<Self>, id: String) {
+        tokio::spawn(async move {
+            sleep(FINISHED_RETAIN).await;
+            self.channels.lock().unwrap().remove(&id);
+        });
+    }
+
+    /// 订阅指定查询的进度, 查询尚未开始时等待一段时间, 超时返回 None
+    pub async fn subscribe(&self, id: &str) -> Option<watch::Receiver<Progress>> {
+        let deadline = Instant::now() + SUBSCRIBE_WAIT;
+
+        loop {
+            if let Some(sender) = self.channels.lock().unwrap().get(id) {
+                return Some(sender.subscribe());
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            sleep(SUBSCRIBE_POLL).await;
+        }
+    }
+}
+
+// 进度流的状态
+enum StreamState {
+    Waiting(Arc<ProgressHub>, String),  // 等待查询开始
+    Receiving(watch::Receiver<Progress>),
+    Finished,
+}
+
+/// 把指定查询的进度转换为流, 查询结束或超时仍未开始时流随之结束
+pub fn progress_stream(hub: Arc<ProgressHub>, id: String) -> impl Stream<Item = Progress> {
+    stream::unfold(StreamState::Waiting(hub, id), |state| async move {
+        let mut receiver = match state {
+            StreamState::Waiting(hub, id) => hub.subscribe(&id).await?,
+            StreamState::Receiving(mut receiver) => {
+                // 发送端全部释放时 changed 返回错误, 流结束
+                receiver.changed().await.ok()?;
+                receiver
+            }
+            StreamState::Finished => return None
+        };
+
+        let progress = receiver.borrow_and_update().clone();
+        let next = if progress.done { StreamState::Finished } else { StreamState::Receiving(receiver) };
+
+        Some((progress, next))
+    })
+}
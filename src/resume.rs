@@ -0,0 +1,67 @@
+// 断点续传层 - 分学期获取成绩、班级统计等分批进行的任务, 每完成一批就写入数据目录
+// 网络中断或程序重启后重新执行同一任务时, 已完成的部分直接使用保存的结果
+use anyhow::{anyhow, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime}
+};
+
+const RESUME_DIR: &str = "resume";
+
+// 任务进度存储, 每个任务是一个单独的 JSON 文件
+pub struct ResumeStore {
+    dir: PathBuf,
+    lock: Mutex<()>,    // 同一任务的多个部分可能并发完成, 读改写需要互斥
+}
+
+impl ResumeStore {
+    pub fn new(data_dir: &Path) -> Self {
+        Self { dir: data_dir.join(RESUME_DIR), lock: Mutex::new(()) }
+    }
+
+    /// 读取任务进度, 不存在、已损坏或超过 ttl 未更新时返回 None
+    pub fn load<T: DeserializeOwned>(&self, name: &str, ttl: Duration) -> Option<T> {
+        let path = self.path_of(name).ok()?;
+
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        if SystemTime::now().duration_since(modified).unwrap_or_default() > ttl {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+
+        serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()
+    }
+
+    /// 修改任务进度并立即写入, 进度不存在或已过期时从默认值开始
+    pub fn update<T: Serialize + DeserializeOwned + Default>(&self, name: &str, ttl: Duration, f: impl FnOnce(&mut T)) -> Result<T> {
+        let _guard = self.lock.lock().unwrap();
+
+        let mut progress: T = self.load(name, ttl).unwrap_or_default();
+        f(&mut progress);
+
+        fs::create_dir_all(&self.dir).with_context(|| format!("无法创建任务进度目录 {}", self.dir.display()))?;
+        let path = self.path_of(name)?;
+        fs::write(&path, serde_json::to_string(&progress)?).with_context(|| format!("无法写入任务进度 {}", path.display()))?;
+
+        Ok(progress)
+    }
+
+    /// 任务全部完成后删除进度
+    pub fn remove(&self, name: &str) {
+        if let Ok(path) = self.path_of(name) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn path_of(&self, name: &str) -> Result<PathBuf> {
+        // 任务名只能由字母、数字和连字符组成, 防止读写数据目录以外的文件
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(anyhow!("无效的任务名: {}", name));
+        }
+
+        Ok(self.dir.join(format!("{}.json", name)))
+    }
+}
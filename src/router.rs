@@ -1,22 +1,90 @@
 // 纯路由层
-use crate::handler::{
-    download_temp, first_result, login, logout,
-    next_result, score_from_file, score_from_official, shutdown, static_file
+use crate::{
+    handler::{
+        api_changelog, api_summary, api_summary_preflight, add_cohort_file, calculate_comprehensive, cohort_batch_status, cohort_distribution, cohort_page, create_cohort_batch, delete_cohort_batch, export_cohort_batch, delete_course, diagnostic_page, edit_exclusions, download_export_job, download_temp, export_file,
+        export_job_status, export_settings, first_result, get_exclusions, history_page, import_settings, login, logout, next_result, read_only_rejected, redo_course_edit,
+        reset_exclusions, restore_backup, score_dry_run, search_history, simulate_what_if, plan_target_gpa, estimate_class_ranking, set_retake_policy, list_gpa_policies, set_gpa_policy, score_from_file, score_from_text, upload_preview, score_from_official, scrape_progress, semester_gpa, gpa_trend_series, course_impact, grade_distribution_data, semester_options, set_benchmark, set_watch,
+        shutdown, snapshot_page, start_export_job, static_file, undo_course_edit, update_course, verify, watch_status
+    },
+    selfcheck::SelfCheckReport,
+    state::AppState
 };
 
-use axum::{routing::{get, post}, Router};
-use tera::Tera;
+use axum::{extract::DefaultBodyLimit, routing::{get, post}, Router};
+use std::sync::Arc;
+
+pub fn create_router(state: AppState) -> Router {
+    // 只读模式下, 所有需要登录教务系统的接口都直接拒绝
+    let (score_from_official_route, score_dry_run_route, semester_options_route) = if state.config.read_only {
+        (post(read_only_rejected), post(read_only_rejected), post(read_only_rejected))
+    } else {
+        (post(score_from_official), post(score_dry_run), post(semester_options))
+    };
+
+    // 上传文件的大小上限, 超过时读取请求体会失败, 由各处理器提示"文件过大"
+    let upload_limit = DefaultBodyLimit::max(state.config.max_upload_size * 1024 * 1024);
 
-pub fn create_router(tera: Tera) -> Router {
     Router::new()
         .route("/", get(login))    // 根目录是登录页面
-        .route("/score-from-official-website", post(score_from_official))    // 这是回传登录数据的 API 接口
+        .route("/score-from-official-website", score_from_official_route)    // 这是回传登录数据的 API 接口
+        .route("/score-dry-run", score_dry_run_route)    // 试运行, 只测试登录与成绩页面
+        .route("/score-progress", get(scrape_progress))    // 登录查询进度(SSE)
+        .route("/api/semesters", semester_options_route)   // 登录并获取可查询的学期
         .route("/score-from-file", post(score_from_file))  // 免登录 API 接口
+        .route("/score-from-text", post(score_from_text))  // 免登录 API 接口, 粘贴的成绩文字
+        .route("/api/upload/preview", post(upload_preview))  // 预览上传的表格, 供用户指定各列
         .route("/download-template", get(download_temp)) // 获取文件
         .route("/result", get(first_result)) // 显示计算后学分
         .route("/recalc", post(next_result))   // 重新计算 GPA 的 API 接口
+        .route("/api/semester-gpa", get(semester_gpa))  // 各学期绩点
+        .route("/api/trend", get(gpa_trend_series))  // 逐学期的累计绩点走势
+        .route("/api/impact", get(course_impact))   // 各课程对平均绩点的影响
+        .route("/api/distribution", get(grade_distribution_data))  // 各分数段的课程数
+        .route("/api/courses/delete", post(delete_course))  // 删除课程
+        .route("/api/courses/update", post(update_course))  // 修改课程学分或成绩
+        .route("/api/courses/undo", post(undo_course_edit)) // 撤销课程编辑
+        .route("/api/courses/redo", post(redo_course_edit)) // 重做课程编辑
+        .route("/settings/benchmark", post(set_benchmark))  // 设置目标绩点
+        .route("/settings/watch", post(set_watch))  // 开启或关闭出成绩提醒
+        .route("/api/watch/status", get(watch_status))  // 后台成绩监控的状态
+        .route("/settings/exclusions", get(get_exclusions).post(edit_exclusions))  // 读取或增删排除规则
+        .route("/settings/exclusions/reset", post(reset_exclusions))  // 恢复内置的排除规则
+        .route("/settings/retake-policy", post(set_retake_policy))  // 修改重复课程的处理方式
+        .route("/settings/policy", get(list_gpa_policies).post(set_gpa_policy))  // 查看或切换计算策略
+        .route("/settings/export", get(export_settings))    // 导出设置文件
+        .route("/settings/import", post(import_settings))   // 导入设置文件
+        .route("/api/evaluation", post(calculate_comprehensive))  // 计算综测总分
+        .route("/api/what-if", post(simulate_what_if))  // 加入假设课程模拟绩点
+        .route("/plan", post(plan_target_gpa))  // 目标绩点规划
+        .route("/api/ranking", post(estimate_class_ranking))    // 根据班级平均值估算排名
+        .route("/api/changelog", get(api_changelog))   // 更新日志
+        .route("/api/v1/summary", get(api_summary).options(api_summary_preflight))    // 结果摘要, 供外部页面嵌入
+        .route("/export/{format}", get(export_file))  // 按指定格式导出结果
+        .route("/export/{format}/jobs", post(start_export_job))  // 创建后台导出任务
+        .route("/export/jobs/{id}", get(export_job_status))  // 查询导出任务状态
+        .route("/export/jobs/{id}/download", get(download_export_job))  // 下载导出任务生成的文件
+        .route("/verify", post(verify))     // 校验导出文件
+        .route("/history", get(history_page))   // 备份记录页面
+        .route("/history/snapshots/{id}", get(snapshot_page))   // 只读查看一份历史快照
+        .route("/api/history/search", get(search_history))  // 按课程名称搜索成绩历史
+        .route("/cohort", get(cohort_page))     // 班级统计页面
+        .route("/api/cohort/distribution", post(cohort_distribution))   // 统计全班绩点分布
+        .route("/api/cohort/batches", post(create_cohort_batch))    // 创建分批上传的班级统计
+        .route("/api/cohort/batches/{id}", get(cohort_batch_status).delete(delete_cohort_batch))   // 查询或删除分批统计
+        .route("/api/cohort/batches/{id}/files", post(add_cohort_file))    // 向分批统计上传一个文件
+        .route("/api/cohort/batches/{id}/export", get(export_cohort_batch)) // 导出分批统计的结果
+        .route("/api/backups/restore", post(restore_backup))    // 从备份恢复结果
         .route("/logout", post(logout))     // 退出登录
         .route("/shutdown", post(shutdown)) // 关闭服务器
         .fallback(static_file)   // 自动加载并注册 static 的资源
-        .with_state(tera)   // 将 Tera 模板引擎作为共享状态以便所有路由处理器都能访问
-}
\ No newline at end of file
+        .layer(upload_limit)
+        .with_state(state)   // 将 Tera 模板引擎与配置作为共享状态以便所有路由处理器都能访问
+}
+
+/// 启动自检未通过时使用的路由, 除关闭服务器外所有页面都显示诊断信息
+pub fn create_diagnostic_router(report: Arc<SelfCheckReport>) -> Router {
+    Router::new()
+        .route("/shutdown", post(shutdown)) // 关闭服务器
+        .fallback(diagnostic_page)
+        .with_state(report)
+}
@@ -1,49 +1,156 @@
 // 获取数据层
 use crate::{
-    business::{b64_encode, print_info, round_2decimal, score_trans_grade},
-    models::{Course, WebScrapingError}
+    business::{b64_encode, parsed_grade, print_info},
+    config::{LoginMethod, ProxyConfig, ScraperConfig},
+    models::{Course, CourseNature, GradeProbe, ParsedCourses, Score, Semester, SkippedRow, StudentProfile, WebScrapingError},
+    profile::GradingTable,
+    progress::ProgressReporter,
+    resume::ResumeStore,
+    storage::SnapshotStore
 };
 
 use crate::business::print_error;
 use anyhow::Result;
-use fake_user_agent::get_rua;
-use lazy_static::lazy_static;
-use reqwest::{cookie::Cookie, header::{HeaderMap, HeaderValue}, Client};
+use futures::future::try_join_all;
+use reqwest::{cookie::Cookie, header::{HeaderMap, HeaderValue}, Client, ClientBuilder, Proxy, RequestBuilder, Response};
 use rust_decimal::Decimal;
 use scraper::{Html, Selector};
-use std::{collections::HashMap, sync::Mutex};
+use chrono::Local;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration
+};
+use tokio::time::{sleep, sleep_until, Instant};
+
+// 教务处网站结构体
+// 登录页面表单的提交地址, 页面中出现它说明当前处于未登录状态
+const LOGIN_PAGE_MARKER: &str = "/yjlgxy_jsxsd/xk/LoginToXk";
+// 期末未完成教学评价时, 成绩页面会被替换为评教提示, 页面中会出现以下任一文字
+const EVALUATION_REQUIRED_MARKERS: &[&str] = &["教学评价未完成", "未完成教学评价", "请先完成评教", "未进行评教"];
+// 学生评教页面的路径
+const EVALUATION_PATH: &str = "/xspj/xspj_find.do";
+// 教务系统接收统一身份认证票据(ticket)的入口, 作为 CAS 的 service 参数
+const CAS_SERVICE_PATH: &str = "/sso.jsp";
+// 分学期获取成绩时, 网络中断后自动重试的次数与间隔, 已获取的学期不会重复获取
+const FETCH_RETRIES: u32 = 2;
+const FETCH_RETRY_DELAY: Duration = Duration::from_secs(3);
+// 中断的查询保存的进度在这段时间内有效, 超过后重新获取全部学期
+const CHECKPOINT_TTL: Duration = Duration::from_secs(3600);
 
-// 每次程序启动都随机加载一个 UA, 由于后续需要更改此内容, 故此处使用互斥锁
-lazy_static! {
-    pub static ref USER_AGENT: Mutex<String> = Mutex::new(get_rua().to_string());
+// 成绩查询的类别, 双学位学生的辅修成绩需要单独查询
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GradeTrack {
+    Major,  // 主修
+    Minor,  // 辅修, 查询时附带 fxkc=1
+}
+
+// 成绩数据来源, 教务系统与演示模式的模拟数据都实现此接口
+pub trait GradeSource {
+    /// 登录, 包括获取初始 Cookie 等准备工作
+    async fn sign_in(&mut self, account: &str, password: &str) -> Result<(), WebScrapingError>;
+    /// 获取指定学期的成绩, 学期列表为空时获取全部学期
+    async fn fetch_grades(&mut self, semesters: &[String]) -> Result<Vec<Course>, WebScrapingError>;
+    /// 获取辅修(第二学位)课程的成绩, 没有辅修时返回空列表
+    async fn fetch_minor_grades(&mut self, semesters: &[String]) -> Result<Vec<Course>, WebScrapingError>;
+    /// 获取官方给出的平均学分绩点, 在获取成绩之后调用, 只查询部分学期时返回 None
+    async fn fetch_official_gpa(&self) -> Result<Option<Decimal>, WebScrapingError>;
+    /// 获取学生的姓名、学号等基本信息, 页面上找不到时返回 None
+    async fn fetch_student_profile(&self) -> Result<Option<StudentProfile>, WebScrapingError>;
+    /// 获取成绩查询页面中可选的学期, 最新的在前
+    async fn fetch_semesters(&self) -> Result<Vec<String>, WebScrapingError>;
+    /// 试运行: 只读取成绩列表第一页的概况, 用于确认网络与账号可用
+    async fn probe_grades(&self) -> Result<GradeProbe, WebScrapingError>;
+    /// 本次查询中学分或成绩无法识别而跳过的行
+    fn skipped_rows(&self) -> Vec<SkippedRow> {
+        Vec::new()
+    }
 }
 
-// 教务处网站结构体
 pub struct AAOWebsite {
     client: Client, // HTTP 客户端, 相当于隔壁 Python 的 requests.Session()
     base_url: String,    // HOST
-    headers: HeaderMap  // 动态管理请求头
+    headers: HeaderMap,  // 动态管理请求头
+    credentials: Option<(String, String)>,  // 登录成功后保存账号密码, 会话过期时用于自动重新登录
+    sequential_fetch: bool, // 逐个请求并立即解析, 解析完释放网页后才发出下一个请求, 用于低内存模式
+    progress: Option<ProgressReporter>, // 向前端推送查询进度, 没有前端监听时为空
+    debug_html_dir: Option<PathBuf>,    // 解析不到成绩时保存网页的目录
+    debug_dumps: Mutex<Vec<PathBuf>>,   // 本次查询已保存的调试网页, 各学期并发获取, 所以使用互斥锁
+    skipped: Mutex<Vec<SkippedRow>>,    // 本次查询中无法解析而跳过的行, 同样并发写入
+    official_gpa: Mutex<Option<Decimal>>,   // 全部学期成绩列表第一页顶部的官方绩点
+    limiter: RateLimiter,   // 控制请求频率, 避免触发学校服务器的防火墙
+    login_method: LoginMethod,  // 直接登录或通过统一身份认证登录
+    grading: GradingTable,  // 成绩换算绩点使用的换算表
+    resume: Option<Arc<ResumeStore>>,   // 分学期获取成绩时保存进度, 为空则不保存
+}
+
+// 分学期获取成绩的进度: 已获取的学期及其课程
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FetchCheckpoint {
+    completed: BTreeMap<String, Vec<Course>>,
+}
+
+// 统一身份认证登录页面中的表单
+struct CasForm {
+    action: String,     // 表单提交地址, 可能是相对地址
+    hidden_fields: Vec<(String, String)>,   // lt、execution 等隐藏字段, 需原样提交
+}
+
+// 请求限速器: 相邻两次请求之间至少间隔 min_interval, 再加上随机抖动
+// 并发获取多个学期时, 各请求依次预约发送时间, 所以不会同时到达服务器
+struct RateLimiter {
+    min_interval: Duration,
+    jitter: Duration,
+    next_slot: Mutex<Instant>,  // 下一个请求最早可以发送的时间
+}
+
+impl RateLimiter {
+    fn new(min_interval: Duration, jitter: Duration) -> Self {
+        Self { min_interval, jitter, next_slot: Mutex::new(Instant::now()) }
+    }
+
+    /// 等待到可以发送请求的时间
+    async fn wait(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let slot = (*next_slot).max(Instant::now());
+
+            let jitter_ms = rand::rng().random_range(0..=self.jitter.as_millis() as u64);
+            *next_slot = slot + self.min_interval + Duration::from_millis(jitter_ms);
+            slot
+        };
+
+        sleep_until(slot).await;
+    }
 }
 
 // 实现结构体功能
 impl AAOWebsite {
     // 创建爬虫实例
-    pub fn new() -> Result<Self> {
+    // UA 按配置在创建时选择并固定在客户端中, 每个实例各自持有, 互不影响
+    pub fn new(config: &ScraperConfig, grading: &GradingTable) -> Result<Self> {
         #[cfg(debug_assertions)]
         print_info("正在初始化客户端实例");
 
+        let user_agent = config.user_agent.pick();
+
         // 创建客户端实例, `?`表示失败就返回错误, 类似隔壁的 raise
         // 需要启动 cookie 储存
         let client = {
-            let user_agent_guard = USER_AGENT.lock().unwrap();
-
             #[cfg(debug_assertions)]
-            print_info(&format!("UA 已被设置为: {}", user_agent_guard.clone()));
+            print_info(&format!("UA 已被设置为: {}", user_agent));
 
-            Client::builder()
-                .user_agent(user_agent_guard.clone())    // 设置 UA
+            let builder = Client::builder()
+                .user_agent(user_agent.as_str())    // 设置 UA
                 .cookie_store(true) // 自动处理 Cookie
-                .build()?
+                .connect_timeout(config.connect_timeout)    // 连接超时, 避免学校服务器无响应时一直卡住
+                .timeout(config.request_timeout);   // 整个请求的超时
+
+            apply_proxy(builder, &config.proxy)?.build()?
         };
 
         // cfg(debug_assertions) 表示下方紧贴着的内容只在 dev 模式下出现
@@ -72,20 +179,50 @@ impl AAOWebsite {
         Ok(Self {
             client,
             base_url: "http://yitjw.yinghuaonline.com/yjlgxy_jsxsd".to_string(),
-            headers: init_headers
+            headers: init_headers,
+            credentials: None,
+            sequential_fetch: config.sequential_fetch,
+            progress: None,
+            debug_html_dir: config.debug_html_dir.clone(),
+            debug_dumps: Mutex::new(Vec::new()),
+            skipped: Mutex::new(Vec::new()),
+            official_gpa: Mutex::new(None),
+            limiter: RateLimiter::new(config.min_request_interval, config.request_jitter),
+            login_method: config.login.clone(),
+            grading: grading.clone(),
+            resume: None,
         })
     }
 
+    // 设置进度通知
+    pub fn set_progress(&mut self, progress: Option<ProgressReporter>) {
+        self.progress = progress;
+    }
+
+    // 设置断点续传使用的进度存储
+    pub fn set_resume(&mut self, resume: Option<Arc<ResumeStore>>) {
+        self.resume = resume;
+    }
+
+    // 通知前端进入新阶段
+    fn report(&self, stage: &str, percent: u8) {
+        if let Some(progress) = &self.progress {
+            progress.report(stage, percent);
+        }
+    }
+
     // [异步]初始化会话, 获取 cookie
     // self 前面要加 mut 因为需要更新请求头 headers
     pub async fn init(&mut self) -> Result<(), WebScrapingError> {
+        self.report("初始化会话", 10);
+
         #[cfg(debug_assertions)]
         print_info(&format!("尝试访问：{}", self.base_url));
 
         // await 表示等待请求完成, 出错会转换成自定义错误类型
-        let response = self.client.get(&self.base_url)
-            .headers(self.headers.clone())  // 设置请求头
-            .send().await.map_err(|e| WebScrapingError::HttpRequest(e.to_string()))?;
+        let response = self.send(
+            self.client.get(&self.base_url).headers(self.headers.clone())  // 设置请求头
+        ).await?;
 
         let status_code = response.status();
 
@@ -122,6 +259,8 @@ impl AAOWebsite {
     // username 和 password 本来就是切片引用(&str), 所以它们已经是借用的形式, 所有权不会被消耗和移除
     // 它们的生命周期会随着其真正的拥有者(owner)被清理而移除, 在这之前它们一直存在
     pub async fn login(&mut self, username: &str, password: &str) -> Result<(), WebScrapingError> {
+        self.report("登录中", 25);
+
         #[cfg(debug_assertions)]
         print_info(&format!("用户输入了登录信息[账：{}，密：{}]，将对其进行编码", username, password));
 
@@ -138,10 +277,9 @@ impl AAOWebsite {
         print_info(&format!("现在开始提交表单数据并尝试登录，目标 URL 为 {}", login_url));
 
         let form_data = [("encoded", &encoded)];
-        let response = self.client.post(&login_url)
-            .headers(self.headers.clone())
-            .form(&form_data)
-            .send().await.map_err(|e| WebScrapingError::HttpRequest(e.to_string()))?;
+        let response = self.send(
+            self.client.post(&login_url).headers(self.headers.clone()).form(&form_data)
+        ).await?;
 
         let status_code = response.status();
 
@@ -155,18 +293,86 @@ impl AAOWebsite {
         // 该操作不会导致所有权转移(moved)
         let final_url_option = response.url().to_string();
 
-        let response_text = response.text().await.map_err(|e| WebScrapingError::HttpRequest(e.to_string()))?;
-        let login_failure_indicator = "/yjlgxy_jsxsd/xk/LoginToXk";
-        if response_text.contains(login_failure_indicator) {
+        let response_text = response.text().await.map_err(http_error)?;
+        if response_text.contains(LOGIN_PAGE_MARKER) {
             return Err(WebScrapingError::LoginFailed)
         }
 
         #[cfg(debug_assertions)]
         print_info(&format!("登录成功！ HTTP Code {}", status_code));
 
+        self.logged_in(username, password, &final_url_option)
+    }
+
+    // [异步]通过统一身份认证(CAS)登录
+    // 先从认证服务器取得登录表单, 提交账号密码后, 认证服务器签发票据并重定向回教务系统,
+    // 教务系统验证票据后建立会话; 重定向与 Cookie 都由 reqwest 自动处理
+    pub async fn login_cas(&mut self, cas_url: &str, username: &str, password: &str) -> Result<(), WebScrapingError> {
+        self.report("统一身份认证登录中", 25);
+
+        let service = format!("{}{}", self.base_url, CAS_SERVICE_PATH);
+
+        #[cfg(debug_assertions)]
+        print_info(&format!("开始访问统一身份认证页面：{}，service：{}", cas_url, service));
+
+        let response = self.send(self.client.get(cas_url).query(&[("service", service.as_str())])).await?;
+        if !response.status().is_success() {
+            return Err(WebScrapingError::HttpRequest(format!("无法访问统一身份认证页面 {}：{}", cas_url, response.status())))
+        }
+
+        let page_url = response.url().clone();
+        let html = response.text().await.map_err(http_error)?;
+
+        // 部分认证平台在浏览器中加密密码后再提交, 这里无法模拟
+        if html.contains("pwdEncryptSalt") {
+            return Err(WebScrapingError::ParseError(
+                "统一身份认证页面要求在浏览器中加密密码, 暂不支持, 请去掉 --cas-url 参数直接登录教务系统".to_string()
+            ));
+        }
+
+        let form = parse_cas_form(&html)?;
+        let action = page_url.join(&form.action).map_err(|e| WebScrapingError::ParseError(e.to_string()))?;
+
+        let mut fields = form.hidden_fields;
+        if !fields.iter().any(|(name, _)| name == "_eventId") {
+            fields.push(("_eventId".to_string(), "submit".to_string()));
+        }
+        fields.push(("username".to_string(), username.to_string()));
+        fields.push(("password".to_string(), password.to_string()));
+
+        #[cfg(debug_assertions)]
+        print_info(&format!("提交统一身份认证表单：{}", action));
+
+        let response = self.send(self.client.post(action).form(&fields)).await?;
+        if !response.status().is_success() {
+            return Err(WebScrapingError::HttpRequest(format!("统一身份认证失败：{}", response.status())))
+        }
+
+        // 账号或密码错误时认证服务器不会重定向, 仍停留在认证页面
+        let final_url = response.url().clone();
+        if final_url.host_str() == page_url.host_str() {
+            return Err(WebScrapingError::LoginFailed)
+        }
+
+        let response_text = response.text().await.map_err(http_error)?;
+        if response_text.contains(LOGIN_PAGE_MARKER) {
+            return Err(WebScrapingError::LoginFailed)
+        }
+
+        #[cfg(debug_assertions)]
+        print_info(&format!("统一身份认证登录成功, 已跳转到 {}", final_url));
+
+        self.logged_in(username, password, final_url.as_str())
+    }
+
+    // 登录成功后保存账号密码并更新请求头
+    fn logged_in(&mut self, username: &str, password: &str, referer: &str) -> Result<(), WebScrapingError> {
+        // 仅保存在当前实例中, 实例销毁后即释放
+        self.credentials = Some((username.to_string(), password.to_string()));
+
         self.headers.insert(
             "Referer",
-            HeaderValue::from_str(&final_url_option).map_err(|e| WebScrapingError::ParseError(e.to_string()))?
+            HeaderValue::from_str(referer).map_err(|e| WebScrapingError::ParseError(e.to_string()))?
         );
 
         // 添加 x-requested-with 头
@@ -182,18 +388,388 @@ impl AAOWebsite {
     }
 
     // 获取成绩数据, 这里不再需要更新 headers 的状态了, 所以不用 mut
-    pub async fn get_grades(&self) -> Result<Vec<Course>, WebScrapingError> {
+    // semesters 为空时一次性查询全部学期, 否则按学期并发请求
+    // 登录后教务系统会话可能过期, 此时自动重新登录一次再重试, 所以需要 mut
+    // 期末未完成教学评价时成绩页面被评教提示替换, 返回 EvaluationRequired 而不是空列表
+    pub async fn get_grades(&mut self, semesters: &[String]) -> Result<Vec<Course>, WebScrapingError> {
         #[cfg(not(debug_assertions))]
         print_info("尝试获取成绩数据...");
 
+        self.report("获取成绩", 45);
+
+        let course_list = self.fetch_track_grades(semesters, GradeTrack::Major).await?;
+
+        // 一门课程都没有解析到, 且保存了调试网页时, 把文件位置告诉用户以便反馈问题
+        let dumps = self.debug_dumps.lock().unwrap().clone();
+        if course_list.is_empty() && !dumps.is_empty() {
+            let paths: Vec<String> = dumps.iter().map(|p| p.display().to_string()).collect();
+            return Err(WebScrapingError::ParseError(format!(
+                "未找到成绩数据, 成绩页面可能已改版。网页内容已保存到 {}, 反馈问题时请附上该文件", paths.join(", ")
+            )));
+        }
+
+        #[cfg(not(debug_assertions))]
+        print_info("成功获取成绩数据");
+
+        // 返回课程数据列表
+        Ok(course_list)
+    }
+
+    // 获取辅修课程的成绩, 查询方式与主修相同, 只是多了辅修标记
+    // 没有辅修的学生得到的是空列表, 不视为解析失败
+    pub async fn get_minor_grades(&mut self, semesters: &[String]) -> Result<Vec<Course>, WebScrapingError> {
+        self.report("获取辅修成绩", 75);
+
+        let mut course_list = self.fetch_track_grades(semesters, GradeTrack::Minor).await?;
+        course_list.iter_mut().for_each(|course| course.minor = true);
+
+        #[cfg(debug_assertions)]
+        print_info(&format!("辅修课程共{}门", course_list.len()));
+
+        Ok(course_list)
+    }
+
+    // 获取指定类别的成绩, 会话过期时自动重新登录一次再重试
+    // 网络中断时(包括重新登录之后)等待片刻后重试, 分学期获取时只重新获取尚未完成的学期
+    async fn fetch_track_grades(&mut self, semesters: &[String], track: GradeTrack) -> Result<Vec<Course>, WebScrapingError> {
+        // 之前获取的其他类别成绩记下的跳过行与调试网页不受重试影响
+        let skipped_len = self.skipped.lock().unwrap().len();
+        let dumps_len = self.debug_dumps.lock().unwrap().len();
+
+        let mut attempt = 0;
+        let mut relogged_in = false;
+        loop {
+            match self.fetch_all_grades(semesters, track).await {
+                Err(WebScrapingError::SessionExpired) if !relogged_in => {
+                    print_error("教务系统会话已过期，正在尝试重新登录...");
+                    self.relogin().await?;
+                    relogged_in = true;
+                    self.discard_attempt(track, skipped_len, dumps_len);
+                }
+                Err(e @ (WebScrapingError::Timeout | WebScrapingError::HttpRequest(_))) if attempt < FETCH_RETRIES => {
+                    attempt += 1;
+                    self.discard_attempt(track, skipped_len, dumps_len);
+                    print_error(&format!("获取成绩时网络中断: {}, 将从中断处重试(第{}次)", e, attempt));
+                    sleep(FETCH_RETRY_DELAY * attempt).await;
+                }
+                result => return result
+            }
+        }
+    }
+
+    // 重试前丢弃上一次尝试记下的跳过行与调试网页, 重新获取的页面会再记一次, 不会重复提示
+    // 已保存进度的学期重试时不再获取, 保留它们的记录
+    fn discard_attempt(&self, track: GradeTrack, skipped_len: usize, dumps_len: usize) {
+        let completed = self.checkpoint_name(track).map(|name| self.load_checkpoint(&name)).unwrap_or_default();
+
+        let mut skipped = self.skipped.lock().unwrap();
+        let kept: Vec<SkippedRow> = skipped
+            .drain(skipped_len..)
+            .filter(|row| completed.keys().any(|semester| row.location.starts_with(&format!("{}第", page_source(track, semester)))))
+            .collect();
+        skipped.extend(kept);
+
+        let mut dumps = self.debug_dumps.lock().unwrap();
+        let kept: Vec<PathBuf> = dumps
+            .drain(dumps_len..)
+            .filter(|path| {
+                let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                completed.keys().any(|semester| name.contains(&format!("-{}-p", semester)))
+            })
+            .collect();
+        dumps.extend(kept);
+    }
+
+    // 教务系统自己计算的平均学分绩点, 用于和本工具的结果对照
+    // 该数据显示在全部学期成绩列表的顶部, 查询成绩时顺带读取, 不再单独请求
+    // 只查询部分学期时页面上的是这些学期的绩点, 无法与全部课程的结果对照, 返回 None
+    pub fn official_gpa(&self) -> Option<Decimal> {
+        let official_gpa = *self.official_gpa.lock().unwrap();
+
+        #[cfg(debug_assertions)]
+        print_info(&format!("官方平均学分绩点：{:?}", official_gpa));
+
+        official_gpa
+    }
+
+    // 获取成绩查询页面"开课时间"下拉框中的学期
+    pub async fn get_semesters(&self) -> Result<Vec<String>, WebScrapingError> {
+        let query_url = format!("{}/kscj/cjcx_query", self.base_url);
+
+        #[cfg(debug_assertions)]
+        print_info(&format!("开始获取学期列表：{}", query_url));
+
+        let response = self.send(self.client.get(&query_url)).await?;
+
+        if !response.status().is_success() {
+            return Err(WebScrapingError::HttpRequest(format!("无法访问{}：{}", query_url, response.status())))
+        }
+
+        let html = response.text().await.map_err(http_error)?;
+        if html.contains(LOGIN_PAGE_MARKER) {
+            return Err(WebScrapingError::SessionExpired);
+        }
+
+        parse_semester_options(&html)
+    }
+
+    // 试运行, 只取成绩列表的第一页, 统计页数、课程数与学期
+    pub async fn get_grade_probe(&self) -> Result<GradeProbe, WebScrapingError> {
+        let html = self.fetch_grade_html("", 1, GradeTrack::Major).await?;
+        let courses = parse_grades(&html, &self.grading)?.courses;
+
+        let mut semesters: Vec<String> = courses.iter().map(|c| c.semester.clone()).filter(|s| !s.is_empty()).collect();
+        semesters.sort_by(|a, b| Semester::order_key(a).cmp(&Semester::order_key(b)));
+        semesters.dedup();
+
+        Ok(GradeProbe { page_count: parse_page_count(&html), first_page_rows: courses.len(), semesters })
+    }
+
+    // 获取学生基本信息, 登录后的教务系统首页顶部显示姓名、学号、专业与班级
+    pub async fn get_student_profile(&self) -> Result<Option<StudentProfile>, WebScrapingError> {
+        self.report("获取学生信息", 90);
+
+        let home_url = format!("{}/framework/xsMain.jsp", self.base_url);
+
+        #[cfg(debug_assertions)]
+        print_info(&format!("开始获取学生信息：{}", home_url));
+
+        let response = self.send(self.client.get(&home_url)).await?;
+
+        if !response.status().is_success() {
+            return Err(WebScrapingError::HttpRequest(format!("无法访问{}：{}", home_url, response.status())))
+        }
+
+        let html = response.text().await.map_err(http_error)?;
+        if html.contains(LOGIN_PAGE_MARKER) {
+            return Err(WebScrapingError::SessionExpired);
+        }
+
+        let profile = parse_student_profile(&html);
+
+        #[cfg(debug_assertions)]
+        print_info(&format!("学生信息：{:?}", profile));
+
+        Ok(profile)
+    }
+
+    // 按配置的登录方式登录
+    async fn authenticate(&mut self, username: &str, password: &str) -> Result<(), WebScrapingError> {
+        match self.login_method.clone() {
+            LoginMethod::Direct => {
+                self.init().await?;
+                self.login(username, password).await
+            }
+            LoginMethod::Cas(cas_url) => self.login_cas(&cas_url, username, password).await
+        }
+    }
+
+    // 使用保存的账号密码重新初始化会话并登录
+    async fn relogin(&mut self) -> Result<(), WebScrapingError> {
+        let (username, password) = self.credentials.clone().ok_or(WebScrapingError::SessionExpired)?;
+
+        self.authenticate(&username, &password).await.map_err(|e| match e {
+            WebScrapingError::LoginFailed => WebScrapingError::SessionExpired,
+            other => other
+        })?;
+
+        print_info("重新登录成功");
+
+        Ok(())
+    }
+
+    // 获取所有指定学期的成绩, 重修、补考的多条成绩原样返回, 计算时按学校规则去重
+    async fn fetch_all_grades(&self, semesters: &[String], track: GradeTrack) -> Result<Vec<Course>, WebScrapingError> {
+        // 查询全部学期时只有一个列表, 各页是否逐个获取由 fetch_grade_page 决定
+        let pages: Vec<Vec<Course>> = if semesters.is_empty() {
+            vec![self.fetch_grade_page("", track).await?]
+        } else {
+            let checkpoint = self.checkpoint_name(track);
+            let completed = checkpoint.as_deref().map(|name| self.load_checkpoint(name)).unwrap_or_default();
+            if !completed.is_empty() {
+                print_info(&format!("上次查询中断前已获取{}个学期的成绩, 将从中断处继续", completed.len()));
+            }
+
+            let pages = if self.sequential_fetch {
+                let mut pages = Vec::with_capacity(semesters.len());
+                for semester in semesters {
+                    pages.push(self.fetch_semester(semester, track, &completed, checkpoint.as_deref()).await?);
+                }
+                pages
+            } else {
+                #[cfg(debug_assertions)]
+                print_info(&format!("将并发获取以下学期的成绩：{:?}", semesters));
+
+                // 所有请求同时发出, 任意一个失败则整体失败, 已成功的学期会保存下来
+                try_join_all(semesters.iter().map(|s| self.fetch_semester(s, track, &completed, checkpoint.as_deref()))).await?
+            };
+
+            // 全部学期获取完成, 不再需要保存的进度
+            if let (Some(resume), Some(name)) = (&self.resume, &checkpoint) {
+                resume.remove(name);
+            }
+            pages
+        };
+
+        let courses: Vec<Course> = pages.into_iter().flatten().collect();
+
+        #[cfg(debug_assertions)]
+        print_info(&format!("成绩数据收集完成，如下：\n{:?}", courses));
+
+        Ok(courses)
+    }
+
+    // 获取单个学期的成绩, 中断前已获取的学期直接使用保存的结果, 新获取的学期立即保存
+    async fn fetch_semester(
+        &self, semester: &str, track: GradeTrack, completed: &BTreeMap<String, Vec<Course>>, checkpoint: Option<&str>
+    ) -> Result<Vec<Course>, WebScrapingError> {
+        if let Some(courses) = completed.get(semester) {
+            return Ok(courses.clone());
+        }
+
+        let courses = self.fetch_grade_page(semester, track).await?;
+
+        if let (Some(resume), Some(name)) = (&self.resume, checkpoint) {
+            let saved = resume.update(name, CHECKPOINT_TTL, |progress: &mut FetchCheckpoint| {
+                progress.completed.insert(semester.to_string(), courses.clone());
+            });
+            if let Err(e) = saved {
+                print_error(&format!("保存查询进度失败: {}", e));
+            }
+        }
+
+        Ok(courses)
+    }
+
+    // 当前账号与成绩类别对应的进度名, 未登录或未设置进度存储时为空
+    fn checkpoint_name(&self, track: GradeTrack) -> Option<String> {
+        self.resume.as_ref()?;
+        let (username, _) = self.credentials.as_ref()?;
+        let track = match track {
+            GradeTrack::Major => "major",
+            GradeTrack::Minor => "minor"
+        };
+
+        Some(format!("fetch-{}-{}", SnapshotStore::account_key(username), track))
+    }
+
+    // 读取中断前已获取的学期
+    fn load_checkpoint(&self, name: &str) -> BTreeMap<String, Vec<Course>> {
+        self.resume
+            .as_ref()
+            .and_then(|resume| resume.load::<FetchCheckpoint>(name, CHECKPOINT_TTL))
+            .map(|progress| progress.completed)
+            .unwrap_or_default()
+    }
+
+    // 获取单个学期(空字符串表示全部学期)的成绩并解析
+    // 成绩较多(如重修较多)时列表会分页, 需要把每一页都取回来合并
+    async fn fetch_grade_page(&self, semester: &str, track: GradeTrack) -> Result<Vec<Course>, WebScrapingError> {
+        let first_page = self.fetch_grade_html(semester, 1, track).await?;
+        let page_count = parse_page_count(&first_page);
+
+        if track == GradeTrack::Major {
+            self.report("解析成绩…", 65);
+            if semester.is_empty() {
+                *self.official_gpa.lock().unwrap() = parse_official_gpa(&first_page);
+            }
+        }
+        let mut courses = self.parse_page(&first_page, semester, 1, track)?;
+
+        drop(first_page);
+
+        if page_count > 1 && self.sequential_fetch {
+            // 每取回一页就立即解析并释放网页内容
+            for page in 2..=page_count {
+                let html = self.fetch_grade_html(semester, page, track).await?;
+                courses.extend(self.parse_page(&html, semester, page, track)?);
+            }
+        } else if page_count > 1 {
+            #[cfg(debug_assertions)]
+            print_info(&format!("学期 {} 的成绩共有 {} 页，将获取剩余页面", semester, page_count));
+
+            let rest_pages = try_join_all((2..=page_count).map(|page| self.fetch_grade_html(semester, page, track))).await?;
+            for (page, html) in (2..).zip(rest_pages) {
+                courses.extend(self.parse_page(&html, semester, page, track)?);
+            }
+        }
+
+        Ok(courses)
+    }
+
+    // 解析成绩页面, 解析失败或一门课程都没有时按需保存网页用于排查
+    // 跳过的行记下所在学期与页码, 查询结束后一并提示用户
+    fn parse_page(&self, html: &str, semester: &str, page: usize, track: GradeTrack) -> Result<Vec<Course>, WebScrapingError> {
+        let result = parse_grades(html, &self.grading);
+
+        let suspicious = match &result {
+            Ok(parsed) => parsed.courses.is_empty(),
+            Err(_) => true
+        };
+        if suspicious && let Some(dir) = &self.debug_html_dir {
+            match self.dump_html(dir, html, semester, page) {
+                Ok(path) => {
+                    print_error(&format!("未能从成绩页面解析到课程, 网页已保存到 {}", path.display()));
+                    if let Err(WebScrapingError::ParseError(msg)) = result {
+                        return Err(WebScrapingError::ParseError(format!("{}。网页内容已保存到 {}", msg, path.display())));
+                    }
+                    self.debug_dumps.lock().unwrap().push(path);
+                }
+                Err(e) => print_error(&format!("保存调试网页失败: {}", e))
+            }
+        }
+
+        let ParsedCourses { courses, mut skipped, .. } = result?;
+        if !skipped.is_empty() {
+            let location = format!("{}第{}页", page_source(track, semester), page);
+            skipped.iter_mut().for_each(|row| row.location = location.clone());
+            self.skipped.lock().unwrap().extend(skipped);
+        }
+
+        Ok(courses)
+    }
+
+    // 把网页去掉账号密码后保存到调试目录, 返回文件路径
+    fn dump_html(&self, dir: &Path, html: &str, semester: &str, page: usize) -> std::io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+
+        let semester = if semester.is_empty() { "all" } else { semester };
+        let file_name = format!("grades-{}-{}-p{}.html", Local::now().format("%Y%m%d-%H%M%S"), semester, page);
+        let path = dir.join(file_name);
+
+        let secrets: Vec<String> = match &self.credentials {
+            Some((username, password)) => vec![
+                username.clone(), password.clone(), b64_encode(username), b64_encode(password)
+            ],
+            None => Vec::new()
+        };
+        fs::write(&path, scrub_secrets(html, &secrets))?;
+
+        Ok(path)
+    }
+
+    // 经过限速器发送请求
+    async fn send(&self, request: RequestBuilder) -> Result<Response, WebScrapingError> {
+        self.limiter.wait().await;
+        request.send().await.map_err(http_error)
+    }
+
+    // 获取成绩列表的某一页 HTML, 页码从 1 开始
+    async fn fetch_grade_html(&self, semester: &str, page: usize, track: GradeTrack) -> Result<String, WebScrapingError> {
         // 获取成绩页面
         let grades_url = format!("{}/kscj/cjcx_list", self.base_url);
 
         #[cfg(debug_assertions)]
-        print_info(&format!("开始访问成绩页面：{}", grades_url));
+        print_info(&format!("开始访问成绩页面：{}，学期：{}，第 {} 页", grades_url, semester, page));
 
-        let form_data = [("kksj", ""), ("kcxz", ""), ("kcmc", ""), ("xsfs", "all")];
-        let response = self.client.post(&grades_url).form(&form_data).send().await.map_err(|e| WebScrapingError::HttpRequest(e.to_string()))?;
+        let page_index = page.to_string();
+        let mut form_data = vec![
+            ("kksj", semester), ("kcxz", ""), ("kcmc", ""), ("xsfs", "all"), ("pageIndex", page_index.as_str())
+        ];
+        if track == GradeTrack::Minor {
+            form_data.push(("fxkc", "1"));
+        }
+        let response = self.send(self.client.post(&grades_url).form(&form_data)).await?;
 
         let status_code = response.status();
 
@@ -204,81 +780,419 @@ impl AAOWebsite {
         #[cfg(debug_assertions)]
         print_info(&format!("访问成功！ HTTP Code {}。将获取并解析网页数据", status_code));
 
-        // 获取响应文本并解析
-        let html_content = response.text().await.map_err(|e| WebScrapingError::HttpRequest(e.to_string()))?;
-        let document = Html::parse_document(&html_content);
+        // 获取响应文本
+        let html = response.text().await.map_err(http_error)?;
 
-        // 解析 HTML 课程表格数据
-        // 创建选择器, 类似隔壁 Beautiful Soup
-        let tr_selector = Selector::parse("tr").map_err(|e| WebScrapingError::ParseError(e.to_string()))?;
-        let td_selector = Selector::parse("td").map_err(|e| WebScrapingError::ParseError(e.to_string()))?;
+        // 会话过期时教务系统会返回登录页面, 而不是成绩列表
+        if html.contains(LOGIN_PAGE_MARKER) {
+            return Err(WebScrapingError::SessionExpired);
+        }
 
-        #[cfg(debug_assertions)]
-        print_info("解析完成，将收集成绩数据");
+        // 未完成评教时成绩页面只有提示文字, 直接按空成绩解析会让用户误以为没有成绩
+        if EVALUATION_REQUIRED_MARKERS.iter().any(|marker| html.contains(marker)) {
+            return Err(WebScrapingError::EvaluationRequired(format!("{}{}", self.base_url, EVALUATION_PATH)));
+        }
 
-        // 创建[可变]哈希表, 只有 let 后面带 mut 关键字, 变量内容才可被改变, 或者说被重新赋值
-        // 但作为静态强类型语言, 不论内容如何改变, 数据类型都不可变
-        let mut courses_record: HashMap<String, Course> = HashMap::new();
+        Ok(html)
+    }
+}
 
-        // 遍历所有数据行, 跳过表头行, 所以用 skip(1)
-        for tr in document.select(&tr_selector).skip(1) {
-            // 获取当前行的所有单元格, 过滤掉不完整的行
-            let tds: Vec<_> = tr.select(&td_selector).collect();
-            if tds.len() < 12 { continue }
+impl GradeSource for AAOWebsite {
+    async fn sign_in(&mut self, account: &str, password: &str) -> Result<(), WebScrapingError> {
+        self.authenticate(account, password).await
+    }
 
-            // 提取课程名称(在第4个单元格)
-            let name = tds[3].text().collect::<String>().trim().to_string();
+    async fn fetch_grades(&mut self, semesters: &[String]) -> Result<Vec<Course>, WebScrapingError> {
+        self.get_grades(semesters).await
+    }
 
-            // 提取总分(在第5个单元格)
-            let score_text = tds[4].text().collect::<String>().trim().to_string();
+    async fn fetch_minor_grades(&mut self, semesters: &[String]) -> Result<Vec<Course>, WebScrapingError> {
+        self.get_minor_grades(semesters).await
+    }
 
-            // 提取课程性质(在第12个单元格)
-            let nature = tds[11].text().collect::<String>().trim().to_string();
+    async fn fetch_official_gpa(&self) -> Result<Option<Decimal>, WebScrapingError> {
+        Ok(self.official_gpa())
+    }
 
-            // 提取学分并且转换为 Decimal 类型
-            let credit_text = tds[6].text().collect::<String>().trim().to_string();
-            let credit = match credit_text.parse::<Decimal>() {
-                Ok(c) => c,
-                Err(_) => continue
-            };
+    async fn fetch_student_profile(&self) -> Result<Option<StudentProfile>, WebScrapingError> {
+        self.get_student_profile().await
+    }
 
-            // 转换绩点, 无效绩点则跳过
-            let grade_point = match score_trans_grade(&score_text) {
-                Some(g) => g,
-                None => continue
-            };
+    async fn fetch_semesters(&self) -> Result<Vec<String>, WebScrapingError> {
+        self.get_semesters().await
+    }
 
-            // 计算加权绩点并保留后2位小数
-            let credit_gpa = round_2decimal(grade_point * credit);
-
-            // 哈希表去重: 课程存在多个, 则取较高绩点者; 否则直接插入表
-            let course = Course {
-                name: name.clone(),
-                nature,
-                score: score_text,
-                credit,
-                grade: grade_point,
-                credit_gpa
-            };
-            if let Some(existing) = courses_record.get_mut(&name) {
-                if course.grade > existing.grade {
-                    *existing = course.clone();
-                }
+    async fn probe_grades(&self) -> Result<GradeProbe, WebScrapingError> {
+        self.get_grade_probe().await
+    }
+
+    // 会话过期或网络中断重试时同一页面会再解析一次, 按位置去重
+    fn skipped_rows(&self) -> Vec<SkippedRow> {
+        let mut rows = self.skipped.lock().unwrap().clone();
+        rows.sort_by(|a, b| a.location.cmp(&b.location).then(a.row.cmp(&b.row)));
+        rows.dedup_by(|a, b| a.location == b.location && a.row == b.row);
+        rows
+    }
+}
+
+/// 跳过的行所在的成绩列表, 如"辅修2023-2024-1", 后面再加上页码
+fn page_source(track: GradeTrack, semester: &str) -> String {
+    let track = if track == GradeTrack::Minor { "辅修" } else { "" };
+    let semester = if semester.is_empty() { "全部学期" } else { semester };
+    format!("{}{}", track, semester)
+}
+
+/// 把网页中出现的账号、密码等敏感内容替换为星号
+fn scrub_secrets(html: &str, secrets: &[String]) -> String {
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(html.to_string(), |text, secret| text.replace(secret.as_str(), "******"))
+}
+
+/// 从页面文本中解析教务系统给出的平均学分绩点, 形如"平均学分绩点：3.45"
+fn parse_official_gpa(html_content: &str) -> Option<Decimal> {
+    let text: String = Html::parse_document(html_content).root_element().text().collect();
+    let label = "平均学分绩点";
+
+    let start = text.find(label)? + label.len();
+    let rest = text[start..].trim_start_matches(|c: char| c == ':' || c == '：' || c.is_whitespace());
+    let number: String = rest.chars().take_while(|c| c.is_ascii_digit() || *c == '.').collect();
+
+    number.parse::<Decimal>().ok()
+}
+
+/// 解析统一身份认证登录页面中的表单, 优先选择带有 execution 或 lt 字段的表单
+fn parse_cas_form(html_content: &str) -> Result<CasForm, WebScrapingError> {
+    let document = Html::parse_document(html_content);
+    let form_selector = Selector::parse("form").map_err(|e| WebScrapingError::ParseError(e.to_string()))?;
+    let hidden_selector = Selector::parse(r#"input[type="hidden"]"#).map_err(|e| WebScrapingError::ParseError(e.to_string()))?;
+
+    let forms: Vec<CasForm> = document
+        .select(&form_selector)
+        .map(|form| CasForm {
+            action: form.value().attr("action").unwrap_or_default().to_string(),
+            hidden_fields: form
+                .select(&hidden_selector)
+                .filter_map(|input| {
+                    let name = input.value().attr("name")?;
+                    Some((name.to_string(), input.value().attr("value").unwrap_or_default().to_string()))
+                })
+                .collect(),
+        })
+        .collect();
+
+    let is_cas_form = |form: &CasForm| form.hidden_fields.iter().any(|(name, _)| name == "execution" || name == "lt");
+    match forms.iter().position(is_cas_form) {
+        Some(index) => Ok(forms.into_iter().nth(index).unwrap()),
+        None => Err(WebScrapingError::ParseError("未在统一身份认证页面中找到登录表单".to_string()))
+    }
+}
+
+/// 解析"开课时间"下拉框(name="kksj")中的学期, 跳过"全部"之类值为空的选项
+fn parse_semester_options(html_content: &str) -> Result<Vec<String>, WebScrapingError> {
+    let document = Html::parse_document(html_content);
+    let option_selector = Selector::parse(r#"select[name="kksj"] option"#)
+        .map_err(|e| WebScrapingError::ParseError(e.to_string()))?;
+
+    Ok(document
+        .select(&option_selector)
+        .filter_map(|option| option.value().attr("value"))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// 从首页文本中解析学生信息, 支持"姓名：张三"写在同一段文字中, 或标签与值分别位于相邻单元格两种形式
+/// 姓名与学号都找不到时返回 None
+fn parse_student_profile(html_content: &str) -> Option<StudentProfile> {
+    let document = Html::parse_document(html_content);
+    let texts: Vec<&str> = document
+        .root_element()
+        .text()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let find_value = |labels: &[&str]| -> String {
+        texts.iter().enumerate().find_map(|(index, text)| {
+            let rest = labels.iter().find_map(|label| text.strip_prefix(label))?;
+            let rest = rest.trim_start_matches([':', '：']).trim();
+            if !rest.is_empty() {
+                Some(rest.to_string())
             } else {
-                courses_record.insert(name, course);
+                texts.get(index + 1).map(|next| next.to_string())
             }
+        })
+        .unwrap_or_default()
+    };
+
+    let student_id = find_value(&["学号"]);
+    // 年级一栏形如"2023级"或"2023", 只取开头的年份
+    let enrollment_year = find_value(&["入学年份", "入学年级", "年级"])
+        .get(..4)
+        .and_then(|year| year.parse().ok())
+        .or_else(|| StudentProfile::enrollment_year_from_id(&student_id));
+
+    let profile = StudentProfile {
+        name: find_value(&["姓名"]),
+        student_id,
+        major: find_value(&["专业名称", "所属专业", "专业"]),
+        class_name: find_value(&["班级名称", "所属班级", "行政班级", "班级"]),
+        enrollment_year,
+    };
+
+    if profile.name.is_empty() && profile.student_id.is_empty() {
+        None
+    } else {
+        Some(profile)
+    }
+}
+
+/// 从分页控件中解析总页数, 形如"共 3 页", 找不到分页控件时视为只有 1 页
+fn parse_page_count(html_content: &str) -> usize {
+    // 使用解析后的文本, 这样 &nbsp; 之类的实体会被还原为空白字符
+    let text: String = Html::parse_document(html_content).root_element().text().collect();
+    let mut page_count = 1;
+
+    for (i, _) in text.match_indices('共') {
+        let rest = text[i + '共'.len_utf8()..].trim_start();
+        let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        let after = rest[digits.len()..].trim_start();
+
+        if after.starts_with('页') && let Ok(n) = digits.parse::<usize>() {
+            page_count = page_count.max(n);
         }
+    }
+
+    page_count
+}
 
+/// 为客户端配置代理, 未配置时沿用 reqwest 的默认行为(读取系统代理)
+/// 分协议的代理先注册, 因此优先于通用代理
+fn apply_proxy(mut builder: ClientBuilder, proxy: &ProxyConfig) -> Result<ClientBuilder> {
+    if proxy.disabled {
         #[cfg(debug_assertions)]
-        print_info(&format!("成绩数据收集完成，如下：\n{:?}", courses_record));
+        print_info("已禁用所有代理");
 
-        // 将值转为向量便于后续处理
-        let course_list: Vec<_> = courses_record.into_values().collect();
+        return Ok(builder.no_proxy());
+    }
 
-        #[cfg(not(debug_assertions))]
-        print_info("成功获取成绩数据");
+    if let Some(url) = &proxy.http {
+        builder = builder.proxy(Proxy::http(url)?);
+    }
+    if let Some(url) = &proxy.https {
+        builder = builder.proxy(Proxy::https(url)?);
+    }
+    if let Some(url) = &proxy.all {
+        builder = builder.proxy(Proxy::all(url)?);
+    }
 
-        // 返回课程数据列表
-        Ok(course_list)
+    #[cfg(debug_assertions)]
+    print_info(&format!("代理设置: {:?}", proxy));
+
+    Ok(builder)
+}
+
+/// 将 reqwest 错误转换为爬取异常, 超时单独区分以便给出友好提示
+fn http_error(e: reqwest::Error) -> WebScrapingError {
+    if e.is_timeout() {
+        WebScrapingError::Timeout
+    } else {
+        WebScrapingError::HttpRequest(e.to_string())
     }
-}
\ No newline at end of file
+}
+
+// 成绩表格各列的位置
+// 教务系统调整列顺序时, 通过表头文字重新定位, 找不到表头时使用以下默认位置
+struct ColumnMap {
+    semester: usize,    // 开课学期
+    course_code: usize, // 课程编号
+    name: usize,    // 课程名称
+    score: usize,   // 总成绩
+    credit: usize,  // 学分
+    nature: usize,  // 课程性质
+    exam_type: usize,   // 考试性质, 部分页面可能没有这一列
+    teacher: Option<usize>, // 任课教师, 以下几列没有默认位置, 只按表头查找
+    hours: Option<usize>,   // 学时
+    assessment: Option<usize>,  // 考核方式
+}
+
+impl Default for ColumnMap {
+    fn default() -> Self {
+        Self {
+            semester: 1, course_code: 2, name: 3, score: 4, credit: 6, nature: 11, exam_type: 12,
+            teacher: None, hours: None, assessment: None
+        }
+    }
+}
+
+impl ColumnMap {
+    /// 根据表头文字建立列位置, 某一列找不到时保留默认位置
+    fn from_headers(headers: &[String]) -> Self {
+        let default = Self::default();
+
+        Self {
+            semester: find_column(headers, &["开课学期", "学期"]).unwrap_or(default.semester),
+            course_code: find_column(headers, &["课程编号", "课程代码", "课程号"]).unwrap_or(default.course_code),
+            name: find_column(headers, &["课程名称", "课程名"]).unwrap_or(default.name),
+            score: find_column(headers, &["总成绩", "成绩"]).unwrap_or(default.score),
+            credit: find_column(headers, &["学分"]).unwrap_or(default.credit),
+            nature: find_column(headers, &["课程性质"]).unwrap_or(default.nature),
+            exam_type: find_column(headers, &["考试性质"]).unwrap_or(default.exam_type),
+            teacher: find_column(headers, &["任课教师", "授课教师", "教师"]),
+            hours: find_column(headers, &["总学时", "学时"]),
+            assessment: find_column(headers, &["考核方式"]),
+        }
+    }
+
+    /// 计算一门课程至少需要的单元格数量(考试性质为可选列)
+    fn required_len(&self) -> usize {
+        [self.semester, self.course_code, self.name, self.score, self.credit, self.nature]
+            .into_iter()
+            .max()
+            .unwrap_or_default() + 1
+    }
+}
+
+/// 在表头中查找列, 先按别名完全匹配, 再按包含匹配, 避免"成绩"误匹配到"成绩标识"之类的列
+fn find_column(headers: &[String], aliases: &[&str]) -> Option<usize> {
+    aliases
+        .iter()
+        .find_map(|alias| headers.iter().position(|h| h == alias))
+        .or_else(|| aliases.iter().find_map(|alias| headers.iter().position(|h| h.contains(alias))))
+}
+
+/// 解析成绩页面 HTML, 返回未去重的课程列表
+pub fn parse_grades(html_content: &str, grading: &GradingTable) -> Result<ParsedCourses, WebScrapingError> {
+    let document = Html::parse_document(html_content);
+
+    // 解析 HTML 课程表格数据
+    // 创建选择器, 类似隔壁 Beautiful Soup
+    let tr_selector = Selector::parse("tr").map_err(|e| WebScrapingError::ParseError(e.to_string()))?;
+    let th_selector = Selector::parse("th").map_err(|e| WebScrapingError::ParseError(e.to_string()))?;
+    let td_selector = Selector::parse("td").map_err(|e| WebScrapingError::ParseError(e.to_string()))?;
+
+    // 先找到表头行, 根据表头文字确定各列的位置
+    let columns = document
+        .select(&tr_selector)
+        .map(|tr| tr.select(&th_selector).map(|th| th.text().collect::<String>().trim().to_string()).collect::<Vec<_>>())
+        .find(|headers| !headers.is_empty())
+        .map(|headers| ColumnMap::from_headers(&headers))
+        .unwrap_or_default();
+
+    #[cfg(debug_assertions)]
+    print_info("解析完成，将收集成绩数据");
+
+    let mut parsed = ParsedCourses::default();
+    let required_len = columns.required_len();
+
+    // 遍历所有数据行, 表头行没有 td, 会在这里被过滤掉
+    for (index, tr) in document.select(&tr_selector).filter(|tr| tr.select(&td_selector).next().is_some()).enumerate() {
+        // 获取当前行的所有单元格, 过滤掉不完整的行(如"未查询到数据"的提示)
+        let tds: Vec<String> = tr.select(&td_selector).map(|td| td.text().collect::<String>().trim().to_string()).collect();
+        if tds.len() < required_len { continue }
+
+        let semester = tds[columns.semester].clone();
+        let course_code = tds[columns.course_code].clone();
+        let name = tds[columns.name].clone();
+        let score = Score::from(tds[columns.score].as_str());
+        let nature = CourseNature::from(tds[columns.nature].as_str());
+        let exam_type = tds.get(columns.exam_type).cloned().unwrap_or_default();
+        let optional = |column: Option<usize>| column.and_then(|i| tds.get(i)).cloned().unwrap_or_default();
+
+        // 学分转换为 Decimal 类型, 无法识别则记录并跳过
+        let credit = match tds[columns.credit].parse::<Decimal>() {
+            Ok(c) => c,
+            Err(_) => {
+                parsed.skipped.push(skipped_row(index + 1, &name, "学分", &tds[columns.credit]));
+                continue
+            }
+        };
+
+        // 转换绩点, 免修、缓考等特殊成绩先保留, 无效绩点则记录并跳过
+        let grade_point = match parsed_grade(&score, grading) {
+            Some(g) => g,
+            None => {
+                parsed.skipped.push(skipped_row(index + 1, &name, "成绩", &score.to_string()));
+                continue
+            }
+        };
+
+        // 计算加权绩点并按学校规则取整
+        let credit_gpa = grading.rounding.round(grade_point * credit);
+
+        parsed.courses.push(Course {
+            course_code,
+            semester,
+            name,
+            nature,
+            score,
+            credit,
+            grade: grade_point,
+            credit_gpa,
+            exam_type,
+            teacher: optional(columns.teacher),
+            hours: optional(columns.hours),
+            assessment: optional(columns.assessment),
+            minor: false,
+            source: String::new()
+        });
+    }
+
+    Ok(parsed)
+}
+
+/// 成绩页面中因某一列无法识别而跳过的一行, 所在页面由调用方补充
+fn skipped_row(row: usize, name: &str, column: &str, value: &str) -> SkippedRow {
+    SkippedRow {
+        location: String::new(), row, name: name.to_string(), column: column.to_string(), value: value.to_string(),
+        reason: format!("{}“{}”无法识别", column, value)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_grades_by_header() {
+        // 各列顺序与默认位置不同, 按表头定位; 末行是"未查询到数据"之类单元格不足的提示行
+        let html = r#"<table>
+            <tr><th>序号</th><th>课程名称</th><th>开课学期</th><th>课程编号</th><th>学分</th><th>总成绩</th><th>课程性质</th><th>考试性质</th><th>任课教师</th></tr>
+            <tr><td>1</td><td>高等数学</td><td>2023-2024-1</td><td>MA101</td><td>4</td><td>90</td><td>必修课</td><td>正常考试</td><td>张老师</td></tr>
+            <tr><td>2</td><td>大学英语</td><td>2023-2024-1</td><td>EN101</td><td>2</td><td>良</td><td>必修课</td><td>正常考试</td><td></td></tr>
+            <tr><td>3</td><td>体育</td><td>2023-2024-1</td><td>PE101</td><td>一</td><td>85</td><td>必修课</td><td>正常考试</td><td></td></tr>
+            <tr><td colspan="9">未查询到数据</td></tr>
+        </table>"#;
+
+        let parsed = parse_grades(html, &GradingTable::default()).unwrap();
+        assert_eq!(parsed.courses.len(), 2);
+
+        let math = &parsed.courses[0];
+        assert_eq!((math.name.as_str(), math.semester.as_str(), math.course_code.as_str()), ("高等数学", "2023-2024-1", "MA101"));
+        assert_eq!((math.credit, math.grade, math.credit_gpa), (Decimal::from(4), Decimal::new(433, 2), Decimal::new(1732, 2)));
+        assert_eq!(math.nature, CourseNature::Compulsory);
+        assert_eq!((math.exam_type.as_str(), math.teacher.as_str()), ("正常考试", "张老师"));
+        assert_eq!(parsed.courses[1].grade, Decimal::new(333, 2));
+
+        // 学分无法识别的行被跳过并记录
+        assert_eq!(parsed.skipped.len(), 1);
+        assert_eq!((parsed.skipped[0].row, parsed.skipped[0].name.as_str(), parsed.skipped[0].column.as_str()), (3, "体育", "学分"));
+    }
+
+    #[test]
+    fn parse_grades_without_header_uses_default_columns() {
+        let html = r#"<table>
+            <tr><td>1</td><td>2023-2024-2</td><td>CS201</td><td>数据结构</td><td>85</td><td></td><td>3</td><td></td><td></td><td></td><td></td><td>选修课</td><td>补考</td></tr>
+        </table>"#;
+
+        let parsed = parse_grades(html, &GradingTable::default()).unwrap();
+        assert_eq!(parsed.courses.len(), 1);
+        let course = &parsed.courses[0];
+        assert_eq!((course.name.as_str(), course.credit, course.nature.clone()), ("数据结构", Decimal::from(3), CourseNature::Elective));
+        assert_eq!(course.exam_type, "补考");
+    }
+}
@@ -0,0 +1,174 @@
+// 启动自检层 - 启动时检查运行环境, 有问题时显示诊断页面, 而不是等到某个请求处理到一半才出错
+use crate::{
+    business::{format_log_msg, print_error, print_info},
+    BinaryAsset, TemplateAsset
+};
+
+use anyhow::{anyhow, Context, Result};
+use std::{collections::HashMap, fs, path::Path};
+use tera::{Tera, Value};
+
+// 程序运行必需的嵌入资源
+const REQUIRED_TEMPLATE_ASSETS: &[&str] = &[
+    "base.html", "login.html", "result.html", "history.html", "empty.html", "cohort.html", "snapshot.html",
+    "static/css/bootstrap.min.css", "static/js/bootstrap.bundle.min.js",
+];
+const REQUIRED_BINARY_ASSETS: &[&str] = &["changelog.json"];
+
+// 写入测试使用的临时文件名
+const WRITE_PROBE_FILE: &str = ".write-test";
+
+// 单项检查结果
+#[derive(Debug, Clone)]
+pub struct CheckItem {
+    pub name: String,   // 检查项名称, 如"模板编译"
+    pub passed: bool,
+    pub detail: String, // 通过时为简要说明, 失败时为错误原因
+}
+
+// 全部检查结果
+#[derive(Debug, Clone, Default)]
+pub struct SelfCheckReport {
+    pub items: Vec<CheckItem>,
+}
+
+impl SelfCheckReport {
+    /// 记录一项检查, 成功时返回检查得到的值
+    pub fn record<T>(&mut self, name: &str, result: Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.items.push(CheckItem { name: name.to_string(), passed: true, detail: "正常".to_string() });
+                Some(value)
+            }
+            Err(e) => {
+                // {:#} 会带上完整的错误链, 便于定位问题
+                self.items.push(CheckItem { name: name.to_string(), passed: false, detail: format!("{:#}", e) });
+                None
+            }
+        }
+    }
+
+    /// 是否全部通过
+    pub fn passed(&self) -> bool {
+        self.items.iter().all(|item| item.passed)
+    }
+
+    /// 在控制台输出检查结果
+    pub fn print(&self) {
+        for item in &self.items {
+            if item.passed {
+                print_info(&format!("自检 [{}]: {}", item.name, item.detail));
+            } else {
+                print_error(&format!("自检 [{}] 未通过: {}", item.name, item.detail));
+            }
+        }
+    }
+
+    /// 生成诊断页面
+    /// 模板本身也可能是出问题的部分, 所以不使用模板引擎与 Bootstrap, 直接拼接 HTML
+    pub fn render_html(&self) -> String {
+        let rows: String = self.items
+            .iter()
+            .map(|item| format!(
+                "<tr><td>{}</td><td class=\"{}\">{}</td><td>{}</td></tr>",
+                tera::escape_html(&item.name),
+                if item.passed { "ok" } else { "fail" },
+                if item.passed { "通过" } else { "未通过" },
+                tera::escape_html(&item.detail)
+            ))
+            .collect();
+
+        format!(r#"<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+    <meta charset="UTF-8">
+    <title>GPA查询 - 启动自检未通过</title>
+    <style>
+        body {{ font-family: sans-serif; max-width: 960px; margin: 40px auto; padding: 0 16px; color: #222; }}
+        table {{ border-collapse: collapse; width: 100%; }}
+        th, td {{ border: 1px solid #ccc; padding: 8px 12px; text-align: left; vertical-align: top; }}
+        td {{ word-break: break-all; }}
+        .ok {{ color: #198754; white-space: nowrap; }}
+        .fail {{ color: #dc3545; font-weight: bold; white-space: nowrap; }}
+    </style>
+</head>
+<body>
+<h2>启动自检未通过</h2>
+<p>程序在启动时发现以下问题，为避免使用过程中出错，暂时只显示此诊断页面。请根据提示处理后重新启动程序。</p>
+<table>
+    <thead><tr><th>检查项</th><th>结果</th><th>说明</th></tr></thead>
+    <tbody>{}</tbody>
+</table>
+</body>
+</html>"#, rows)
+    }
+}
+
+/// 编译所有嵌入的模板
+pub fn load_templates() -> Result<Tera> {
+    let mut tera = Tera::default();
+
+    // 遍历所有嵌入的文件
+    for file_path in TemplateAsset::iter() {
+        // 静态资源不是模板, 不需要编译
+        if file_path.starts_with("static/") {
+            continue;
+        }
+
+        // 获取文件内容
+        if let Some(embedded_file) = TemplateAsset::get(&file_path) {
+            // embedded_file.data 是文件内容, 类型为 Vec<u8>
+            // 将 Vec<u8> 转换为 &str
+            let content = std::str::from_utf8(embedded_file.data.as_ref())
+                .with_context(|| format_log_msg(&format!("模板文件不是有效的 UTF-8: {}", file_path)))?;
+
+            // 将 HTML 模板添加到 Tera 实例
+            tera.add_raw_template(&file_path, content).with_context(|| format_log_msg(&format!("导入嵌入文件失败: {}", file_path)))?;
+        }
+    }
+
+    // 构建 Tera 的继承链
+    tera.build_inheritance_chains().with_context(|| format_log_msg("构建Tera继承链失败"))?;
+
+    tera.register_filter("json_script", json_script);
+
+    Ok(tera)
+}
+
+/// 把数据编码为可以放进 <script type="application/json"> 的 JSON
+/// 转义尖括号与 &, 课程名称或排除规则中的 </script> 不会提前结束脚本块
+fn json_script(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let json = serde_json::to_string(value)?
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026");
+
+    Ok(Value::String(json))
+}
+
+/// 检查必需的嵌入资源是否存在
+pub fn check_assets() -> Result<()> {
+    let missing: Vec<&str> = REQUIRED_TEMPLATE_ASSETS
+        .iter()
+        .filter(|path| TemplateAsset::get(path).is_none())
+        .chain(REQUIRED_BINARY_ASSETS.iter().filter(|path| BinaryAsset::get(path).is_none()))
+        .copied()
+        .collect();
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("缺少以下资源文件: {}", missing.join(", ")))
+    }
+}
+
+/// 检查数据目录是否可写, 不存在时自动创建
+pub fn check_data_dir(data_dir: &Path) -> Result<()> {
+    fs::create_dir_all(data_dir).with_context(|| format!("无法创建数据目录 {}", data_dir.display()))?;
+
+    let probe = data_dir.join(WRITE_PROBE_FILE);
+    fs::write(&probe, b"ok").with_context(|| format!("数据目录 {} 不可写", data_dir.display()))?;
+    let _ = fs::remove_file(&probe);
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+// 用户设置层 - 以 JSON 文件的形式持久化保存在数据目录中
+use crate::{business::print_error, profile::SchoolProfile};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::RwLock
+};
+
+pub const SETTINGS_FILE: &str = "settings.json";
+const BUNDLE_VERSION: u32 = 1;  // 设置文件的格式版本, 格式不兼容地修改时递增
+
+// 目标绩点(如保研线)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Benchmark {
+    pub gpa: Decimal,   // 目标绩点, 如 3.6
+    pub remaining_semesters: u32,   // 距离截止时间(如保研评定)还剩几个学期
+    pub credits_per_semester: Decimal,  // 预计每学期修读的学分
+}
+
+// 所有用户设置, 新增字段需带上 serde(default) 以兼容旧的设置文件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserSettings {
+    #[serde(default)]
+    pub benchmark: Option<Benchmark>,
+    #[serde(default)]
+    pub watch_new_grades: bool,     // 是否希望在出成绩时收到提醒, 供后台成绩监控使用
+    #[serde(default)]
+    pub last_seen_version: Option<String>,  // 上次打开登录页时的程序版本, 用于升级后提示新增功能
+}
+
+// 设置文件, 包含用户设置与学校规则, 便于全班在不同电脑上使用同一套规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsBundle {
+    pub version: u32,
+    pub exported_at: DateTime<Local>,
+    pub settings: UserSettings,
+    pub profile: SchoolProfile,
+}
+
+impl SettingsBundle {
+    /// 以当前时间打包设置
+    pub fn new(settings: UserSettings, profile: SchoolProfile) -> Self {
+        Self { version: BUNDLE_VERSION, exported_at: Local::now(), settings, profile }
+    }
+
+    /// 解析设置文件, 由更新版本的程序导出的文件可能无法正确理解, 直接拒绝
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let bundle: Self = serde_json::from_slice(data).context("设置文件格式有误")?;
+        if bundle.version > BUNDLE_VERSION {
+            return Err(anyhow!("设置文件由更新版本的程序导出(格式版本 {}), 请先更新本程序", bundle.version));
+        }
+
+        Ok(bundle)
+    }
+}
+
+// 设置存储, 读多写少, 所以使用读写锁
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: RwLock<UserSettings>,
+}
+
+impl SettingsStore {
+    /// 从数据目录加载设置, 文件不存在时使用默认设置
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(SETTINGS_FILE);
+
+        let settings = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                // 设置文件损坏时不阻止程序启动, 回退到默认设置
+                print_error(&format!("设置文件 {} 解析失败, 将使用默认设置: {}", path.display(), e));
+                UserSettings::default()
+            }),
+            Err(_) => UserSettings::default()
+        };
+
+        Ok(Self { path, settings: RwLock::new(settings) })
+    }
+
+    /// 获取当前设置的副本
+    pub fn get(&self) -> UserSettings {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// 修改设置并立即写回文件, 写入失败时内存中的设置保持不变
+    pub fn update(&self, f: impl FnOnce(&mut UserSettings)) -> Result<UserSettings> {
+        let mut guard = self.settings.write().unwrap();
+        let mut settings = guard.clone();
+        f(&mut settings);
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("无法创建数据目录 {}", dir.display()))?;
+        }
+        let content = serde_json::to_string_pretty(&settings)?;
+        fs::write(&self.path, content).with_context(|| format!("无法写入设置文件 {}", self.path.display()))?;
+
+        *guard = settings;
+
+        Ok(guard.clone())
+    }
+}
@@ -0,0 +1,109 @@
+// 共享状态层 - 所有路由处理器都能访问的全局状态
+use crate::{backup::BackupStore, changelog::Changelog, config::AppConfig, export::{ExportJobs, ExporterRegistry}, profile::{ProfileStore, SchoolProfile}, progress::ProgressHub, resume::ResumeStore, settings::SettingsStore, storage::SnapshotStore, summary::SummaryStore, watcher::GradeWatcher};
+
+use axum::extract::FromRef;
+use std::sync::Arc;
+use tera::Tera;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub tera: Arc<Tera>,    // 模板引擎, 共享同一份而不是每个请求复制一份
+    pub config: Arc<AppConfig>, // 启动配置, 只读
+    pub settings: Arc<SettingsStore>,   // 用户设置, 可在运行时修改并持久化
+    pub snapshots: Arc<SnapshotStore>,  // 每次查询的成绩快照数据库
+    pub progress: Arc<ProgressHub>,     // 正在进行的查询的进度
+    pub profile: Arc<ProfileStore>,     // 学校规则, 可通过导入设置文件替换
+    pub backups: Arc<BackupStore>,      // 覆盖或清除结果前的自动备份
+    pub exporters: Arc<ExporterRegistry>,   // 可用的导出格式
+    pub export_jobs: Arc<ExportJobs>,   // 正在生成或等待下载的导出文件
+    pub summary: Arc<SummaryStore>,     // 最近一次结果的摘要, 供外部 API 读取
+    pub changelog: Arc<Changelog>,      // 内嵌的更新日志
+    pub resume: Arc<ResumeStore>,       // 分批任务的进度, 中断后从中断处继续
+    pub watcher: Arc<GradeWatcher>,     // 后台成绩监控
+}
+
+// 让处理器可以只提取自己需要的部分, 如 State<Arc<Tera>>
+impl FromRef<AppState> for Arc<Tera> {
+    fn from_ref(state: &AppState) -> Self {
+        state.tera.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<AppConfig> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<SettingsStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.settings.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<SnapshotStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.snapshots.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ProgressHub> {
+    fn from_ref(state: &AppState) -> Self {
+        state.progress.clone()
+    }
+}
+
+// 只读取规则的处理器直接提取当前规则
+impl FromRef<AppState> for Arc<SchoolProfile> {
+    fn from_ref(state: &AppState) -> Self {
+        state.profile.get()
+    }
+}
+
+impl FromRef<AppState> for Arc<ProfileStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.profile.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<BackupStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.backups.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ExporterRegistry> {
+    fn from_ref(state: &AppState) -> Self {
+        state.exporters.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ExportJobs> {
+    fn from_ref(state: &AppState) -> Self {
+        state.export_jobs.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<SummaryStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.summary.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Changelog> {
+    fn from_ref(state: &AppState) -> Self {
+        state.changelog.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ResumeStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.resume.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<GradeWatcher> {
+    fn from_ref(state: &AppState) -> Self {
+        state.watcher.clone()
+    }
+}
@@ -0,0 +1,447 @@
+// 成绩存储层 - 每次登录查询得到的课程列表都以带时间的快照保存到数据目录的 SQLite 数据库中, 程序退出后也不会丢失
+// 以学号(登录账号)的哈希值区分学生; 哈希未加盐, 学号的取值范围有限, 可以被逐个尝试还原, 不能当作对学号保密
+// 结果页的成绩变动提示、历史成绩搜索、与上一次查询的对比以及历史页面都读取这里的快照
+use crate::{business::print_info, export::content_hash, models::{Course, CourseNature, Score}};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::Path, sync::Mutex};
+
+pub const DATABASE_FILE: &str = "grades.db";
+const LEGACY_HISTORY_FILE: &str = "history.json";   // 旧版本保存成绩历史的文件, 启动时导入数据库
+const LIST_LIMIT: usize = 200;  // 历史页面最多列出的快照数量
+const SEARCH_LIMIT: usize = 50;     // 搜索最多返回的课程数
+
+// 数据库结构, 每次启动时执行, 已存在的表不受影响
+// 课程的完整内容以 JSON 保存在 data 中, 名称、学期与成绩另存为列, 便于直接查询
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS snapshots (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        student_key TEXT NOT NULL,
+        taken_at TEXT NOT NULL,
+        course_count INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS snapshots_by_student ON snapshots (student_key, taken_at);
+    CREATE TABLE IF NOT EXISTS snapshot_courses (
+        snapshot_id INTEGER NOT NULL REFERENCES snapshots (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        semester TEXT NOT NULL,
+        score TEXT NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (snapshot_id, position)
+    );
+";
+
+// 某门课程的一次成绩变动
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreChange {
+    pub changed_at: DateTime<Local>,    // 发现变动的查询时间
+    pub from: Option<String>,   // 之前的成绩, 之前没有这门课程时为空
+    pub to: String,     // 新的成绩
+}
+
+// 按课程名称搜索历史成绩的一条结果, 同一学期同一成绩在多份快照中出现时合并为一条
+#[derive(Debug, Clone, Serialize)]
+pub struct CourseMatch {
+    pub name: String,
+    pub semester: String,
+    pub nature: CourseNature,
+    pub credit: Decimal,
+    pub score: Score,
+    pub first_seen: DateTime<Local>,    // 最早出现该成绩的查询时间
+    pub last_seen: DateTime<Local>,     // 最近出现该成绩的查询时间
+    #[serde(skip)]
+    relevance: u8,  // 匹配程度, 越小越相关
+}
+
+// 旧版本 history.json 中的快照, 只用于导入
+#[derive(Deserialize)]
+struct LegacySnapshot {
+    taken_at: DateTime<Local>,
+    courses: Vec<Course>,
+}
+
+#[derive(Deserialize)]
+struct LegacyHistory {
+    #[serde(default)]
+    snapshots: HashMap<String, Vec<LegacySnapshot>>,
+}
+
+// 与上一份快照相比的一门课程, 新出现的课程 from 为空
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffEntry {
+    pub name: String,
+    pub semester: String,
+    pub exam_type: String,  // 同一学期的补考与正常考试分开比较
+    pub from: Option<String>,   // 上一份快照中的成绩
+    pub to: String,     // 本次查询的成绩
+}
+
+// 本次查询与上一份快照的差异, 结果页据此提示"本次新增 3 门成绩"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradeDiff {
+    pub previous_at: DateTime<Local>,   // 上一份快照的查询时间
+    pub added: Vec<DiffEntry>,      // 新公布的成绩
+    pub changed: Vec<DiffEntry>,    // 成绩有变动的课程
+}
+
+impl GradeDiff {
+    /// 比较两份课程列表, 以学期、课程名称与考试性质区分课程, 同一课程重修后出现在新学期或补考成绩公布时算作新增
+    pub fn between(previous_at: DateTime<Local>, previous: &[Course], current: &[Course]) -> Self {
+        let previous: HashMap<(&str, &str, &str), String> = previous.iter()
+            .map(|c| ((c.semester.as_str(), c.name.as_str(), c.exam_type.as_str()), c.score.to_string()))
+            .collect();
+
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for course in current {
+            let score = course.score.to_string();
+            let entry = |from: Option<String>| DiffEntry {
+                name: course.name.clone(), semester: course.semester.clone(), exam_type: course.exam_type.clone(), from, to: score.clone()
+            };
+            match previous.get(&(course.semester.as_str(), course.name.as_str(), course.exam_type.as_str())) {
+                None => added.push(entry(None)),
+                Some(from) if *from != score => changed.push(entry(Some(from.clone()))),
+                Some(_) => {}
+            }
+        }
+
+        Self { previous_at, added, changed }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty()
+    }
+}
+
+// 快照列表中的一项, 不含课程内容
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotInfo {
+    pub id: i64,
+    pub taken_at: DateTime<Local>,
+    pub course_count: usize,
+}
+
+// 成绩快照数据库, 同一时间只有一个请求写入
+pub struct SnapshotStore {
+    conn: Mutex<Connection>,
+}
+
+impl SnapshotStore {
+    /// 打开数据目录中的数据库, 不存在时创建; 旧版本的 history.json 会先导入数据库
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(DATABASE_FILE);
+        let mut conn = Connection::open(&path).with_context(|| format!("无法打开成绩数据库 {}", path.display()))?;
+        conn.execute_batch(SCHEMA).with_context(|| format!("成绩数据库 {} 初始化失败", path.display()))?;
+        import_legacy_history(&mut conn, data_dir)?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// 账号在数据库中使用的键, 只用于区分账号, 不能防止还原出学号
+    pub fn account_key(account: &str) -> String {
+        content_hash(account.trim().as_bytes())
+    }
+
+    /// 保存一份快照, 返回快照编号; 成绩与上一次相同时也保存, 以记录每次查询的时间
+    pub fn save(&self, student_key: &str, courses: &[Course]) -> Result<i64> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let id = insert_snapshot(&tx, student_key, &Local::now(), courses)?;
+        tx.commit()?;
+
+        Ok(id)
+    }
+
+    /// 读取最近一份快照的查询时间与课程, 没有快照时返回 None
+    pub fn latest(&self, student_key: &str) -> Result<Option<(DateTime<Local>, Vec<Course>)>> {
+        let conn = self.conn.lock().unwrap();
+
+        let latest: Option<(i64, String)> = conn.query_row(
+            "SELECT id, taken_at FROM snapshots WHERE student_key = ?1 ORDER BY taken_at DESC, id DESC LIMIT 1",
+            params![student_key],
+            |row| Ok((row.get(0)?, row.get(1)?))
+        ).optional()?;
+        let Some((id, taken_at)) = latest else {
+            return Ok(None);
+        };
+
+        Ok(Some((parse_taken_at(id, &taken_at)?, load_courses(&conn, id)?)))
+    }
+
+    /// 列出学生的快照, 最近的在前
+    pub fn list(&self, student_key: &str) -> Result<Vec<SnapshotInfo>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut select = conn.prepare(
+            "SELECT id, taken_at, course_count FROM snapshots WHERE student_key = ?1 ORDER BY taken_at DESC, id DESC LIMIT ?2"
+        )?;
+        let rows = select.query_map(params![student_key, LIST_LIMIT], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, usize>(2)?))
+        })?;
+
+        let mut snapshots = Vec::new();
+        for row in rows {
+            let (id, taken_at, course_count) = row?;
+            snapshots.push(SnapshotInfo { id, taken_at: parse_taken_at(id, &taken_at)?, course_count });
+        }
+
+        Ok(snapshots)
+    }
+
+    /// 读取一份快照的查询时间与课程, 快照不存在或不属于该学生时返回 None
+    pub fn load(&self, student_key: &str, id: i64) -> Result<Option<(DateTime<Local>, Vec<Course>)>> {
+        let conn = self.conn.lock().unwrap();
+
+        let taken_at: Option<String> = conn.query_row(
+            "SELECT taken_at FROM snapshots WHERE id = ?1 AND student_key = ?2",
+            params![id, student_key],
+            |row| row.get(0)
+        ).optional()?;
+        let Some(taken_at) = taken_at else {
+            return Ok(None);
+        };
+
+        Ok(Some((parse_taken_at(id, &taken_at)?, load_courses(&conn, id)?)))
+    }
+
+    /// 按课程汇总各快照之间的成绩变动, 没有变动过的课程不包含在结果中
+    /// 与 GradeDiff 一样以学期、课程名称与考试性质区分课程, 键为"学期|课程名称|考试性质", 重修与补考分开记录
+    /// 第一份快照只作为基准, 不算作变动; 考试性质从课程数据中取出, 其余只读取需要的列
+    pub fn course_changes(&self, student_key: &str) -> Result<HashMap<String, Vec<ScoreChange>>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut select = conn.prepare(
+            "SELECT s.id, s.taken_at, c.semester, c.name, COALESCE(json_extract(c.data, '$.exam_type'), ''), c.score
+             FROM snapshots s JOIN snapshot_courses c ON c.snapshot_id = s.id
+             WHERE s.student_key = ?1 ORDER BY s.taken_at, s.id, c.position"
+        )?;
+        let rows = select.query_map(params![student_key], |row| {
+            let key = course_key(&row.get::<_, String>(2)?, &row.get::<_, String>(3)?, &row.get::<_, String>(4)?);
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, key, row.get::<_, String>(5)?))
+        })?;
+
+        let mut changes: HashMap<String, Vec<ScoreChange>> = HashMap::new();
+        let mut previous: Option<HashMap<String, String>> = None;   // 上一份快照中课程到成绩的映射
+        let mut current: (i64, String, HashMap<String, String>) = (0, String::new(), HashMap::new());
+        for row in rows.chain(std::iter::once(Ok((-1, String::new(), String::new(), String::new())))) {
+            let (id, taken_at, key, score) = row?;
+            if id != current.0 {
+                // 上一份快照读取完毕, 与再之前的一份比较
+                let (done_id, done_at, done) = std::mem::replace(&mut current, (id, taken_at, HashMap::new()));
+                if done_id != 0 {
+                    if let Some(before) = &previous {
+                        let changed_at = parse_taken_at(done_id, &done_at)?;
+                        for (key, score) in &done {
+                            let from = before.get(key);
+                            if from != Some(score) {
+                                changes.entry(key.clone()).or_default().push(ScoreChange {
+                                    changed_at, from: from.cloned(), to: score.clone()
+                                });
+                            }
+                        }
+                    }
+                    previous = Some(done);
+                }
+            }
+            current.2.insert(key, score);
+        }
+
+        Ok(changes)
+    }
+
+    /// 在所有快照中按课程名称模糊搜索, 如"离散"或"离数"都能找到"离散数学"
+    /// 结果按匹配程度排序, 同样相关时较晚的学期在前
+    pub fn search(&self, student_key: &str, query: &str) -> Result<Vec<CourseMatch>> {
+        let query = normalize(query);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let mut select = conn.prepare(
+            "SELECT s.id, s.taken_at, c.name, c.semester, c.score, c.data FROM snapshots s JOIN snapshot_courses c ON c.snapshot_id = s.id
+             WHERE s.student_key = ?1 ORDER BY s.taken_at, s.id, c.position"
+        )?;
+        let rows = select.query_map(params![student_key], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?, row.get::<_, String>(4)?, row.get::<_, String>(5)?))
+        })?;
+
+        let mut matches: Vec<(CourseMatch, String)> = Vec::new();     // 附带成绩文字, 用于合并相同的成绩
+        for row in rows {
+            let (id, taken_at, name, semester, score, data) = row?;
+            let Some(relevance) = match_relevance(&normalize(&name), &query) else {
+                continue;
+            };
+            let taken_at = parse_taken_at(id, &taken_at)?;
+
+            match matches.iter_mut().find(|(m, s)| m.name == name && m.semester == semester && *s == score) {
+                Some((m, _)) => m.last_seen = taken_at,
+                None => {
+                    // 只有匹配的课程才解析完整内容
+                    let course: Course = serde_json::from_str(&data).with_context(|| format!("快照 {} 的课程数据无法读取", id))?;
+                    matches.push((CourseMatch {
+                        name,
+                        semester,
+                        nature: course.nature,
+                        credit: course.credit,
+                        score: course.score,
+                        first_seen: taken_at,
+                        last_seen: taken_at,
+                        relevance,
+                    }, score));
+                }
+            }
+        }
+
+        let mut matches: Vec<CourseMatch> = matches.into_iter().map(|(m, _)| m).collect();
+        matches.sort_by(|a, b| a.relevance.cmp(&b.relevance).then_with(|| b.semester.cmp(&a.semester)).then_with(|| a.first_seen.cmp(&b.first_seen)));
+        matches.truncate(SEARCH_LIMIT);
+        Ok(matches)
+    }
+}
+
+/// 在事务中写入一份快照, 返回快照编号
+fn insert_snapshot(tx: &Transaction, student_key: &str, taken_at: &DateTime<Local>, courses: &[Course]) -> Result<i64> {
+    tx.execute(
+        "INSERT INTO snapshots (student_key, taken_at, course_count) VALUES (?1, ?2, ?3)",
+        params![student_key, taken_at.to_rfc3339(), courses.len()]
+    )?;
+    let id = tx.last_insert_rowid();
+
+    let mut insert = tx.prepare(
+        "INSERT INTO snapshot_courses (snapshot_id, position, name, semester, score, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+    )?;
+    for (position, course) in courses.iter().enumerate() {
+        insert.execute(params![id, position, course.name, course.semester, course.score.to_string(), serde_json::to_string(course)?])?;
+    }
+
+    Ok(id)
+}
+
+/// 成绩变动记录中区分课程的键, 与结果页标出新增课程时使用的键相同
+fn course_key(semester: &str, name: &str, exam_type: &str) -> String {
+    format!("{}|{}|{}", semester, name, exam_type)
+}
+
+/// 把旧版本 history.json 中的快照导入数据库, 导入后改名为 history.json.imported, 不再读取
+fn import_legacy_history(conn: &mut Connection, data_dir: &Path) -> Result<()> {
+    let path = data_dir.join(LEGACY_HISTORY_FILE);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(());
+    };
+    let history: LegacyHistory = serde_json::from_str(&content)
+        .with_context(|| format!("成绩历史文件 {} 解析失败", path.display()))?;
+
+    let tx = conn.transaction()?;
+    let mut count = 0;
+    for (student_key, snapshots) in &history.snapshots {
+        for snapshot in snapshots {
+            insert_snapshot(&tx, student_key, &snapshot.taken_at, &snapshot.courses)?;
+            count += 1;
+        }
+    }
+    tx.commit()?;
+
+    let imported = path.with_extension("json.imported");
+    fs::rename(&path, &imported).with_context(|| format!("无法重命名已导入的成绩历史文件 {}", path.display()))?;
+    print_info(&format!("已将 {} 中的 {} 份成绩快照导入成绩数据库, 原文件改名为 {}", path.display(), count, imported.display()));
+
+    Ok(())
+}
+
+/// 快照的查询时间以 RFC 3339 文本保存
+fn parse_taken_at(id: i64, taken_at: &str) -> Result<DateTime<Local>> {
+    Ok(DateTime::parse_from_rfc3339(taken_at)
+        .with_context(|| format!("快照 {} 的查询时间 {} 无法识别", id, taken_at))?
+        .with_timezone(&Local))
+}
+
+/// 按保存时的顺序读取快照中的课程
+fn load_courses(conn: &Connection, id: i64) -> Result<Vec<Course>> {
+    let mut select = conn.prepare("SELECT data FROM snapshot_courses WHERE snapshot_id = ?1 ORDER BY position")?;
+    let courses = select.query_map(params![id], |row| row.get::<_, String>(0))?
+        .map(|data| Ok(serde_json::from_str(&data?)?))
+        .collect::<Result<Vec<Course>>>()
+        .with_context(|| format!("快照 {} 的课程数据无法读取", id))?;
+
+    Ok(courses)
+}
+
+/// 搜索时忽略大小写与空白
+fn normalize(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect()
+}
+
+/// 课程名称与搜索词的匹配程度: 完全相同为 0, 前缀为 1, 包含为 2, 按顺序包含搜索词的每个字为 3, 不匹配为 None
+fn match_relevance(name: &str, query: &str) -> Option<u8> {
+    if name == query {
+        return Some(0);
+    }
+    if name.starts_with(query) {
+        return Some(1);
+    }
+    if name.contains(query) {
+        return Some(2);
+    }
+
+    let mut chars = name.chars();
+    query.chars().all(|q| chars.any(|c| c == q)).then_some(3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn course(semester: &str, name: &str, exam_type: &str, score: &str) -> Course {
+        Course {
+            course_code: String::new(),
+            semester: semester.to_string(),
+            name: name.to_string(),
+            nature: CourseNature::Compulsory,
+            score: Score::from(score),
+            credit: Decimal::from(4),
+            grade: Decimal::ZERO,
+            credit_gpa: Decimal::ZERO,
+            exam_type: exam_type.to_string(),
+            teacher: String::new(),
+            hours: String::new(),
+            assessment: String::new(),
+            minor: false,
+            source: String::new(),
+        }
+    }
+
+    #[test]
+    fn retake_in_new_term_is_added() {
+        let previous = [
+            course("2022-2023-1", "高等数学", "正常考试", "55"),
+            course("2022-2023-1", "大学英语", "正常考试", "80"),
+        ];
+        let current = [
+            course("2022-2023-1", "高等数学", "正常考试", "55"),
+            course("2022-2023-1", "大学英语", "正常考试", "85"),
+            course("2023-2024-1", "高等数学", "重修", "78"),
+        ];
+
+        let diff = GradeDiff::between(Local::now(), &previous, &current);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!((diff.added[0].semester.as_str(), diff.added[0].exam_type.as_str()), ("2023-2024-1", "重修"));
+        assert_eq!((diff.added[0].from.as_deref(), diff.added[0].to.as_str()), (None, "78"));
+        // 首次考试的成绩没有被重修成绩覆盖, 不算变动
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!((diff.changed[0].name.as_str(), diff.changed[0].from.as_deref()), ("大学英语", Some("80")));
+    }
+
+    #[test]
+    fn same_courses_have_no_diff() {
+        let courses = [course("2022-2023-1", "高等数学", "正常考试", "90")];
+        assert!(GradeDiff::between(Local::now(), &courses, &courses).is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+// 摘要层 - 保存最近一次查询或导入的核心数据, 供个人看板等外部页面通过 API 读取
+use crate::business::ProcessedGPAResults;
+
+use chrono::{DateTime, Local};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::sync::RwLock;
+
+// 各计算模式的平均绩点
+#[derive(Debug, Clone, Serialize)]
+pub struct ModeGpa {
+    pub default: Option<Decimal>,   // 免登录模式没有 Default 模式
+    pub all: Decimal,
+}
+
+// 结果摘要, 不包含具体课程
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub gpa: ModeGpa,
+    pub total_credits: Decimal, // All 模式下的总学分
+    pub course_count: usize,
+    pub refreshed_at: DateTime<Local>,  // 数据的获取时间
+}
+
+impl Summary {
+    pub fn from_results(results: &ProcessedGPAResults) -> Self {
+        Self {
+            gpa: ModeGpa {
+                default: results.default.as_ref().map(|r| r.gpa),
+                all: results.all.gpa,
+            },
+            total_credits: results.all.courses.iter().map(|c| c.credit).sum(),
+            course_count: results.all.courses.len(),
+            refreshed_at: Local::now(),
+        }
+    }
+}
+
+// 只在内存中保存最近一份摘要, 程序重启后需要重新查询
+#[derive(Default)]
+pub struct SummaryStore {
+    latest: RwLock<Option<Summary>>,
+}
+
+impl SummaryStore {
+    pub fn update(&self, summary: Summary) {
+        *self.latest.write().unwrap() = Some(summary);
+    }
+
+    pub fn get(&self) -> Option<Summary> {
+        self.latest.read().unwrap().clone()
+    }
+}
@@ -0,0 +1,239 @@
+// 后台成绩监控 - 开启出成绩提醒后, 定期用最近一次登录的账号重新查询成绩, 与上一份快照比较并记录是否有变化
+// 账号密码只保存在内存中, 不写入数据目录, 程序重启或关闭提醒后需要重新登录一次
+use crate::{
+    business::{print_error, print_info, process_scraped_course_results, ResultSource},
+    demo::DemoSource,
+    export::mode_label,
+    mail::{EmailNotifier, GpaChange},
+    models::Course,
+    notification::notify_grade_diff,
+    profile::SchoolProfile,
+    scraping::{AAOWebsite, GradeSource},
+    state::AppState,
+    storage::{GradeDiff, SnapshotStore}
+};
+
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeDelta};
+use serde::Serialize;
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration
+};
+use tokio::{sync::broadcast, time::sleep};
+
+// 监控使用的账号密码
+#[derive(Clone)]
+struct Credentials {
+    account: String,
+    password: String,
+}
+
+// 历次检查的记录
+#[derive(Debug, Clone, Default)]
+struct WatchRecord {
+    checks: u64,    // 启动以来完成的检查次数, 包括失败的检查
+    last_checked_at: Option<DateTime<Local>>,
+    last_error: Option<String>, // 最近一次检查失败的原因, 成功后清空
+    last_changed_at: Option<DateTime<Local>>,   // 最近一次发现成绩变化的时间
+    last_diff: Option<GradeDiff>,   // 最近一次发现的变化
+    next_check_at: Option<DateTime<Local>>,
+}
+
+// 监控状态, 由状态接口返回
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchStatus {
+    pub enabled: bool,  // 是否开启了出成绩提醒
+    pub has_credentials: bool,  // 是否已有可用于查询的账号, 开启提醒后需要登录查询一次
+    pub email: bool,    // 是否设置了邮件通知
+    pub interval_minutes: u64,
+    pub checks: u64,
+    pub last_checked_at: Option<DateTime<Local>>,
+    pub last_error: Option<String>,
+    pub last_changed_at: Option<DateTime<Local>>,
+    pub last_diff: Option<GradeDiff>,   // 只返回给监控账号自己的会话, 其他人只能看到是否有变化
+    pub next_check_at: Option<DateTime<Local>>,
+}
+
+pub struct GradeWatcher {
+    interval: Duration,
+    credentials: RwLock<Option<Credentials>>,
+    record: RwLock<WatchRecord>,
+    mailer: Option<EmailNotifier>,  // 没有设置 SMTP 时为空, 只弹出桌面通知
+}
+
+impl GradeWatcher {
+    pub fn new(interval: Duration, mailer: Option<EmailNotifier>) -> Self {
+        Self { interval, credentials: RwLock::new(None), record: RwLock::new(WatchRecord::default()), mailer }
+    }
+
+    /// 记住登录成功的账号, 之后的检查都使用该账号; 换了账号时之前的检查记录不再适用
+    pub fn remember(&self, account: &str, password: &str) {
+        let mut credentials = self.credentials.write().unwrap();
+        if credentials.as_ref().is_some_and(|c| c.account != account) {
+            *self.record.write().unwrap() = WatchRecord::default();
+        }
+        *credentials = Some(Credentials { account: account.to_string(), password: password.to_string() });
+    }
+
+    /// 忘记账号密码, 如关闭提醒时
+    pub fn forget(&self) {
+        if self.credentials.write().unwrap().take().is_some() {
+            print_info("后台成绩监控已清除保存的账号");
+        }
+    }
+
+    /// 正在监控的是否为给定的账号, 账号以 SnapshotStore::account_key 的哈希值给出
+    pub fn is_watching(&self, account_key: &str) -> bool {
+        self.credentials.read().unwrap().as_ref().is_some_and(|c| SnapshotStore::account_key(&c.account) == account_key)
+    }
+
+    /// 只忘记给定账号的密码, 如该账号注销会话时; 监控的是其他账号时不受影响
+    pub fn forget_account(&self, account_key: &str) {
+        let mut credentials = self.credentials.write().unwrap();
+        if credentials.as_ref().is_some_and(|c| SnapshotStore::account_key(&c.account) == account_key) {
+            *credentials = None;
+            print_info("后台成绩监控已清除保存的账号");
+        }
+    }
+
+    /// 监控状态, 只有监控账号自己的会话(viewer 为该账号的哈希值)能看到发现的具体成绩
+    pub fn status(&self, enabled: bool, viewer: Option<&str>) -> WatchStatus {
+        let record = self.record.read().unwrap().clone();
+        let owner = viewer.is_some_and(|key| self.is_watching(key));
+        WatchStatus {
+            enabled,
+            has_credentials: self.credentials.read().unwrap().is_some(),
+            email: self.mailer.is_some(),
+            interval_minutes: self.interval.as_secs() / 60,
+            checks: record.checks,
+            last_checked_at: record.last_checked_at,
+            last_error: record.last_error,
+            last_changed_at: record.last_changed_at,
+            last_diff: record.last_diff.filter(|_| owner),
+            next_check_at: record.next_check_at,
+        }
+    }
+
+    /// 按查询间隔循环检查, 收到关闭信号时退出; 没有开启提醒或没有账号时跳过本轮
+    pub async fn run(self: Arc<Self>, state: AppState, mut shutdown: broadcast::Receiver<()>) {
+        print_info(&format!("后台成绩监控已启动, 开启出成绩提醒后每 {} 分钟查询一次", self.interval.as_secs() / 60));
+
+        loop {
+            self.record.write().unwrap().next_check_at = TimeDelta::from_std(self.interval).ok().map(|delta| Local::now() + delta);
+            tokio::select! {
+                _ = sleep(self.interval) => {}
+                _ = shutdown.recv() => break
+            }
+
+            if !state.settings.get().watch_new_grades {
+                continue;
+            }
+            let Some(credentials) = self.credentials.read().unwrap().clone() else {
+                continue;
+            };
+
+            let result = self.check(&state, &credentials).await;
+
+            // 邮件发送需要等待, 先更新检查记录再发送
+            let detected = {
+                let mut record = self.record.write().unwrap();
+                record.checks += 1;
+                record.last_checked_at = Some(Local::now());
+                match result {
+                    Ok(Some((diff, gpas))) => {
+                        print_info(&format!("后台成绩监控: 新增 {} 门成绩, {} 门成绩有变动", diff.added.len(), diff.changed.len()));
+                        notify_grade_diff(&diff);
+                        record.last_error = None;
+                        record.last_changed_at = record.last_checked_at;
+                        record.last_diff = Some(diff.clone());
+                        Some((diff, gpas))
+                    }
+                    Ok(None) => {
+                        #[cfg(debug_assertions)]
+                        print_info("后台成绩监控: 成绩没有变化");
+
+                        record.last_error = None;
+                        None
+                    }
+                    Err(e) => {
+                        print_error(&format!("后台成绩监控查询失败: {}", e));
+                        record.last_error = Some(e.to_string());
+                        None
+                    }
+                }
+            };
+
+            if let (Some(mailer), Some((diff, gpas))) = (&self.mailer, detected) {
+                match mailer.send_grade_diff(&diff, &gpas).await {
+                    Ok(()) => print_info("后台成绩监控: 已发送邮件通知"),
+                    Err(e) => print_error(&format!("后台成绩监控: {:#}", e))
+                }
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        print_info("后台成绩监控已停止");
+    }
+
+    /// 查询一次成绩, 与上一份快照相比有变化时保存新快照并返回差异与各模式绩点的变化
+    /// 演示模式的模拟数据与登录查询一样不写入历史, 所以总是没有变化
+    async fn check(&self, state: &AppState, credentials: &Credentials) -> Result<Option<(GradeDiff, Vec<GpaChange>)>> {
+        let profile = state.profile.get();
+        let courses = if state.config.demo {
+            fetch_courses(&mut DemoSource::new(&profile.grading), credentials).await?
+        } else {
+            let mut scraper = AAOWebsite::new(&state.config.scraper, &profile.grading)?;
+            fetch_courses(&mut scraper, credentials).await?
+        };
+        if state.config.demo {
+            return Ok(None);
+        }
+
+        let key = SnapshotStore::account_key(&credentials.account);
+        // 数据库访问是阻塞调用, 放到阻塞线程中执行
+        let previous = {
+            let (snapshots, key) = (state.snapshots.clone(), key.clone());
+            tokio::task::spawn_blocking(move || snapshots.latest(&key)).await??
+        };
+        let diff = previous.as_ref().map(|(taken_at, previous)| GradeDiff::between(*taken_at, previous, &courses));
+
+        // 只在第一次查询或成绩有变化时保存快照, 否则每次轮询都会在成绩历史中多出一份相同的快照
+        if diff.as_ref().is_none_or(|diff| !diff.is_empty()) {
+            let (snapshots, courses) = (state.snapshots.clone(), courses.clone());
+            tokio::task::spawn_blocking(move || snapshots.save(&key, &courses)).await??;
+        }
+
+        let (Some((_, previous)), Some(diff)) = (previous, diff) else {
+            return Ok(None);
+        };
+        if diff.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some((diff, gpa_changes(&previous, &courses, &profile))))
+    }
+}
+
+/// 按当前的学校规则计算前后两份成绩各模式的绩点
+fn gpa_changes(previous: &[Course], current: &[Course], profile: &SchoolProfile) -> Vec<GpaChange> {
+    let before = process_scraped_course_results(previous, ResultSource::OfficialWebsite, profile);
+    let after = process_scraped_course_results(current, ResultSource::OfficialWebsite, profile);
+
+    let mut changes = Vec::new();
+    if let Some(default) = &after.default {
+        changes.push(GpaChange { label: mode_label("default"), before: before.default.map(|r| r.gpa), after: default.gpa });
+    }
+    changes.push(GpaChange {
+        label: mode_label("all"),
+        before: (!before.all.courses.is_empty()).then_some(before.all.gpa),
+        after: after.all.gpa,
+    });
+    changes
+}
+
+/// 登录并获取全部学期的成绩, 监控只关心主修成绩
+async fn fetch_courses(source: &mut impl GradeSource, credentials: &Credentials) -> Result<Vec<Course>> {
+    source.sign_in(&credentials.account, &credentials.password).await?;
+    Ok(source.fetch_grades(&[]).await?)
+}